@@ -0,0 +1,84 @@
+// times bvh construction and closest hit queries on the random scene and on a large triangle mesh,
+// split with the surface area heuristic against the median split as a baseline
+//
+//     cargo bench --bench bvh
+
+use std::time::Instant;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use beauty::builtin::Builtin;
+use beauty::bvh::SplitMethod;
+use beauty::mat::Lambertian;
+use beauty::ray::Ray;
+use beauty::sampler::Sampler;
+use beauty::texture::ConstantTexture;
+use beauty::triangle::TriangleMesh;
+use beauty::{Color, Hittable, Point3, Vec3};
+
+const RAYS: usize = 1_000_000;
+
+const METHODS: [(&str, SplitMethod); 2] = [("median", SplitMethod::Median), ("sah", SplitMethod::Sah)];
+
+// returns the time the rays took
+fn trace(name: &str, world: &dyn Hittable, rays: &[Ray], rng: &mut Sampler) -> f64 {
+    let start = Instant::now();
+    let hits = rays.iter().filter(|r| world.hit(r, 0.001, f64::INFINITY, rng).is_some()).count();
+    let seconds = start.elapsed().as_secs_f64();
+    println!("{:<14} {:>8.3} s  {:>6.2} Mrays/s  {} hits", name, seconds, rays.len() as f64 / seconds / 1.0e6, hits);
+    seconds
+}
+
+// a bumpy sphere of 2 * n * n triangles
+fn sphere_mesh(n: usize, split: SplitMethod) -> TriangleMesh {
+    let mut positions = Vec::with_capacity((n + 1) * (n + 1));
+    for i in 0..=n {
+        let theta = std::f64::consts::PI * i as f64 / n as f64;
+        for j in 0..=n {
+            let phi = 2.0 * std::f64::consts::PI * j as f64 / n as f64;
+            let r = 1.0 + 0.05 * (12.0 * theta).sin() * (9.0 * phi).cos();
+            positions.push(Point3::new(r * theta.sin() * phi.cos(), r * theta.cos(), r * theta.sin() * phi.sin()));
+        }
+    }
+    let mut indices = Vec::with_capacity(2 * n * n);
+    for i in 0..n {
+        for j in 0..n {
+            let a = i * (n + 1) + j;
+            let b = a + n + 1;
+            indices.push([a, b, a + 1]);
+            indices.push([a + 1, b, b + 1]);
+        }
+    }
+    TriangleMesh::with_split(positions, indices, None, None, Lambertian::new(ConstantTexture::new(Color::new(0.5, 0.5, 0.5))), split)
+}
+
+fn main() {
+    let mut seconds = Vec::new();
+    for (name, method) in METHODS {
+        // the same seed gives the same scene and rays for both methods
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut sampler = Sampler::new(1);
+        let start = Instant::now();
+        let scene = Builtin::Random.build(1.5, method, &mut rng);
+        println!("random scene split by {} built in {:.3} s", name, start.elapsed().as_secs_f64());
+        let rays: Vec<Ray> = (0..RAYS).map(|_| scene.camera.get_ray(rng.gen(), rng.gen(), &mut sampler)).collect();
+        seconds.push(trace(&format!("random {}", name), scene.world.as_ref(), &rays, &mut sampler));
+    }
+    println!("sah traces the random scene {:.2}x as fast as median\n", seconds[0] / seconds[1]);
+
+    // rays from all around the mesh aimed at points near its center
+    let mut rng = StdRng::seed_from_u64(1);
+    let mut sampler = Sampler::new(1);
+    let rays: Vec<Ray> = (0..RAYS).map(|_| {
+        let origin = 3.0 * Vec3::random_in_unit_sphere(&mut rng).normalized();
+        let target = 0.5 * Vec3::random_in_unit_sphere(&mut rng);
+        Ray::new(origin, target - origin, 0.0)
+    }).collect();
+    let mut seconds = Vec::new();
+    for (name, method) in METHODS {
+        let start = Instant::now();
+        let mesh = sphere_mesh(400, method);
+        println!("320000 triangle mesh split by {} built in {:.3} s", name, start.elapsed().as_secs_f64());
+        seconds.push(trace(&format!("mesh {}", name), &mesh, &rays, &mut sampler));
+    }
+    println!("sah traces the mesh {:.2}x as fast as median", seconds[0] / seconds[1]);
+}
@@ -2,6 +2,7 @@ use std::f64;
 use super::vec::Vec3;
 use super::ray::Ray;
 
+/// axis aligned bounding box
 #[derive(Clone, Copy)]
 pub struct AABB{
     pub min:Vec3,
@@ -34,6 +35,25 @@ impl AABB{
         }
         true
     }
+
+    /// the same test with the reciprocal of the ray direction computed once by the caller
+    pub fn hit_inverse(&self, origin: Vec3, inverse_direction: Vec3, mut t_in: f64, mut t_out: f64) -> bool {
+        for a in 0..3 {
+            let t0 = (self.min[a] - origin[a]) * inverse_direction[a];
+            let t1 = (self.max[a] - origin[a]) * inverse_direction[a];
+            let (t0, t1) = if inverse_direction[a] < 0.0 {
+                (t1, t0)
+            } else {
+                (t0, t1)
+            };
+            t_in = t_in.max(t0);
+            t_out = t_out.min(t1);
+            if t_out <= t_in {
+                return false
+            }
+        }
+        true
+    }
 }
 
 /// merge 2 AABB into 1
@@ -0,0 +1,204 @@
+use super::vec::{Vec3, Point3};
+use super::ray::Ray;
+use super::sampler::Sampler;
+use super::hit::{Hittable, HitRecord};
+use super::mat4::Mat4;
+use super::quat::Quat;
+use super::aabb::{self, AABB};
+
+// samples per keyframe interval when bounding the motion
+const BOUND_STEPS: usize = 16;
+
+/// pose of an animated object at one instant, applied as scale, then rotation, then translation
+#[derive(Clone, Copy)]
+pub struct Keyframe {
+    time: f64,
+    translation: Vec3,
+    rotation: Quat,
+    scale: Vec3
+}
+
+impl Keyframe {
+    /// the identity pose at `time`
+    pub fn new(time: f64) -> Keyframe {
+        Keyframe {
+            time,
+            translation: Vec3::new(0.0, 0.0, 0.0),
+            rotation: Quat::identity(),
+            scale: Vec3::new(1.0, 1.0, 1.0)
+        }
+    }
+
+    pub fn with_translation(mut self, offset: Vec3) -> Keyframe {
+        self.translation = offset;
+        self
+    }
+
+    /// rotation around an axis through the origin, the angle is in degrees
+    pub fn with_rotation(mut self, axis: Vec3, angle: f64) -> Keyframe {
+        self.rotation = Quat::from_axis_angle(axis, angle);
+        self
+    }
+
+    pub fn with_scale(mut self, factors: Vec3) -> Keyframe {
+        self.scale = factors;
+        self
+    }
+
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    fn matrix(&self) -> Mat4 {
+        Mat4::translation(self.translation) * self.rotation.to_mat4() * Mat4::scaling(self.scale)
+    }
+
+    // translation and scale move linearly, the rotation at constant angular speed
+    fn lerp(&self, other: &Keyframe, t: f64) -> Keyframe {
+        Keyframe {
+            time: (1.0 - t) * self.time + t * other.time,
+            translation: (1.0 - t) * self.translation + t * other.translation,
+            rotation: self.rotation.slerp(other.rotation, t),
+            scale: (1.0 - t) * self.scale + t * other.scale
+        }
+    }
+}
+
+/// an object moving through keyframed poses, evaluated at the time of each ray for motion blur
+pub struct Animated<H: Hittable> {
+    hittable: H,
+    keyframes: Vec<Keyframe>
+}
+
+impl<H: Hittable> Animated<H> {
+    /// keyframes can come in any order, before the first and after the last the pose is held
+    pub fn new(hittable: H, mut keyframes: Vec<Keyframe>) -> Animated<H> {
+        assert!(!keyframes.is_empty(), "an animation needs at least one keyframe");
+        assert!(keyframes.iter().all(|k| (0..3).all(|a| k.scale[a] != 0.0)), "keyframe scale factors must not be zero");
+        keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+        Animated { hittable, keyframes }
+    }
+
+    fn pose(&self, time: f64) -> Keyframe {
+        let next = self.keyframes.partition_point(|k| k.time <= time);
+        if next == 0 {
+            return self.keyframes[0]
+        }
+        if next == self.keyframes.len() {
+            return self.keyframes[next - 1]
+        }
+        let (a, b) = (&self.keyframes[next - 1], &self.keyframes[next]);
+        a.lerp(b, (time - a.time) / (b.time - a.time))
+    }
+}
+
+impl<H: Hittable> Hittable for Animated<H> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rng: &mut Sampler) -> Option<HitRecord<'_>> {
+        let pose = self.pose(r.time());
+        let rotation = pose.rotation.to_mat4();
+        // the inverse of a rotation is its transpose
+        let inverse_rotation = rotation.transpose();
+        let inverse_scale = Vec3::new(1.0 / pose.scale.x(), 1.0 / pose.scale.y(), 1.0 / pose.scale.z());
+
+        let object_ray = Ray::new(inverse_scale * inverse_rotation.transform_vector(r.origin() - pose.translation),
+                                  inverse_scale * inverse_rotation.transform_vector(r.direction()),
+                                  r.time());
+        let mut rec = self.hittable.hit(&object_ray, t_min, t_max, rng)?;
+
+        rec.position = rotation.transform_vector(pose.scale * rec.position) + pose.translation;
+        rec.normal = rotation.transform_vector(inverse_scale * rec.normal).normalized();
+        rec.tangent = rotation.transform_vector(pose.scale * rec.tangent);
+        Some(rec)
+    }
+
+    /// encloses every pose in [t0, t1], not just the ones at the ends
+    fn bounding_box(&self, t0: f64, t1: f64) -> Option<AABB> {
+        let object_box = self.hittable.bounding_box(t0, t1)?;
+        let corners: Vec<Point3> = (0..8).map(|corner| Point3::new(
+            if corner & 1 == 0 { object_box.min.x() } else { object_box.max.x() },
+            if corner & 2 == 0 { object_box.min.y() } else { object_box.max.y() },
+            if corner & 4 == 0 { object_box.min.z() } else { object_box.max.z() }
+        )).collect();
+
+        let mut bbox = aabb::surrounding_box(&self.pose(t0).matrix().transform_box(&object_box),
+                                             &self.pose(t1).matrix().transform_box(&object_box));
+        for pair in self.keyframes.windows(2) {
+            let (a, b) = (&pair[0], &pair[1]);
+            let (lo, hi) = (a.time.max(t0), b.time.min(t1));
+            if lo >= hi {
+                continue
+            }
+            let duration = b.time - a.time;
+            let start = a.lerp(b, (lo - a.time) / duration);
+            let end = a.lerp(b, (hi - a.time) / duration);
+
+            let mut segment = start.matrix().transform_box(&object_box);
+            for i in 1..=BOUND_STEPS {
+                let pose = start.lerp(&end, i as f64 / BOUND_STEPS as f64);
+                segment = aabb::surrounding_box(&segment, &pose.matrix().transform_box(&object_box));
+            }
+
+            // between two samples a corner strays from the straight line joining them by at most
+            // step^2 / 8 times its largest second derivative, which the turning and the scaling bound
+            let step_angle = a.rotation.angle_to(b.rotation) * (hi - lo) / duration / BOUND_STEPS as f64;
+            let (radius, stretch) = corners.iter().fold((0.0f64, 0.0f64), |(radius, stretch), &c| (
+                radius.max((start.scale * c).length()).max((end.scale * c).length()),
+                stretch.max(((end.scale - start.scale) * c).length())
+            ));
+            let pad = step_angle * step_angle / 8.0 * radius + step_angle / 4.0 * stretch / BOUND_STEPS as f64;
+            let pad = Vec3::new(pad, pad, pad);
+            bbox = aabb::surrounding_box(&bbox, &AABB::new(segment.min - pad, segment.max + pad));
+        }
+        Some(bbox)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sphere::Sphere;
+    use crate::test_util::{grey, Grey};
+    use super::*;
+
+    // a ball beside the z axis, turning a quarter around it while moving along x
+    fn orbit() -> Animated<Sphere<Grey>> {
+        Animated::new(Sphere::new(Point3::new(1.0, 0.0, 0.0), 0.5, grey()), vec![
+            Keyframe::new(1.0).with_rotation(Vec3::new(0.0, 0.0, 1.0), 90.0).with_translation(Vec3::new(4.0, 0.0, 0.0)),
+            Keyframe::new(0.0)
+        ])
+    }
+
+    // where the center of the ball is at `time`
+    fn center(time: f64) -> Point3 {
+        let angle = (90.0 * time.clamp(0.0, 1.0)).to_radians();
+        Point3::new(angle.cos() + 4.0 * time.clamp(0.0, 1.0), angle.sin(), 0.0)
+    }
+
+    #[test]
+    fn hit_follows_the_keyframes() {
+        let orbit = orbit();
+        for time in [-1.0, 0.0, 0.3, 0.5, 1.0, 2.0] {
+            let c = center(time);
+            let r = Ray::new(c + Vec3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0), time);
+            let rec = orbit.hit(&r, 0.001, f64::INFINITY, &mut Sampler::new(0)).unwrap();
+            assert!((rec.position - (c + Vec3::new(0.0, 0.0, 0.5))).length() < 1e-9, "at time {}", time);
+            assert!((rec.normal - Vec3::new(0.0, 0.0, 1.0)).length() < 1e-9);
+        }
+        // where the ball was at the start is empty at the end
+        let r = Ray::new(center(0.0) + Vec3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0), 1.0);
+        assert!(orbit.hit(&r, 0.001, f64::INFINITY, &mut Sampler::new(0)).is_none());
+    }
+
+    #[test]
+    fn box_holds_every_pose() {
+        let orbit = orbit();
+        for (t0, t1) in [(0.0, 1.0), (0.2, 0.6), (-0.5, 0.5)] {
+            let bbox = orbit.bounding_box(t0, t1).unwrap();
+            for i in 0..=20 {
+                let c = center(t0 + (t1 - t0) * i as f64 / 20.0);
+                for a in 0..3 {
+                    assert!(bbox.min[a] <= c[a] - 0.5 && c[a] + 0.5 <= bbox.max[a], "shutter {} to {}", t0, t1);
+                }
+            }
+        }
+    }
+}
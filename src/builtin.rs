@@ -0,0 +1,427 @@
+use rand::Rng;
+use rand::rngs::StdRng;
+use super::vec::{Vec3, Point3, Color};
+use super::translate::Translate;
+use super::rotate::{Axis, Rotate};
+use super::hit::{Hittable, HittableList, FlipNormal};
+use super::sphere::{Sphere, MovingSphere};
+use super::rect::{Plane, AARect};
+use super::cube::Cube;
+use super::camera::Camera;
+use super::mat::{Lambertian, Metal, Dielectric, DiffuseLight};
+use super::bvh::{BVH, BVHStats, SplitMethod};
+use super::texture::{ConstantTexture, CheckTexture, NoiseTexture, ImageTexture};
+use super::medium::ConstantMedium;
+use super::scene::Scene;
+
+fn random_scene(rng: &mut StdRng, split: SplitMethod, stats: &mut Vec<BVHStats>) -> (Box<dyn Hittable>, HittableList) {
+    let mut world: Vec<Box<dyn Hittable>> = Vec::new();
+
+    let ground_mat = Lambertian::new(CheckTexture::new(ConstantTexture::new(Color::new(1.0, 1.0, 1.0)), ConstantTexture::new(Color::new(0.3, 0.3, 1.0))));
+    let ground_sphere = Sphere::new(Point3::new(0.0, -1000.0, 0.0), 1000.0, ground_mat);
+
+    world.push(Box::new(ground_sphere));
+
+    for a in -11..=11 {
+        for b in -11..=11 {
+            let choose_mat: f64 = rng.gen();
+            let center = Point3::new((a as f64) + rng.gen_range(0.0..0.9),
+                                     0.2,
+                                     (b as f64) + rng.gen_range(0.0..0.9));
+
+            if choose_mat < 0.8 {
+                // Diffuse
+                let albedo = Color::new(rng.gen(), rng.gen(), rng.gen()) * Color::new(rng.gen(), rng.gen(), rng.gen());
+                let sphere_mat = Lambertian::new(ConstantTexture::new(albedo));
+                let center1 = center + Vec3::new(0.0, rng.gen_range(0.0..0.01), 0.0);
+                let sphere = MovingSphere::new(center, center1, 0.0, 1.0, 0.2 ,sphere_mat);
+
+                world.push(Box::new(sphere));
+            } else if choose_mat < 0.95 {
+                // Metal
+                let albedo = Color::new(rng.gen_range(0.4..1.0), rng.gen_range(0.4..1.0), rng.gen_range(0.4..1.0));
+                let fuzz = rng.gen_range(0.0..0.5);
+                let sphere_mat = Metal::new(albedo, fuzz);
+                let sphere = Sphere::new(center, 0.2, sphere_mat);
+
+                world.push(Box::new(sphere));
+            } else {
+                // Glass
+                let sphere_mat = Dielectric::new(1.5);
+                let sphere = Sphere::new(center, 0.2, sphere_mat);
+
+                world.push(Box::new(sphere));
+            }
+        }
+    }
+
+    let mat1 = Dielectric::new(1.5);
+    let mat2 = Lambertian::new(ConstantTexture::new(Color::new(0.4, 0.2, 0.1)));
+    let mat3 = Metal::new(Color::new(0.7, 0.6, 0.5), 0.0);
+
+    let sphere1 = Sphere::new(Point3::new(0.0, 1.0, 0.0), 1.0, mat1);
+    let sphere2 = Sphere::new(Point3::new(-4.0, 1.0, 0.0), 1.0, mat2);
+    let sphere3 = Sphere::new(Point3::new(4.0, 1.0, 0.0), 1.0, mat3);
+
+    world.push(Box::new(sphere1));
+    world.push(Box::new(sphere2));
+    world.push(Box::new(sphere3));
+
+    let mut lights = HittableList::default();
+
+    let bvh = BVH::with_split(world, 0.0, 1.0, split);
+    stats.push(bvh.stats());
+    ( Box::new(bvh), lights)
+}
+
+fn two_spehre() -> (Box<dyn Hittable>, HittableList) {
+    let mut world = HittableList::default();
+
+    let top_mat = Lambertian::new(CheckTexture::new(ConstantTexture::new(Color::new(1.0, 1.0, 1.0)), ConstantTexture::new(Color::new(0.3, 0.3, 1.0))));
+    let bottom_mat = Lambertian::new(CheckTexture::new(ConstantTexture::new(Color::new(1.0, 1.0, 1.0)), ConstantTexture::new(Color::new(0.3, 0.3, 1.0))));
+
+    let top_sphere = Sphere::new(Point3::new(0.0, 10.0, 0.0), 10.0, top_mat);
+    let bottom_sphere = Sphere::new(Point3::new(0.0, -10.0, 0.0), 10.0, bottom_mat);
+
+    world.push(top_sphere);
+    world.push(bottom_sphere);
+
+    let mut lights = HittableList::default();
+
+    (Box::new(world), lights)
+}
+
+fn two_perlin_sphere(rng: &mut StdRng) -> (Box<dyn Hittable>, HittableList) {
+    let mut world = HittableList::default();
+
+    let top_mat = Lambertian::new(NoiseTexture::new(2.0, rng));
+    let bottom_mat = Lambertian::new(NoiseTexture::new(2.0, rng));
+
+    //hash goes wrong in negative field, move object to Fitst Quadrant for now
+    let top_sphere = Sphere::new(Point3::new(1000.0, 2.0, 1000.0), 2.0, top_mat);
+    let bottom_sphere = Sphere::new(Point3::new(1000.0, -1000.0, 1000.0), 1000.0, bottom_mat);
+
+    world.push(top_sphere);
+    world.push(bottom_sphere);
+
+    let mut lights = HittableList::default();
+
+    (Box::new(world), lights)
+}
+
+fn earth() -> (Box<dyn Hittable>, HittableList) {
+    let image = image::open("earthmap.jpg").expect("image not found").to_rgb8();
+    let (width ,height) = image.dimensions();
+    let data = image.into_raw();
+    let texture = ImageTexture::new(data, width, height);
+    let earth = Sphere::new(Vec3::new(0.0, 0.0, 0.0), 2.0, Lambertian::new(texture));
+    let mut lights = HittableList::default();
+    (Box::new(earth), lights)
+}
+
+fn light_room() -> (Box<dyn Hittable>, HittableList) {
+    let mut world = HittableList::default();
+
+    let bottom_mat = Lambertian::new(ConstantTexture::new(Color::new(0.7, 0.7, 0.7)));
+    let top_mat = Lambertian::new(ConstantTexture::new(Color::new(0.0, 0.1843, 0.6549)));
+    let emitted = DiffuseLight::new(ConstantTexture::new(Color::new(4.0, 4.0, 4.0)));
+    
+    let ground = Sphere::new(Point3::new(0.0, -1000.0, 0.0), 1000.0, bottom_mat);
+    let sphere = Sphere::new(Point3::new(0.0, 2.0, 0.0), 2.0, top_mat);
+    let plane = AARect::new(Plane::XY, 3.0, 5.0, 1.0, 3.0, -2.0, emitted);
+
+    world.push(ground);
+    world.push(sphere);
+    world.push(plane.clone());
+
+    let mut lights = HittableList::default();
+    lights.push(plane);
+
+    (Box::new(world), lights)
+}
+
+fn cornell_box() -> (Box<dyn Hittable>, HittableList) {
+    let mut world = HittableList::default();
+    let mut lights = HittableList::default();
+
+    let red = Lambertian::new(ConstantTexture::new(Color::new(0.65, 0.05, 0.05)));
+    let white = Lambertian::new(ConstantTexture::new(Color::new(0.73, 0.73, 0.73)));
+    let green = Lambertian::new(ConstantTexture::new(Color::new(0.12, 0.45, 0.15)));
+    let dielectric = Dielectric::new(1.5);
+    let metal = Metal::new(Color::new(0.8, 0.85, 0.88), 0.0);
+    let light = DiffuseLight::new(ConstantTexture::new(Color::new(15.0, 15.0, 15.0)));
+
+    let rect_light = FlipNormal::new(AARect::new(Plane::XZ, 213.0, 343.0, 227.0, 332.0, 554.0, light));
+
+    world.push(AARect::new(Plane::YZ, 0.0, 555.0, 0.0, 555.0, 555.0, green));
+    world.push(AARect::new(Plane::YZ, 0.0, 555.0, 0.0, 555.0, 0.0, red));
+    world.push(rect_light.clone());
+    world.push(AARect::new(Plane::XZ, 0.0, 555.0, 0.0, 555.0, 0.0, white.clone()));
+    world.push(AARect::new(Plane::XZ, 0.0, 555.0, 0.0, 555.0, 555.0, white.clone()));
+    world.push(AARect::new(Plane::XY, 0.0, 555.0, 0.0, 555.0, 555.0, white.clone()));
+
+    world.push(Sphere::new(Point3::new(190.0, 90.0, 190.0), 90.0, dielectric));
+    // world.push(
+    //     Translate::new(
+    //         Rotate::new(Axis::Y,
+    //                     Cube::new(Point3::new(0.0, 0.0, 0.0), Point3::new(165.0, 165.0, 165.0), white),-18.0), Vec3::new(130.0, 0.0, 65.0)));
+    world.push(
+        Translate::new(
+            Rotate::new(Axis::Y,
+                        Cube::new(Point3::new(0.0, 0.0, 0.0), Point3::new(165.0, 330.0, 165.0), white),15.0), Vec3::new(265.0, 0.0, 295.0)));
+
+    lights.push(rect_light);
+
+    (Box::new(world), lights)
+}
+
+fn cornell_box_with_smoke() -> (Box<dyn Hittable>, HittableList) {
+    let mut world = HittableList::default();
+    let mut lights = HittableList::default();
+
+    let red = Lambertian::new(ConstantTexture::new(Color::new(0.65, 0.05, 0.05)));
+    let white = Lambertian::new(ConstantTexture::new(Color::new(0.73, 0.73, 0.73)));
+    let green = Lambertian::new(ConstantTexture::new(Color::new(0.12, 0.45, 0.15)));
+    let light = DiffuseLight::new(ConstantTexture::new(Color::new(15.0, 15.0, 15.0)));
+
+    let rect_light = FlipNormal::new(AARect::new(Plane::XZ, 213.0, 343.0, 227.0, 332.0, 554.0, light));
+
+    world.push(AARect::new(Plane::YZ, 0.0, 555.0, 0.0, 555.0, 555.0, green));
+    world.push(AARect::new(Plane::YZ, 0.0, 555.0, 0.0, 555.0, 0.0, red));
+    world.push(rect_light.clone());
+    world.push(AARect::new(Plane::XZ, 0.0, 555.0, 0.0, 555.0, 0.0, white.clone()));
+    world.push(AARect::new(Plane::XZ, 0.0, 555.0, 0.0, 555.0, 555.0, white.clone()));
+    world.push(AARect::new(Plane::XY, 0.0, 555.0, 0.0, 555.0, 555.0, white.clone()));
+
+    let box1 = 
+        Translate::new(
+            Rotate::new(Axis::Y,
+                        Cube::new(Point3::new(0.0, 0.0, 0.0), Point3::new(165.0, 165.0, 165.0), white.clone()),-18.0), Vec3::new(130.0, 0.0, 65.0));
+    let box2 =
+        Translate::new(
+            Rotate::new(Axis::Y,
+                        Cube::new(Point3::new(0.0, 0.0, 0.0), Point3::new(165.0, 330.0, 165.0), white),15.0), Vec3::new(265.0, 0.0, 295.0));
+
+    world.push(ConstantMedium::new(box1, 0.01, ConstantTexture::new(Color::new(1.0, 1.0, 1.0))));
+    world.push(ConstantMedium::new(box2, 0.01, ConstantTexture::new(Color::new(0.0, 0.0, 0.0))));
+
+    lights.push(rect_light);
+
+    (Box::new(world), lights)
+}
+
+fn final_scene(rng: &mut StdRng, split: SplitMethod, stats: &mut Vec<BVHStats>) -> (Box<dyn Hittable>, HittableList) {
+    let mut world = HittableList::default();
+    let mut lights = HittableList::default();
+
+    let ground = Lambertian::new(ConstantTexture::new(Color::new(0.48, 0.83, 0.53)));
+    let mut box_list1: Vec<Box<dyn Hittable>> = Vec::new();
+    let boxes_per_side = 20;
+    for i in 0..boxes_per_side {
+        for j in 0..boxes_per_side {
+            let w = 100.0;
+            let x0 = -1000.0 + i as f64 * w;
+            let z0 = -1000.0 + j as f64 * w;
+            let y0 = 0.0;
+            let x1 = x0 + w;
+            let y1 = 100.0 * (rng.gen::<f64>() + 0.01);
+            let z1 = z0 + w;
+            box_list1.push(Box::new(Cube::new(Point3::new(x0, y0, z0), Point3::new(x1, y1, z1), ground.clone())));
+        }
+    }
+    let ground_boxes = BVH::with_split(box_list1, 0.0, 1.0, split);
+    stats.push(ground_boxes.stats());
+    world.push(ground_boxes);
+
+    let light = DiffuseLight::new(ConstantTexture::new(Color::new(7.0, 7.0, 7.0)));
+    let rect_light = FlipNormal::new(AARect::new(Plane::XZ, 147.0, 412.0, 123.0, 423.0, 554.0, light));
+    world.push(rect_light.clone());
+
+    let center = Point3::new(400.0, 400.0, 200.0);
+    world.push(MovingSphere::new(center, center + Point3::new(30.0, 0.0, 0.0), 0.0, 1.0, 50.0, Lambertian::new(ConstantTexture::new(Color::new(0.7, 0.3, 0.1)))));
+    world.push(Sphere::new(Point3::new(260.0, 150.0, 45.0), 50.0, Dielectric::new(1.5)));
+    world.push(Sphere::new(Point3::new(0.0, 150.0, 145.0), 50.0, Metal::new(Color::new(0.8, 0.8, 0.9), 1.0)));
+
+    let boundary = Sphere::new(Point3::new(360.0, 150.0, 145.0), 70.0, Dielectric::new(1.5));
+    world.push(boundary.clone());
+    world.push(ConstantMedium::new(boundary, 0.2, ConstantTexture::new(Color::new(0.2, 0.4, 0.9))));
+
+    let boundary = Sphere::new(Point3::new(0.0, 0.0, 0.0), 5000.0, Dielectric::new(1.5));
+    world.push(ConstantMedium::new(boundary, 0.0001, ConstantTexture::new(Color::new(1.0, 1.0, 1.0))));
+
+    let image = image::open("earthmap.jpg").expect("image not found").to_rgb8();
+    let (nx, ny) = image.dimensions();
+    let data = image.into_raw();
+    let texture = ImageTexture::new(data, nx, ny);
+    world.push(Sphere::new(Point3::new(400.0, 200.0, 400.0), 100.0, Lambertian::new(texture)));
+    world.push(Sphere::new(Point3::new(220.0, 280.0, 300.0), 80.0, Lambertian::new(NoiseTexture::new(0.1, rng))));
+
+    let white = Lambertian::new(ConstantTexture::new(Color::new(0.73, 0.73, 0.73)));
+    let mut box_list2: Vec<Box<dyn Hittable>> = Vec::new();
+    let ns = 1000;
+    for _ in 0..ns {
+        box_list2.push(Box::new(Sphere::new(Point3::new(165.0 * rng.gen::<f64>(), 165.0 * rng.gen::<f64>(), 165.0 * rng.gen::<f64>()), 10.0, white.clone())));
+    }
+    let spheres = BVH::with_split(box_list2, 0.0, 0.1, split);
+    stats.push(spheres.stats());
+    world.push(
+        Translate::new(
+            Rotate::new(Axis::Y, spheres, 15.0),
+                Point3::new(-100.0, 270.0, 395.0))
+    );
+
+    lights.push(rect_light);
+
+    (Box::new(world), lights)
+}
+
+/// the scenes that come with the renderer
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Builtin {
+    Random,
+    TwoSphere,
+    TwoPerlinSphere,
+    Earth,
+    LightRoom,
+    CornellBox,
+    CornellSmoke,
+    FinalScene
+}
+
+impl Builtin {
+    pub const NAMES: [&'static str; 8] = ["random", "two-sphere", "two-perlin-sphere", "earth", "light-room", "cornell-box", "cornell-smoke", "final"];
+
+    pub fn from_name(name: &str) -> Option<Builtin> {
+        match name {
+            "random" => Some(Builtin::Random),
+            "two-sphere" => Some(Builtin::TwoSphere),
+            "two-perlin-sphere" => Some(Builtin::TwoPerlinSphere),
+            "earth" => Some(Builtin::Earth),
+            "light-room" => Some(Builtin::LightRoom),
+            "cornell-box" => Some(Builtin::CornellBox),
+            "cornell-smoke" => Some(Builtin::CornellSmoke),
+            "final" => Some(Builtin::FinalScene),
+            _ => None
+        }
+    }
+
+    /// build the scene, its bvhs are split with `split`, `rng` drives the layout of the randomly
+    /// generated ones and the noise textures
+    pub fn build(self, aspect_ratio: f64, split: SplitMethod, rng: &mut StdRng) -> Scene {
+        let mut bvh_stats = Vec::new();
+        match self {
+            Builtin::Random => {
+                let (world, lights) = random_scene(rng, split, &mut bvh_stats);
+
+                let backgournd = Color::new(0.7, 0.8, 1.0);
+
+                let lookfrom = Point3::new(13.0, 2.0, 3.0);
+                let lookat = Point3::new(0.0, 0.0, 0.0);
+                let vup = Vec3::new(0.0, 1.0, 0.0);
+                let dist_to_focus = 10.0;
+                let aperture = 0.1;
+                let camera = Camera::new(lookfrom, lookat, vup, 20.0, aspect_ratio, aperture, dist_to_focus, 0.0, 1.0);
+
+                Scene { world, lights, background: backgournd, camera, bvh_stats }
+            }
+            Builtin::TwoSphere =>{
+                let (world, lights) = two_spehre();
+
+                let backgournd = Color::new(0.7, 0.8, 1.0);
+
+                let lookfrom = Point3::new(13.0, 2.0, 3.0);
+                let lookat = Point3::new(0.0, 0.0, 0.0);
+                let vup = Vec3::new(0.0, 1.0, 0.0);
+                let dist_to_focus = 10.0;
+                let aperture = 0.0;
+                let camera = Camera::new(lookfrom, lookat, vup, 20.0, aspect_ratio, aperture, dist_to_focus, 0.0, 1.0);
+
+                Scene { world, lights, background: backgournd, camera, bvh_stats }
+            }
+            Builtin::TwoPerlinSphere => {
+                let (world, lights) = two_perlin_sphere(rng);
+
+                let backgournd = Color::new(0.7, 0.8, 1.0);
+
+                let lookfrom = Point3::new(1013.0, 2.0, 1003.0);
+                let lookat = Point3::new(1000.0, 0.0, 1000.0);
+                let vup = Vec3::new(0.0, 1.0, 0.0);
+                let dist_to_focus = 10.0;
+                let aperture = 0.0;
+                let camera = Camera::new(lookfrom, lookat, vup, 20.0, aspect_ratio, aperture, dist_to_focus, 0.0, 1.0);
+
+                Scene { world, lights, background: backgournd, camera, bvh_stats }
+            }
+            Builtin::Earth => {
+                let (world, lights) = earth();
+
+                let backgournd = Color::new(0.7, 0.8, 1.0);
+
+                let lookfrom = Point3::new(13.0, 2.0, 3.0);
+                let lookat = Point3::new(0.0, 0.0, 0.0);
+                let vup = Vec3::new(0.0, 1.0, 0.0);
+                let dist_to_focus = 10.0;
+                let aperture = 0.1;
+                let camera = Camera::new(lookfrom, lookat, vup, 20.0, aspect_ratio, aperture, dist_to_focus, 0.0, 1.0);
+
+                Scene { world, lights, background: backgournd, camera, bvh_stats }
+            }
+            Builtin::LightRoom => {
+                let (world, lights) = light_room();
+
+                let backgournd = Color::new(0.0, 0.0, 0.0);
+
+                let lookfrom = Point3::new(26.0, 3.0, 6.0);
+                let lookat = Point3::new(0.0, 2.0, 0.0);
+                let vup = Vec3::new(0.0, 1.0, 0.0);
+                let dist_to_focus = 10.0;
+                let aperture = 0.0;
+                let camera = Camera::new(lookfrom, lookat, vup, 20.0, aspect_ratio, aperture, dist_to_focus, 0.0, 1.0);
+
+                Scene { world, lights, background: backgournd, camera, bvh_stats }
+            }
+            Builtin::CornellBox => {
+                let (world, lights) = cornell_box();
+
+                let backgournd = Color::new(0.0, 0.0, 0.0);
+
+                let lookfrom = Point3::new(278.0, 278.0, -800.0);
+                let lookat = Point3::new(278.0, 278.0, 0.0);
+                let vup = Vec3::new(0.0, 1.0, 0.0);
+                let dist_to_focus = 10.0;
+                let aperture = 0.05;
+                let camera = Camera::new(lookfrom, lookat, vup, 40.0, aspect_ratio, aperture, dist_to_focus, 0.0, 1.0);
+
+                Scene { world, lights, background: backgournd, camera, bvh_stats }
+            },
+            Builtin::CornellSmoke => {
+                let (world, lights) = cornell_box_with_smoke();
+
+                let backgournd = Color::new(0.0, 0.0, 0.0);
+
+                let lookfrom = Point3::new(278.0, 278.0, -800.0);
+                let lookat = Point3::new(278.0, 278.0, 0.0);
+                let vup = Vec3::new(0.0, 1.0, 0.0);
+                let dist_to_focus = 10.0;
+                let aperture = 0.05;
+                let camera = Camera::new(lookfrom, lookat, vup, 40.0, aspect_ratio, aperture, dist_to_focus, 0.0, 1.0);
+
+                Scene { world, lights, background: backgournd, camera, bvh_stats }
+            }
+            Builtin::FinalScene => {
+                let (world, lights) = final_scene(rng, split, &mut bvh_stats);
+
+                let backgournd = Color::new(0.0, 0.0, 0.0);
+
+                let lookfrom = Point3::new(478.0, 278.0, -600.0);
+                let lookat = Point3::new(278.0, 278.0, 0.0);
+                let vup = Vec3::new(0.0, 1.0, 0.0);
+                let dist_to_focus = 10.0;
+                let aperture = 0.01;
+                let camera = Camera::new(lookfrom, lookat, vup, 40.0, aspect_ratio, aperture, dist_to_focus, 0.0, 1.0);
+
+                Scene { world, lights, background: backgournd, camera, bvh_stats }
+            }
+        }
+    }
+}
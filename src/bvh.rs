@@ -1,96 +1,603 @@
 use std::cmp::Ordering;
+use std::fmt;
 use super::aabb;
 use super::aabb::AABB;
+use super::sampler::Sampler;
 use super::hit::{Hittable, HitRecord};
 use super::ray::Ray;
+use super::vec::Vec3;
 
-enum BVHNode {
-    Branch { left: Box<BVH>, right: Box<BVH> },
-    Leaf(Box<dyn Hittable>)
+// costs of the surface area heuristic, relative to intersecting one object
+const TRAVERSAL_COST: f64 = 0.125;
+const INTERSECTION_COST: f64 = 1.0;
+const BINS: usize = 16;
+// the heuristic may keep up to this many objects in one leaf
+const MAX_LEAF_SIZE: usize = 4;
+// also the size of the traversal stack
+const MAX_DEPTH: usize = 64;
+// refit rebuilds the tree once its sah cost exceeds the cost it had when it was built by this factor
+const REFIT_TOLERANCE: f64 = 1.5;
+
+/// how a bvh node divides its objects between the two children
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SplitMethod {
+    /// binned surface area heuristic
+    Sah,
+    /// sort along the widest axis and cut the list in half
+    Median
 }
 
-pub struct BVH {
-    tree: BVHNode,
-    bbox: AABB
+impl SplitMethod {
+    pub fn from_name(name: &str) -> Option<SplitMethod> {
+        match name.to_ascii_lowercase().as_str() {
+            "sah" => Some(SplitMethod::Sah),
+            "median" => Some(SplitMethod::Median),
+            _ => None
+        }
+    }
 }
 
-impl BVH {
-    pub fn new(mut hit: Vec<Box<dyn Hittable>>, time0: f64, time1: f64) -> BVH {
-        fn box_compare(time0: f64, time1: f64, axis: usize) -> impl FnMut(&Box<dyn Hittable>, &Box<dyn Hittable>) -> Ordering {
-            move |a, b| {
-                let a_bbox = a.bounding_box(time0, time1);
-                let b_bbox = b.bounding_box(time0, time1);
-                if let (Some(a), Some(b)) = (a_bbox, b_bbox) {
-                    let ac = a.min[axis] + a.max[axis];
-                    let bc = b.min[axis] + b.max[axis];
-                    ac.partial_cmp(&bc).unwrap()
-                } else {
-                    panic!("no bounding box in bvh node")
+fn surface_area(b: &AABB) -> f64 {
+    let d = b.max - b.min;
+    2.0 * (d.x() * d.y() + d.y() * d.z() + d.z() * d.x())
+}
+
+fn centroid(b: &AABB, axis: usize) -> f64 {
+    0.5 * (b.min[axis] + b.max[axis])
+}
+
+/// shape of a bvh, to compare split methods
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BVHStats {
+    pub nodes: usize,
+    pub leaves: usize,
+    /// the longest path from the root to a leaf, a single leaf has depth 1
+    pub depth: usize,
+    /// expected cost of a ray hitting the root, lower is better
+    pub sah_cost: f64
+}
+
+impl fmt::Display for BVHStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} nodes, {} leaves, depth {}, sah cost {:.2}", self.nodes, self.leaves, self.depth, self.sah_cost)
+    }
+}
+
+// the first child of an interior node directly follows it in the array
+#[derive(Clone, Copy)]
+enum NodeKind {
+    Interior { second: usize, axis: usize },
+    Leaf { first: usize, count: usize }
+}
+
+#[derive(Clone, Copy)]
+struct LinearNode {
+    bbox: AABB,
+    kind: NodeKind
+}
+
+
+/// the node array of a bvh, over primitives known only by their boxes
+///
+/// the tree keeps the primitives in the order `build` returns, a leaf holds a range of that
+/// order, and the owner tests the primitives of the leaves the ray reaches in `traverse`
+pub(crate) struct LinearTree {
+    nodes: Vec<LinearNode>
+}
+
+impl LinearTree {
+    /// builds the tree over the boxes, returning it with the primitives in the order of the leaves
+    pub(crate) fn build(boxes: &[AABB], method: SplitMethod) -> (LinearTree, Vec<usize>) {
+        assert!(!boxes.is_empty(), "no primitive in bvh");
+        let mut items: Vec<(usize, AABB)> = boxes.iter().copied().enumerate().collect();
+        let mut nodes = Vec::with_capacity(2 * items.len());
+        LinearTree::build_node(&mut nodes, &mut items, 0, 1, method);
+        (LinearTree { nodes }, items.into_iter().map(|(i, _)| i).collect())
+    }
+
+    // appends the subtree over `items`, which start at `offset` in the final order
+    fn build_node(nodes: &mut Vec<LinearNode>, items: &mut [(usize, AABB)], offset: usize, depth: usize, method: SplitMethod) {
+        let bbox = items.iter().skip(1).fold(items[0].1, |b, (_, bbox)| aabb::surrounding_box(&b, bbox));
+        let index = nodes.len();
+        nodes.push(LinearNode { bbox, kind: NodeKind::Leaf { first: offset, count: items.len() } });
+
+        // past the depth of the traversal stack everything left goes into one leaf
+        if items.len() == 1 || depth == MAX_DEPTH {
+            return
+        }
+
+        let split = match method {
+            SplitMethod::Sah => match LinearTree::find_sah_split(items) {
+                Some(split) => {
+                    let leaf_cost = INTERSECTION_COST * items.len() as f64;
+                    let split_cost = TRAVERSAL_COST + INTERSECTION_COST * split.cost / surface_area(&bbox);
+                    if items.len() <= MAX_LEAF_SIZE && leaf_cost <= split_cost {
+                        return
+                    }
+                    Some((split.partition(items), split.axis))
+                },
+                None => None
+            },
+            SplitMethod::Median => None
+        };
+        let (mid, axis) = match split {
+            Some(split) => split,
+            None if items.len() <= MAX_LEAF_SIZE => return,
+            None => LinearTree::partition_median(items, &bbox)
+        };
+
+        let (left, right) = items.split_at_mut(mid);
+        LinearTree::build_node(nodes, left, offset, depth + 1, method);
+        let second = nodes.len();
+        LinearTree::build_node(nodes, right, offset + mid, depth + 1, method);
+        nodes[index].kind = NodeKind::Interior { second, axis };
+    }
+
+    // orders the items along the widest axis around the middle one, the first half goes to the left child
+    fn partition_median(items: &mut [(usize, AABB)], bounds: &AABB) -> (usize, usize) {
+        // find the axis with the greatest range for this set of objects
+        let extent = bounds.max - bounds.min;
+        let axis = (0..3).max_by(|&a, &b| extent[a].partial_cmp(&extent[b]).unwrap_or(Ordering::Equal)).unwrap();
+
+        // only the halves matter, not the order within them
+        let mid = items.len() / 2;
+        items.select_nth_unstable_by(mid, |a, b| centroid(&a.1, axis).partial_cmp(&centroid(&b.1, axis)).unwrap_or(Ordering::Equal));
+        (mid, axis)
+    }
+
+    // bins the box centers along each axis and keeps the cheapest plane between two bins,
+    // None when every center is at the same place
+    fn find_sah_split(items: &[(usize, AABB)]) -> Option<SahSplit> {
+        let mut best: Option<SahSplit> = None;
+
+        for axis in 0..3 {
+            let (lo, hi) = items.iter().fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), (_, bbox)| {
+                let c = centroid(bbox, axis);
+                (lo.min(c), hi.max(c))
+            });
+            if hi - lo <= 0.0 {
+                continue
+            }
+
+            let mut counts = [0usize; BINS];
+            let mut bounds: [Option<AABB>; BINS] = [None; BINS];
+            for (_, bbox) in items.iter() {
+                let b = bin_of(bbox, axis, lo, hi);
+                counts[b] += 1;
+                bounds[b] = Some(bounds[b].map_or(*bbox, |acc| aabb::surrounding_box(&acc, bbox)));
+            }
+
+            // area times count of everything left of each plane, then right of it
+            let mut left_cost = [0.0; BINS];
+            let mut acc: Option<AABB> = None;
+            let mut count = 0;
+            for b in 0..BINS - 1 {
+                count += counts[b];
+                if let Some(bbox) = bounds[b] {
+                    acc = Some(acc.map_or(bbox, |a| aabb::surrounding_box(&a, &bbox)));
+                }
+                left_cost[b] = acc.map_or(0.0, |a| surface_area(&a) * count as f64);
+            }
+            let mut acc: Option<AABB> = None;
+            let mut count = 0;
+            for b in (1..BINS).rev() {
+                count += counts[b];
+                if let Some(bbox) = bounds[b] {
+                    acc = Some(acc.map_or(bbox, |a| aabb::surrounding_box(&a, &bbox)));
+                }
+                // the plane between the bins b - 1 and b
+                let cost = left_cost[b - 1] + acc.map_or(0.0, |a| surface_area(&a) * count as f64);
+                if count > 0 && count < items.len() && best.as_ref().is_none_or(|best| cost < best.cost) {
+                    best = Some(SahSplit { cost, axis, bin: b, lo, hi });
                 }
             }
         }
 
-        fn axis_range(hit: &Vec<Box<dyn Hittable>>, time0: f64, time1: f64, axis: usize) -> f64 {
-            let (min, max) = hit.iter().fold((f64::MAX, f64::MIN), |(bmin, bmax), hit| {
-                if let Some(aabb) = hit.bounding_box(time0, time1) {
-                    (bmin.min(aabb.min[axis]), bmax.max(aabb.max[axis]))
-                } else {
-                    (bmin, bmax)
+        best
+    }
+
+    pub(crate) fn bbox(&self) -> AABB {
+        self.nodes[0].bbox
+    }
+
+    /// recomputes the boxes of the nodes bottom up, `primitive_box` gives the box of the
+    /// primitive at a place in the order of the leaves
+    pub(crate) fn refit(&mut self, primitive_box: impl Fn(usize) -> AABB) {
+        // children are stored after their parent, so going backwards visits them first
+        for index in (0..self.nodes.len()).rev() {
+            let bbox = match self.nodes[index].kind {
+                NodeKind::Interior { second, .. } => aabb::surrounding_box(&self.nodes[index + 1].bbox, &self.nodes[second].bbox),
+                NodeKind::Leaf { first, count } => {
+                    (first..first + count).map(&primitive_box).reduce(|a, b| aabb::surrounding_box(&a, &b)).unwrap()
                 }
-            });
-            max - min
-        }
-
-        // find the axis and the greatest range for this set of objects by Closure and Iterator
-        let mut axis_ranges: Vec<(usize, f64)> = (0..3).map(|a| (a, axis_range(&hit, time0, time1, a))).collect();
-        // reversed comparison function, to sort descending:
-        axis_ranges.sort_unstable_by(|a, b| b.1.partial_cmp(&a.1).unwrap());     
-        let axis = axis_ranges[0].0;
-
-        // sort objects along it by  widest extension of bounding box along that axis
-        hit.sort_unstable_by(box_compare(time0, time1, axis));
-
-        let length = hit.len();
-        match length {
-            0 => panic!("no object in the scene"),
-            1 => {
-                let leaf = hit.pop().unwrap();
-                if let Some(bbox) = leaf.bounding_box(time0, time1) {
-                    BVH { tree: BVHNode::Leaf(leaf), bbox }
-                } else {
-                    panic!("no bounding box in bvh node")
+            };
+            self.nodes[index].bbox = bbox;
+        }
+    }
+
+    pub(crate) fn stats(&self) -> BVHStats {
+        let mut stats = BVHStats::default();
+        let root_area = surface_area(&self.nodes[0].bbox);
+        let mut stack = vec![(0, 1)];
+        while let Some((index, depth)) = stack.pop() {
+            let node = &self.nodes[index];
+            stats.nodes += 1;
+            stats.depth = stats.depth.max(depth);
+            // probability that a ray through the root also passes through the node
+            let area = if root_area > 0.0 { surface_area(&node.bbox) / root_area } else { 1.0 };
+            match node.kind {
+                NodeKind::Interior { second, .. } => {
+                    stats.sah_cost += TRAVERSAL_COST * area;
+                    stack.push((index + 1, depth + 1));
+                    stack.push((second, depth + 1));
+                },
+                NodeKind::Leaf { count, .. } => {
+                    stats.leaves += 1;
+                    stats.sah_cost += INTERSECTION_COST * count as f64 * area;
                 }
-            },
-            _ => {
-                let right = BVH::new(hit.drain(length/2..).collect(), time0, time1);
-                // half the hit moved
-                let left = BVH::new(hit, time0, time1);
-                let bbox = aabb::surrounding_box(&left.bbox, &right.bbox);
-                BVH { tree: BVHNode::Branch { left: Box::new(left), right: Box::new(right) }, bbox }
             }
         }
+        stats
+    }
+
+    /// walks the leaves the ray passes through nearest child first, calling `hit` with the place
+    /// of each of their primitives in the order of the leaves and the current end of the ray.
+    /// `hit` returns the distance of a closer hit, which then shortens the ray
+    pub(crate) fn traverse(&self, r: &Ray, t_min: f64, mut t_max: f64, mut hit: impl FnMut(usize, f64) -> Option<f64>) {
+        let origin = r.origin();
+        let d = r.direction();
+        let inverse_direction = Vec3::new(1.0 / d.x(), 1.0 / d.y(), 1.0 / d.z());
 
+        let mut stack = [0usize; MAX_DEPTH];
+        let mut top = 0;
+        let mut current = 0;
+        loop {
+            let node = &self.nodes[current];
+            if node.bbox.hit_inverse(origin, inverse_direction, t_min, t_max) {
+                match node.kind {
+                    NodeKind::Interior { second, axis } => {
+                        // visit the child on the side the ray comes from first, the other one is
+                        // often culled by the closer hit
+                        if inverse_direction[axis] < 0.0 {
+                            stack[top] = current + 1;
+                            current = second;
+                        } else {
+                            stack[top] = second;
+                            current += 1;
+                        }
+                        top += 1;
+                        continue
+                    },
+                    NodeKind::Leaf { first, count } => {
+                        for i in first..first + count {
+                            if let Some(t) = hit(i, t_max) {
+                                t_max = t;
+                            }
+                        }
+                    }
+                }
+            }
+            if top == 0 {
+                break
+            }
+            top -= 1;
+            current = stack[top];
+        }
     }
 }
 
-impl Hittable for BVH {
-    fn hit(&self, r: &Ray, t_min: f64, mut t_max: f64) -> Option<HitRecord> {
-        if self.bbox.hit(r, t_min, t_max) {
-            match &self.tree {
-                BVHNode::Branch { left, right } => {
-                    let left = left.hit(&r, t_min, t_max);
-                    if let Some(l) = &left {t_max = l.t};
-                    let right = right.hit(&r, t_min, t_max);
-                    if right.is_some() { right } else { left }
-                },
-                BVHNode::Leaf(leaf) => leaf.hit(&r, t_min, t_max),
+fn bin_of(bbox: &AABB, axis: usize, lo: f64, hi: f64) -> usize {
+    (((centroid(bbox, axis) - lo) / (hi - lo) * BINS as f64) as usize).min(BINS - 1)
+}
+
+// the cheapest plane found by the heuristic, `cost` is the area weighted object count of both sides
+struct SahSplit {
+    cost: f64,
+    axis: usize,
+    // first bin of the right child, and the range of the centers binned along the axis
+    bin: usize,
+    lo: f64,
+    hi: f64
+}
+
+impl SahSplit {
+    // moves the items of the left bins to the front, returns how many there are
+    fn partition(&self, items: &mut [(usize, AABB)]) -> usize {
+        let mut mid = 0;
+        for i in 0..items.len() {
+            if bin_of(&items[i].1, self.axis, self.lo, self.hi) < self.bin {
+                items.swap(i, mid);
+                mid += 1;
             }
+        }
+        mid
+    }
+}
+
+/// bounding volume hierarchy over a list of objects
+///
+/// the nodes are stored depth first in one array and the leaves hold ranges of the reordered objects
+pub struct BVH {
+    tree: LinearTree,
+    objects: Vec<Box<dyn Hittable>>,
+    method: SplitMethod,
+    built_cost: f64
+}
+
+impl BVH {
+    /// every object must have a bounding box between time0 and time1, split with the surface area heuristic
+    pub fn new(hit: Vec<Box<dyn Hittable>>, time0: f64, time1: f64) -> BVH {
+        BVH::with_split(hit, time0, time1, SplitMethod::Sah)
+    }
+
+    pub fn with_split(hit: Vec<Box<dyn Hittable>>, time0: f64, time1: f64, method: SplitMethod) -> BVH {
+        if hit.is_empty() {
+            panic!("no object in the scene")
+        }
+        // the boxes are only computed once
+        let boxes: Vec<AABB> = hit.iter().map(|object| {
+            object.bounding_box(time0, time1).expect("no bounding box in bvh node")
+        }).collect();
+
+        let (tree, order) = LinearTree::build(&boxes, method);
+        let mut objects: Vec<Option<Box<dyn Hittable>>> = hit.into_iter().map(Some).collect();
+        let built_cost = tree.stats().sah_cost;
+        BVH {
+            tree,
+            objects: order.into_iter().map(|i| objects[i].take().unwrap()).collect(),
+            method,
+            built_cost
+        }
+    }
+
+    /// updates the boxes for the objects moving between t0 and t1, e.g. the next frame of an animation,
+    /// keeping the tree. when its sah cost has grown past REFIT_TOLERANCE times the cost it had when
+    /// it was last built, it is rebuilt instead and true is returned. the cost is relative to the root
+    /// box, so objects that move together or spread out evenly keep the tree
+    pub fn refit(&mut self, t0: f64, t1: f64) -> bool {
+        let objects = &self.objects;
+        self.tree.refit(|i| objects[i].bounding_box(t0, t1).expect("no bounding box in bvh node"));
+
+        if self.stats().sah_cost > REFIT_TOLERANCE * self.built_cost {
+            let objects = std::mem::take(&mut self.objects);
+            *self = BVH::with_split(objects, t0, t1, self.method);
+            true
         } else {
-            None
+            false
         }
     }
 
+    pub fn stats(&self) -> BVHStats {
+        self.tree.stats()
+    }
+}
+
+impl Hittable for BVH {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rng: &mut Sampler) -> Option<HitRecord> {
+        let mut closest = None;
+        self.tree.traverse(r, t_min, t_max, |i, t_max| {
+            let rec = self.objects[i].hit(r, t_min, t_max, rng)?;
+            let t = rec.t;
+            closest = Some(rec);
+            Some(t)
+        });
+        closest
+    }
+
     fn bounding_box(&self, _t0: f64, _t1: f64) -> Option<AABB> {
-        Some(self.bbox)
+        Some(self.tree.bbox())
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use rand::Rng;
+    use crate::hit::HittableList;
+    use crate::sphere::{Sphere, MovingSphere};
+    use crate::test_util::{grey, Grey};
+    use crate::vec::Point3;
+    use super::*;
+
+    fn random_point(rng: &mut Sampler, size: f64) -> Point3 {
+        Point3::new(rng.gen_range(-size..size), rng.gen_range(-size..size), rng.gen_range(-size..size))
+    }
+
+    // the same spheres as a list of objects, built again for each structure
+    fn spheres(seed: u64) -> Vec<Box<dyn Hittable>> {
+        let mut rng = Sampler::new(seed);
+        (0..200).map(|_| {
+            let center = random_point(&mut rng, 10.0);
+            Box::new(Sphere::new(center, rng.gen_range(0.1..1.0), grey())) as Box<dyn Hittable>
+        }).collect()
+    }
+
+    fn brute_force(objects: Vec<Box<dyn Hittable>>) -> HittableList {
+        let mut list = HittableList::default();
+        for object in objects {
+            list.push(object);
+        }
+        list
+    }
+
+    // shoots rays between random points at the given time, the nearest hits must agree
+    fn assert_same_hits(a: &dyn Hittable, b: &dyn Hittable, time: f64) {
+        let mut rng = Sampler::new(7);
+        for _ in 0..2000 {
+            let origin = random_point(&mut rng, 15.0);
+            let r = Ray::new(origin, random_point(&mut rng, 5.0) - origin, time);
+            let (ta, tb) = (a.hit(&r, 0.001, f64::INFINITY, &mut rng).map(|rec| rec.t),
+                            b.hit(&r, 0.001, f64::INFINITY, &mut rng).map(|rec| rec.t));
+            assert_eq!(ta, tb, "from {} along {}", r.origin(), r.direction());
+        }
+    }
+
+    // the probes reached by the rays, in order
+    type Visits = Arc<Mutex<Vec<usize>>>;
+
+    // a unit box that records the rays reaching it, solid ones are hit where the ray enters them
+    struct Probe {
+        index: usize,
+        solid: bool,
+        visits: Visits,
+        material: Grey
+    }
+
+    impl Hittable for Probe {
+        fn hit(&self, r: &Ray, t_min: f64, t_max: f64, _rng: &mut Sampler) -> Option<HitRecord<'_>> {
+            self.visits.lock().unwrap().push(self.index);
+            let t = (2.0 * self.index as f64 - r.origin().x()) / r.direction().x();
+            if !self.solid || t < t_min || t > t_max {
+                return None
+            }
+            Some(HitRecord {
+                position: r.at(t),
+                normal: Vec3::new(-1.0, 0.0, 0.0),
+                t,
+                u: 0.0,
+                v: 0.0,
+                tangent: Vec3::new(0.0, 1.0, 0.0),
+                front_face: true,
+                material: &self.material
+            })
+        }
+
+        fn bounding_box(&self, _t0: f64, _t1: f64) -> Option<AABB> {
+            let x = 2.0 * self.index as f64;
+            Some(AABB::new(Vec3::new(x, 0.0, 0.0), Vec3::new(x + 1.0, 1.0, 1.0)))
+        }
+    }
+
+    // probes along the x axis, one every two units, with the list of the ones visited
+    fn row(n: usize, solid: &[usize]) -> (Vec<Box<dyn Hittable>>, Visits) {
+        let visits = Arc::new(Mutex::new(Vec::new()));
+        let probes = (0..n).map(|index| {
+            Box::new(Probe { index, solid: solid.contains(&index), visits: visits.clone(), material: grey() }) as Box<dyn Hittable>
+        }).collect();
+        (probes, visits)
+    }
+
+    fn along_x(x: f64, y: f64, direction: f64) -> Ray {
+        Ray::new(Point3::new(x, y, 0.5), Vec3::new(direction, 0.0, 0.0), 0.0)
+    }
+
+    #[test]
+    fn split_methods_match_brute_force() {
+        let list = brute_force(spheres(1));
+        for method in [SplitMethod::Sah, SplitMethod::Median] {
+            let bvh = BVH::with_split(spheres(1), 0.0, 1.0, method);
+            assert_same_hits(&bvh, &list, 0.0);
+        }
+    }
+
+    #[test]
+    fn stats_of_two_objects() {
+        let objects = || vec![Box::new(Sphere::new(Point3::new(-5.0, 0.0, 0.0), 1.0, grey())) as Box<dyn Hittable>,
+                              Box::new(Sphere::new(Point3::new(5.0, 0.0, 0.0), 1.0, grey())) as Box<dyn Hittable>];
+
+        // far apart they are worth splitting, each child is a cube of side 2 in a 12 x 2 x 2 root
+        let stats = BVH::new(objects(), 0.0, 1.0).stats();
+        assert_eq!((stats.nodes, stats.leaves, stats.depth), (3, 2, 2));
+        assert!((stats.sah_cost - (TRAVERSAL_COST + 2.0 * INTERSECTION_COST * 24.0 / 104.0)).abs() < 1e-12);
+
+        // the median split keeps small lists in one leaf
+        let stats = BVH::with_split(objects(), 0.0, 1.0, SplitMethod::Median).stats();
+        assert_eq!((stats.nodes, stats.leaves, stats.depth), (1, 1, 1));
+        assert!((stats.sah_cost - 2.0 * INTERSECTION_COST).abs() < 1e-12);
+    }
+
+    #[test]
+    fn stats_are_consistent() {
+        for method in [SplitMethod::Sah, SplitMethod::Median] {
+            let stats = BVH::with_split(spheres(2), 0.0, 1.0, method).stats();
+            // every interior node has two children
+            assert_eq!(stats.nodes, 2 * stats.leaves - 1);
+            assert!(stats.leaves >= 200 / MAX_LEAF_SIZE && stats.leaves <= 200);
+            assert!(stats.depth as f64 >= (stats.leaves as f64).log2() + 1.0 && stats.depth <= MAX_DEPTH);
+            // the root alone costs a traversal, and no tree is worse than one leaf of everything
+            assert!(stats.sah_cost > TRAVERSAL_COST && stats.sah_cost < 200.0 * INTERSECTION_COST);
+        }
+        let sah = BVH::with_split(spheres(2), 0.0, 1.0, SplitMethod::Sah).stats();
+        let median = BVH::with_split(spheres(2), 0.0, 1.0, SplitMethod::Median).stats();
+        assert!(sah.sah_cost <= median.sah_cost, "sah {} median {}", sah, median);
+    }
+
+    #[test]
+    fn traversal_visits_the_nearest_leaves_first() {
+        let (probes, visits) = row(16, &[]);
+        let bvh = BVH::new(probes, 0.0, 1.0);
+        for (x, direction) in [(-1.0, 1.0), (40.0, -1.0)] {
+            visits.lock().unwrap().clear();
+            assert!(bvh.hit(&along_x(x, 0.5, direction), 0.0, f64::INFINITY, &mut Sampler::new(0)).is_none());
+            let mut expected: Vec<usize> = (0..16).collect();
+            if direction < 0.0 {
+                expected.reverse();
+            }
+            assert_eq!(*visits.lock().unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn closer_hits_cull_the_rest() {
+        let (probes, visits) = row(16, &[3, 9]);
+        let bvh = BVH::new(probes, 0.0, 1.0);
+        let rec = bvh.hit(&along_x(-1.0, 0.5, 1.0), 0.0, f64::INFINITY, &mut Sampler::new(0)).unwrap();
+        assert_eq!(rec.t, 7.0);
+        // past the hit only the rest of its leaf can be visited
+        let visits = visits.lock().unwrap();
+        assert!(visits.starts_with(&[0, 1, 2, 3]));
+        assert!(visits.iter().all(|&i| i < 3 + MAX_LEAF_SIZE), "visited {:?}", visits);
+    }
+
+    #[test]
+    fn leaves_hold_several_primitives() {
+        // small lists are one leaf with the median split
+        let (probes, visits) = row(3, &[0, 1, 2]);
+        let bvh = BVH::with_split(probes, 0.0, 1.0, SplitMethod::Median);
+        let stats = bvh.stats();
+        assert_eq!((stats.nodes, stats.leaves), (1, 1));
+
+        // every primitive of the leaf is tried and the nearest hit is kept, from both sides
+        let rec = bvh.hit(&along_x(-1.0, 0.5, 1.0), 0.0, f64::INFINITY, &mut Sampler::new(0)).unwrap();
+        assert_eq!(rec.t, 1.0);
+        let rec = bvh.hit(&along_x(10.0, 0.5, -1.0), 0.0, f64::INFINITY, &mut Sampler::new(0)).unwrap();
+        assert_eq!(rec.t, 6.0);
+        let mut visited = visits.lock().unwrap().clone();
+        visited.sort();
+        assert_eq!(visited, vec![0, 0, 1, 1, 2, 2]);
+
+        // a ray past the leaf reaches none of them
+        visits.lock().unwrap().clear();
+        assert!(bvh.hit(&along_x(-1.0, 5.0, 1.0), 0.0, f64::INFINITY, &mut Sampler::new(0)).is_none());
+        assert!(visits.lock().unwrap().is_empty());
+    }
+
+    // spheres at random places at time 0 that are displaced by `motion` at time 1
+    fn moving_spheres(motion: impl Fn(&mut Sampler, Point3) -> Point3) -> Vec<Box<dyn Hittable>> {
+        let mut rng = Sampler::new(3);
+        (0..200).map(|_| {
+            let center = random_point(&mut rng, 10.0);
+            let moved = motion(&mut rng, center);
+            Box::new(MovingSphere::new(center, moved, 0.0, 1.0, 0.5, grey())) as Box<dyn Hittable>
+        }).collect()
+    }
+
+    #[test]
+    fn refit_follows_small_motion() {
+        let motion = |_: &mut Sampler, c: Point3| c + Vec3::new(0.2, -0.1, 0.3);
+        let mut bvh = BVH::new(moving_spheres(motion), 0.0, 0.0);
+        assert!(!bvh.refit(1.0, 1.0));
+        assert_same_hits(&bvh, &brute_force(moving_spheres(motion)), 1.0);
+    }
+
+    #[test]
+    fn refit_rebuilds_after_shuffling() {
+        // every sphere jumps to an unrelated place, the old tree is no good anymore
+        let motion = |rng: &mut Sampler, _: Point3| random_point(rng, 10.0);
+        let mut bvh = BVH::new(moving_spheres(motion), 0.0, 0.0);
+        let before = bvh.stats().sah_cost;
+        assert!(bvh.refit(1.0, 1.0));
+        assert!(bvh.stats().sah_cost < REFIT_TOLERANCE * before);
+        assert_same_hits(&bvh, &brute_force(moving_spheres(motion)), 1.0);
+    }
+}
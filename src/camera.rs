@@ -2,7 +2,9 @@ use rand::Rng;
 use std::f64;
 use super::vec::{Vec3, Point3};
 use super::ray::Ray;
+use super::sampler::Sampler;
 
+/// thin lens camera with a shutter open between time0 and time1
 pub struct Camera{
     origin:Point3,
     lower_left_corner: Point3,
@@ -16,6 +18,7 @@ pub struct Camera{
 }
 
 impl Camera{
+    /// `vfov` is the vertical field of view in degrees
     pub fn new(lookfrom:Point3, lookat:Point3, vup:Vec3, vfov:f64, aspect_ratio:f64, aperture:f64, focus_dist:f64,time0:f64, time1:f64) -> Camera{
         // const ASPECT_RATIO: f64 = 16.0/9.0;
         // const VIEWPORT_HEIGHT: f64 = 2.0;
@@ -48,13 +51,14 @@ impl Camera{
         }
     }
 
-    pub fn get_ray(&self, s:f64, t:f64)->Ray{
-        let rd = self.lens_radius*Vec3::random_in_unit_disk();
+    /// ray through the viewport point (s, t), both in [0, 1] from the lower left corner
+    pub fn get_ray(&self, s:f64, t:f64, rng: &mut Sampler)->Ray{
+        let rd = self.lens_radius*Vec3::random_in_unit_disk(rng);
         let offset = self.cu*rd.x() +self.cv*rd.y();
 
         //track time between the time of strat and end
         
-        let time = self.time0+rand::thread_rng().gen::<f64>()*(self.time1 - self.time0);
+        let time = self.time0+rng.gen::<f64>()*(self.time1 - self.time0);
         Ray::new(self.origin+offset, self.lower_left_corner + s * self.horizontal + t * self.vertical - (self.origin+offset), time)
     }
 }
\ No newline at end of file
@@ -0,0 +1,207 @@
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use super::vec::Color;
+use super::framebuffer::Framebuffer;
+
+// a progressive render adds passes of samples into an accumulator, which can be written to a file
+// and read back later to add more samples.
+// the random numbers of a pass only depend on the seed and the pass index, so those two
+// are the whole generator state and the resumed render matches an uninterrupted one
+
+const MAGIC: &[u8; 8] = b"BEAUTYCK";
+const VERSION: u64 = 1;
+// bytes of the summed color of one pixel
+const PIXEL_SIZE: u64 = 3 * 8;
+
+/// radiance summed over every sample taken so far
+#[derive(Clone)]
+pub struct Accumulator {
+    width: u64,
+    height: u64,
+    seed: u64,
+    // what was rendered, see `scene_id`
+    scene: u64,
+    passes: u64,
+    samples: u64,
+    sum: Vec<Color>
+}
+
+fn read_u64(input: &mut impl Read) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    input.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn read_f64(input: &mut impl Read) -> io::Result<f64> {
+    let mut bytes = [0; 8];
+    input.read_exact(&mut bytes)?;
+    Ok(f64::from_le_bytes(bytes))
+}
+
+/// identifies a scene by its source, the json text or the name of a built-in scene, so that a
+/// checkpoint is only resumed with the scene it was started with (fnv-1a, stable across builds)
+pub fn scene_id(source: &[u8]) -> u64 {
+    source.iter().fold(0xcbf29ce484222325, |hash, &byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3))
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+impl Accumulator {
+    /// an empty accumulator, the seed decides the samples of every pass
+    pub fn new(width: u64, height: u64, seed: u64) -> Accumulator {
+        Accumulator {
+            width,
+            height,
+            seed,
+            scene: 0,
+            passes: 0,
+            samples: 0,
+            sum: vec![Color::default(); (width * height) as usize]
+        }
+    }
+
+    /// records the scene being rendered, see `scene_id`
+    pub fn with_scene(mut self, scene: u64) -> Accumulator {
+        self.scene = scene;
+        self
+    }
+
+    pub fn width(&self) -> u64 {
+        self.width
+    }
+
+    pub fn height(&self) -> u64 {
+        self.height
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn scene(&self) -> u64 {
+        self.scene
+    }
+
+    /// number of finished passes
+    pub fn passes(&self) -> u64 {
+        self.passes
+    }
+
+    /// samples per pixel taken so far
+    pub fn samples(&self) -> u64 {
+        self.samples
+    }
+
+    pub(crate) fn add(&mut self, x: u64, y: u64, color: Color) {
+        self.sum[(y * self.width + x) as usize] += color;
+    }
+
+    pub(crate) fn finish_pass(&mut self, samples: u64) {
+        self.passes += 1;
+        self.samples += samples;
+    }
+
+    /// the mean of the samples, black before the first pass
+    pub fn image(&self) -> Framebuffer {
+        let mut image = Framebuffer::new(self.width, self.height);
+        if self.samples > 0 {
+            for y in 0..self.height {
+                for x in 0..self.width {
+                    image.set(x, y, self.sum[(y * self.width + x) as usize] / self.samples as f64);
+                }
+            }
+        }
+        image
+    }
+
+    /// binary little endian checkpoint
+    pub fn write(&self, out: &mut impl Write) -> io::Result<()> {
+        out.write_all(MAGIC)?;
+        for value in [VERSION, self.width, self.height, self.seed, self.scene, self.passes, self.samples] {
+            out.write_all(&value.to_le_bytes())?;
+        }
+        for color in &self.sum {
+            for i in 0..3 {
+                out.write_all(&color[i].to_le_bytes())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// the pixel data must fill the rest of the input exactly, so a truncated or corrupt file is
+    /// rejected before anything is allocated for it
+    pub fn read(input: &mut (impl Read + Seek)) -> io::Result<Accumulator> {
+        let mut magic = [0; 8];
+        input.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("not a render checkpoint"))
+        }
+        if read_u64(input)? != VERSION {
+            return Err(invalid("unsupported checkpoint version"))
+        }
+
+        let width = read_u64(input)?;
+        let height = read_u64(input)?;
+        let seed = read_u64(input)?;
+        let scene = read_u64(input)?;
+        let passes = read_u64(input)?;
+        let samples = read_u64(input)?;
+
+        let position = input.stream_position()?;
+        let remaining = input.seek(SeekFrom::End(0))? - position;
+        input.seek(SeekFrom::Start(position))?;
+        if width.checked_mul(height).and_then(|pixels| pixels.checked_mul(PIXEL_SIZE)) != Some(remaining) {
+            return Err(invalid("the image size in the checkpoint does not match its pixel data"))
+        }
+
+        let mut accumulator = Accumulator::new(width, height, seed).with_scene(scene);
+        accumulator.passes = passes;
+        accumulator.samples = samples;
+        for color in accumulator.sum.iter_mut() {
+            *color = Color::new(read_f64(input)?, read_f64(input)?, read_f64(input)?);
+        }
+        Ok(accumulator)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use super::*;
+
+    fn checkpoint() -> Vec<u8> {
+        let mut accumulator = Accumulator::new(3, 2, 42).with_scene(scene_id(b"cornell-box"));
+        accumulator.add(1, 1, Color::new(0.25, 0.5, 2.0));
+        accumulator.finish_pass(4);
+        let mut bytes = Vec::new();
+        accumulator.write(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn round_trip() {
+        let accumulator = Accumulator::read(&mut Cursor::new(checkpoint())).unwrap();
+        assert_eq!((accumulator.width(), accumulator.height(), accumulator.seed()), (3, 2, 42));
+        assert_eq!(accumulator.scene(), scene_id(b"cornell-box"));
+        assert_ne!(accumulator.scene(), scene_id(b"cornell-smoke"));
+        assert_eq!((accumulator.passes(), accumulator.samples()), (1, 4));
+        assert_eq!(accumulator.image().get(1, 1)[2], 0.5);
+    }
+
+    #[test]
+    fn truncated_pixels_are_rejected() {
+        let mut bytes = checkpoint();
+        bytes.pop();
+        assert!(Accumulator::read(&mut Cursor::new(bytes)).is_err());
+    }
+
+    #[test]
+    fn huge_size_is_rejected_before_allocating() {
+        let mut bytes = checkpoint();
+        // width and height follow the magic and the version
+        bytes[16..24].copy_from_slice(&(1u64 << 31).to_le_bytes());
+        bytes[24..32].copy_from_slice(&(1u64 << 31).to_le_bytes());
+        assert!(Accumulator::read(&mut Cursor::new(bytes)).is_err());
+    }
+}
@@ -0,0 +1,190 @@
+use std::path::PathBuf;
+use std::str::FromStr;
+use beauty::{Color, ImageFormat, ToneMapper};
+use beauty::tonemap::{ToneCurve, Transfer};
+use beauty::bvh::SplitMethod;
+
+pub const USAGE: &str = "\
+usage: Beauty [options]
+
+options:
+    -s, --scene <name|file>   built-in scene or json scene file [default: scenes/cornell_box.json]
+                              built-in scenes: random, two-sphere, two-perlin-sphere, earth,
+                              light-room, cornell-box, cornell-smoke, final
+    -w, --width <pixels>      image width [default: 500]
+        --height <pixels>     image height [default: same as width]
+        --spp <samples>       samples per pixel [default: 1000]
+        --pass-spp <samples>  samples per pixel of each progressive pass
+                              [default: 16 with --checkpoint, otherwise all of them]
+        --checkpoint <file>   save the accumulated samples after every pass
+        --resume <file>       continue a checkpoint up to --spp samples, the image size
+                              and the scene must be the same as when it was started
+        --max-depth <bounces> ray bounce limit [default: 16]
+        --bvh <method>        bvh split method, sah or median [default: sah]
+        --bvh-stats           print the node count, depth and sah cost of every bvh
+    -j, --threads <count>     worker threads, 0 uses every core [default: 0]
+        --tile-size <pixels>  side of the square tiles rendered in parallel [default: 16]
+    -o, --output <file>       output file, - writes to stdout [default: -]
+        --format <format>     output format, guessed from the output extension [default: ppm]
+                              formats: ppm, png, png16 (16 bit png), hdr, exr
+                              hdr and exr keep linear radiance, only exposure and white balance apply
+        --exposure <stops>    brighten or darken the image by powers of two [default: 0]
+        --tonemap <curve>     clamp, reinhard, filmic or aces [default: clamp]
+        --gamma <transfer>    srgb, or 2 for a plain square root [default: srgb]
+        --white-balance <r,g,b>
+                              color of the light that should look white [default: 1,1,1]
+        --seed <number>       seed for the random number generator, the same seed gives
+                              the same image on any number of threads [default: random]
+    -h, --help                print this message";
+
+pub struct Settings {
+    pub scene: String,
+    pub width: u64,
+    pub height: u64,
+    pub samples_per_pixel: u64,
+    pub pass_samples: u64,
+    pub max_depth: u64,
+    pub threads: usize,
+    pub tile_size: u64,
+    pub bvh_split: SplitMethod,
+    pub bvh_stats: bool,
+    // None writes to stdout
+    pub output: Option<PathBuf>,
+    pub format: ImageFormat,
+    pub checkpoint: Option<PathBuf>,
+    pub resume: Option<PathBuf>,
+    pub tone: ToneMapper,
+    pub seed: Option<u64>,
+    pub help: bool
+}
+
+fn value<T: FromStr>(option: &str, arg: Option<String>) -> Result<T, String> {
+    let arg = arg.ok_or_else(|| format!("missing value for {}", option))?;
+    arg.parse::<T>().map_err(|_| format!("invalid value '{}' for {}", arg, option))
+}
+
+fn color(option: &str, arg: Option<String>) -> Result<Color, String> {
+    let arg: String = value(option, arg)?;
+    let c: Vec<f64> = arg.split(',').map(|x| x.trim().parse::<f64>()).collect::<Result<_, _>>()
+        .map_err(|_| format!("invalid value '{}' for {}", arg, option))?;
+    match c[..] {
+        [r, g, b] if r > 0.0 && g > 0.0 && b > 0.0 => Ok(Color::new(r, g, b)),
+        _ => Err(format!("{} needs three positive numbers, like 1,0.9,0.8", option))
+    }
+}
+
+impl Settings {
+    pub fn from_args(args: impl IntoIterator<Item = String>) -> Result<Settings, String> {
+        let mut scene = String::from("scenes/cornell_box.json");
+        let mut width = 500;
+        let mut height = None;
+        let mut samples_per_pixel = 1000;
+        let mut pass_samples = None;
+        let mut max_depth = 16;
+        let mut threads = 0;
+        let mut tile_size = 16;
+        let mut bvh_split = SplitMethod::Sah;
+        let mut bvh_stats = false;
+        let mut output = None;
+        let mut format = None;
+        let mut checkpoint = None;
+        let mut resume = None;
+        let mut seed = None;
+        let mut tone = ToneMapper::default();
+        let mut help = false;
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            // accept --option=value as well as --option value
+            let (option, inline) = match arg.split_once('=') {
+                Some((option, value)) if option.starts_with("--") => (option.to_string(), Some(value.to_string())),
+                _ => (arg, None)
+            };
+            let mut next = || inline.clone().or_else(|| args.next());
+
+            match option.as_str() {
+                "-s" | "--scene" => scene = value(&option, next())?,
+                "-w" | "--width" => width = value(&option, next())?,
+                "--height" => height = Some(value(&option, next())?),
+                "--spp" => samples_per_pixel = value(&option, next())?,
+                "--pass-spp" => pass_samples = Some(value(&option, next())?),
+                "--checkpoint" => checkpoint = Some(PathBuf::from(value::<String>(&option, next())?)),
+                "--resume" => resume = Some(PathBuf::from(value::<String>(&option, next())?)),
+                "--max-depth" => max_depth = value(&option, next())?,
+                "-j" | "--threads" => threads = value(&option, next())?,
+                "--tile-size" => tile_size = value(&option, next())?,
+                "--bvh" => {
+                    let name: String = value(&option, next())?;
+                    bvh_split = SplitMethod::from_name(&name).ok_or_else(|| format!("unknown bvh split method '{}'", name))?;
+                },
+                "--bvh-stats" => bvh_stats = true,
+                "-o" | "--output" => {
+                    let path: String = value(&option, next())?;
+                    output = if path == "-" { None } else { Some(PathBuf::from(path)) };
+                },
+                "--format" => {
+                    let name: String = value(&option, next())?;
+                    format = Some(ImageFormat::from_name(&name).ok_or_else(|| format!("unknown output format '{}', use one of {}", name, ImageFormat::NAMES.join(", ")))?);
+                },
+                "--seed" => seed = Some(value(&option, next())?),
+                "--exposure" => tone.exposure = value(&option, next())?,
+                "--tonemap" => {
+                    let name: String = value(&option, next())?;
+                    tone.curve = ToneCurve::from_name(&name).ok_or_else(|| format!("unknown tone curve '{}', use one of {}", name, ToneCurve::NAMES.join(", ")))?;
+                },
+                "--gamma" => {
+                    let name: String = value(&option, next())?;
+                    tone.transfer = Transfer::from_name(&name).ok_or_else(|| format!("unknown transfer function '{}'", name))?;
+                },
+                "--white-balance" => tone.white_balance = color(&option, next())?,
+                "-h" | "--help" => help = true,
+                _ => return Err(format!("unknown option '{}'", option))
+            }
+        }
+
+        let height = height.unwrap_or(width);
+        if width < 2 || height < 2 {
+            return Err(String::from("the image must be at least 2x2 pixels"))
+        }
+        if samples_per_pixel == 0 {
+            return Err(String::from("--spp must be at least 1"))
+        }
+        let pass_samples = pass_samples.unwrap_or(if checkpoint.is_some() { 16 } else { samples_per_pixel });
+        if pass_samples == 0 {
+            return Err(String::from("--pass-spp must be at least 1"))
+        }
+        if tile_size == 0 {
+            return Err(String::from("--tile-size must be at least 1"))
+        }
+
+        // without an explicit format, the output extension decides
+        let format = match (format, &output) {
+            (Some(format), _) => format,
+            (None, Some(path)) => {
+                ImageFormat::from_path(path)
+                    .ok_or_else(|| format!("cannot guess the output format of '{}', use --format", path.display()))?
+            },
+            (None, None) => ImageFormat::Ppm
+        };
+
+        Ok(Settings {
+            scene,
+            width,
+            height,
+            samples_per_pixel,
+            pass_samples,
+            max_depth,
+            threads,
+            tile_size,
+            bvh_split,
+            bvh_stats,
+            output,
+            format,
+            checkpoint,
+            resume,
+            tone,
+            seed,
+            help
+        })
+    }
+}
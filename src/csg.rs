@@ -0,0 +1,194 @@
+use std::f64;
+use super::vec::Vec3;
+use super::ray::Ray;
+use super::sampler::Sampler;
+use super::hit::{Hittable, HitRecord};
+use super::aabb::{self, AABB};
+
+#[derive(Clone, Copy)]
+pub enum Operation {
+    Union,
+    Intersection,
+    /// a with b cut away
+    Difference
+}
+
+/// constructive solid geometry, combines two closed objects into one solid
+///
+/// both objects need outward facing normals, a hit with front_face set is taken as entering.
+/// the surfaces keep the materials of the objects they come from
+pub struct Csg<A: Hittable, B: Hittable> {
+    operation: Operation,
+    a: A,
+    b: B
+}
+
+impl<A: Hittable, B: Hittable> Csg<A, B> {
+    pub fn new(operation: Operation, a: A, b: B) -> Csg<A, B> {
+        Csg { operation, a, b }
+    }
+
+    pub fn union(a: A, b: B) -> Csg<A, B> {
+        Csg::new(Operation::Union, a, b)
+    }
+
+    pub fn intersection(a: A, b: B) -> Csg<A, B> {
+        Csg::new(Operation::Intersection, a, b)
+    }
+
+    pub fn difference(a: A, b: B) -> Csg<A, B> {
+        Csg::new(Operation::Difference, a, b)
+    }
+
+    fn inside(&self, in_a: bool, in_b: bool) -> bool {
+        match self.operation {
+            Operation::Union => in_a || in_b,
+            Operation::Intersection => in_a && in_b,
+            Operation::Difference => in_a && !in_b
+        }
+    }
+
+    // the surfaces of the combined solid along the ray from t_min on, in order
+    fn boundary(&self, r: &Ray, t_min: f64, rng: &mut Sampler) -> Vec<HitRecord<'_>> {
+        // a closed object can only be seen from inside beyond t_max, so look all the way
+        let mut a_hits = self.a.hit_all(r, t_min, f64::INFINITY, rng).into_iter().peekable();
+        let mut b_hits = self.b.hit_all(r, t_min, f64::INFINITY, rng).into_iter().peekable();
+
+        // the ray starts inside an object when the first thing it does is leave it
+        let mut in_a = a_hits.peek().is_some_and(|rec| !rec.front_face);
+        let mut in_b = b_hits.peek().is_some_and(|rec| !rec.front_face);
+        let mut inside = self.inside(in_a, in_b);
+
+        let mut boundary = Vec::new();
+        loop {
+            let from_a = match (a_hits.peek(), b_hits.peek()) {
+                (Some(a), Some(b)) => a.t <= b.t,
+                (Some(_), None) => true,
+                (None, Some(_)) => false,
+                (None, None) => break
+            };
+            let mut rec = if from_a { a_hits.next() } else { b_hits.next() }.unwrap();
+            if from_a {
+                in_a = rec.front_face;
+            } else {
+                in_b = rec.front_face;
+            }
+
+            let now_inside = self.inside(in_a, in_b);
+            if now_inside != inside {
+                inside = now_inside;
+                // the normal already faces the ray, only which side is the outside can change,
+                // e.g. on the surfaces that difference cuts out of a
+                rec.front_face = now_inside;
+                boundary.push(rec);
+            }
+        }
+        boundary
+    }
+}
+
+impl<A: Hittable, B: Hittable> Hittable for Csg<A, B> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rng: &mut Sampler) -> Option<HitRecord<'_>> {
+        self.boundary(r, t_min, rng).into_iter().next().filter(|rec| rec.t <= t_max)
+    }
+
+    fn hit_all(&self, r: &Ray, t_min: f64, t_max: f64, rng: &mut Sampler) -> Vec<HitRecord<'_>> {
+        let mut boundary = self.boundary(r, t_min, rng);
+        boundary.retain(|rec| rec.t <= t_max);
+        boundary
+    }
+
+    fn bounding_box(&self, t0: f64, t1: f64) -> Option<AABB> {
+        match self.operation {
+            Operation::Union => Some(aabb::surrounding_box(&self.a.bounding_box(t0, t1)?, &self.b.bounding_box(t0, t1)?)),
+            Operation::Intersection => {
+                let (a, b) = (self.a.bounding_box(t0, t1)?, self.b.bounding_box(t0, t1)?);
+                // an empty overlap gives an inverted box that no ray hits
+                Some(AABB::new(Vec3::new(a.min.x().max(b.min.x()), a.min.y().max(b.min.y()), a.min.z().max(b.min.z())),
+                               Vec3::new(a.max.x().min(b.max.x()), a.max.y().min(b.max.y()), a.max.z().min(b.max.z()))))
+            },
+            Operation::Difference => self.a.bounding_box(t0, t1)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sphere::Sphere;
+    use crate::test_util::{grey, Grey};
+    use crate::vec::Point3;
+    use super::*;
+
+    type Ball = Sphere<Grey>;
+
+    fn ball(x: f64, radius: f64) -> Ball {
+        Sphere::new(Point3::new(x, 0.0, 0.0), radius, grey())
+    }
+
+    // the boundary along the x axis from `start` as (t, entering) pairs
+    fn intervals(h: &dyn Hittable, start: f64) -> Vec<(f64, bool)> {
+        let r = Ray::new(Point3::new(start, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0), 0.0);
+        h.hit_all(&r, 0.001, f64::INFINITY, &mut Sampler::new(0)).iter().map(|rec| ((rec.t * 1e9).round() / 1e9, rec.front_face)).collect()
+    }
+
+    // the balls span [-1, 1] and [0, 2] on the x axis, seen from x = -5
+    fn overlapping(operation: Operation) -> Csg<Ball, Ball> {
+        Csg::new(operation, ball(0.0, 1.0), ball(1.0, 1.0))
+    }
+
+    #[test]
+    fn union_merges_overlapping_intervals() {
+        assert_eq!(intervals(&overlapping(Operation::Union), -5.0), vec![(4.0, true), (7.0, false)]);
+    }
+
+    #[test]
+    fn intersection_keeps_the_overlap() {
+        assert_eq!(intervals(&overlapping(Operation::Intersection), -5.0), vec![(5.0, true), (6.0, false)]);
+    }
+
+    #[test]
+    fn difference_turns_the_cut_surface_inside_out() {
+        // entering b leaves the solid, so that surface faces away from the ray
+        assert_eq!(intervals(&overlapping(Operation::Difference), -5.0), vec![(4.0, true), (5.0, false)]);
+        // with the roles swapped only the part of b beyond a is left
+        let rest = Csg::difference(ball(1.0, 1.0), ball(0.0, 1.0));
+        assert_eq!(intervals(&rest, -5.0), vec![(6.0, true), (7.0, false)]);
+    }
+
+    #[test]
+    fn disjoint_intervals() {
+        let apart = || (ball(0.0, 1.0), ball(4.0, 1.0));
+        let (a, b) = apart();
+        assert_eq!(intervals(&Csg::union(a, b), -5.0), vec![(4.0, true), (6.0, false), (8.0, true), (10.0, false)]);
+        let (a, b) = apart();
+        assert!(intervals(&Csg::intersection(a, b), -5.0).is_empty());
+        let (a, b) = apart();
+        assert_eq!(intervals(&Csg::difference(a, b), -5.0), vec![(4.0, true), (6.0, false)]);
+    }
+
+    #[test]
+    fn ray_starting_inside() {
+        // from x = 0.5 the ray is inside both balls, the union is left at x = 2
+        assert_eq!(intervals(&overlapping(Operation::Union), 0.5), vec![(1.5, false)]);
+        assert_eq!(intervals(&overlapping(Operation::Intersection), 0.5), vec![(0.5, false)]);
+        // inside b the difference is empty until b is left, still inside a then
+        assert!(intervals(&overlapping(Operation::Difference), 0.5).is_empty());
+    }
+
+    #[test]
+    fn nested_operations() {
+        // a hollow ball: [-2, 2] without [-1, 1], then cut by [0.5, 3.5]
+        let shell = Csg::difference(ball(0.0, 2.0), ball(0.0, 1.0));
+        assert_eq!(intervals(&shell, -5.0), vec![(3.0, true), (4.0, false), (6.0, true), (7.0, false)]);
+        let cut = Csg::difference(shell, ball(2.0, 1.5));
+        assert_eq!(intervals(&cut, -5.0), vec![(3.0, true), (4.0, false)]);
+    }
+
+    #[test]
+    fn hit_respects_t_max() {
+        let union = overlapping(Operation::Union);
+        let r = Ray::new(Point3::new(-5.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0), 0.0);
+        assert!(union.hit(&r, 0.001, 3.5, &mut Sampler::new(0)).is_none());
+        assert!((union.hit(&r, 0.001, 4.5, &mut Sampler::new(0)).unwrap().t - 4.0).abs() < 1e-12);
+    }
+}
@@ -1,47 +1,141 @@
-use super::vec::{Point3};
-use super::hit::{Hittable, HitRecord, HittableList};
+use super::vec::{Vec3, Point3};
+use super::ray::Ray;
+use super::sampler::Sampler;
+use super::hit::{Hittable, HitRecord, HittableList, FlipNormal};
 use super::mat::{Material};
-use super::rect::{Plane, AARect};
+use super::quad::{self, Quad};
 use super::aabb::AABB;
 
+/// box made of six quads, see `new` and `oriented` for how they face
 pub struct Cube {
-    min: Point3,
-    max: Point3,
+    bbox: AABB,
     sides: HittableList
 }
 
 impl Cube {
+    /// axis aligned box between the corners min and max, made of six quads whose normals all
+    /// point along +x, +y or +z like the axis aligned rectangles, so those on the min sides point
+    /// in. sides without area are left out, so a box of no thickness is a single panel. csg needs
+    /// every normal pointing out, use `oriented` for that
     pub fn new<M: Material + Clone + 'static>(min: Point3, max: Point3, material: M) -> Cube {
+        let extent = max - min;
+        let (dx, dy, dz) = (Vec3::new(extent.x(), 0.0, 0.0), Vec3::new(0.0, extent.y(), 0.0), Vec3::new(0.0, 0.0, extent.z()));
+
         let mut sides = HittableList::default();
+        for corner in [max - dx - dy, min] {
+            if quad::spans_plane(dx, dy) {
+                sides.push(Quad::new(corner, dx, dy, material.clone()));
+            }
+        }
+        // x cross z is -y, the sides are flipped to face +y with u along x and v along z
+        for corner in [max - dx - dz, min] {
+            if quad::spans_plane(dx, dz) {
+                sides.push(FlipNormal::new(Quad::new(corner, dx, dz, material.clone())));
+            }
+        }
+        for corner in [max - dy - dz, min] {
+            if quad::spans_plane(dy, dz) {
+                sides.push(Quad::new(corner, dy, dz, material.clone()));
+            }
+        }
+        assert!(!sides.is_empty(), "the corners of a box must span at least a plane");
 
-        sides.push(AARect::new(Plane::XY, min.x(), max.x(), min.y(), max.y(), max.z(), material.clone()));
-        sides.push(AARect::new(Plane::XY, min.x(), max.x(), min.y(), max.y(), min.z(), material.clone()));
+        Cube {
+            bbox: sides.bounding_box(0.0, 1.0).unwrap(),
+            sides
+        }
+    }
 
-        sides.push(AARect::new(Plane::XZ, min.x(), max.x(), min.z(), max.z(), max.y(), material.clone()));
-        sides.push(AARect::new(Plane::XZ, min.x(), max.x(), min.z(), max.z(), min.y(), material.clone()));
+    /// box with the corner p spanned by the edges a, b and c, which can point anywhere, made of
+    /// six quads with their normals pointing out. sides without area are left out, so edges
+    /// that only span a plane give a flat panel
+    pub fn oriented<M: Material + Clone + 'static>(p: Point3, a: Vec3, b: Vec3, c: Vec3, material: M) -> Cube {
+        // with a left handed set of edges every face would point inwards
+        let (a, b) = if a.cross(b).dot(c) < 0.0 { (b, a) } else { (a, b) };
 
-        sides.push(AARect::new(Plane::YZ, min.y(), max.y(), min.z(), max.z(), max.x(), material.clone()));
-        sides.push(AARect::new(Plane::YZ, min.y(), max.y(), min.z(), max.z(), min.x(), material));
+        let mut sides = HittableList::default();
+        for (q, u, v) in [(p, b, a), (p + c, a, b), (p, a, c), (p + b, c, a), (p, c, b), (p + a, b, c)] {
+            if quad::spans_plane(u, v) {
+                sides.push(Quad::new(q, u, v, material.clone()));
+            }
+        }
+        assert!(!sides.is_empty(), "the edges of a box must span at least a plane");
 
         Cube {
-            min,
-            max,
+            bbox: sides.bounding_box(0.0, 1.0).unwrap(),
             sides
         }
     }
 }
 
 impl Hittable for Cube {
-    fn hit(&self, r: &crate::ray::Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
-        self.sides.hit(r, t_min, t_max)
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rng: &mut Sampler) -> Option<HitRecord> {
+        self.sides.hit(r, t_min, t_max, rng)
     }
 
     fn bounding_box(&self, _t0: f64, _t1: f64) -> Option<AABB> {
-        Some(
-            AABB {
-                min: self.min,
-                max: self.max
+        Some(self.bbox)
+    }
+
+    fn pdf_value(&self, o: Point3, v: Vec3, rng: &mut Sampler) -> f64 {
+        self.sides.pdf_value(o, v, rng)
+    }
+
+    fn random(&self, o: Vec3, rng: &mut Sampler) -> Vec3 {
+        self.sides.random(o, rng)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::{grey, hit};
+    use super::*;
+
+    fn side(cube: &Cube, origin: Point3, direction: Vec3) -> Option<(f64, bool)> {
+        hit(cube, origin, direction).map(|rec| (rec.t, rec.front_face))
+    }
+
+    #[test]
+    fn box_without_thickness() {
+        let down = Vec3::new(0.0, -1.0, 0.0);
+        let flat = Cube::new(Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 0.0, 1.0), grey());
+        assert_eq!(side(&flat, Point3::new(0.5, 1.0, 0.5), down), Some((1.0, true)));
+        let panel = Cube::oriented(Point3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 1.0), grey());
+        assert_eq!(side(&panel, Point3::new(0.5, 1.0, 0.5), down).map(|(t, _)| t), Some(1.0));
+    }
+
+    #[test]
+    fn faces_of_new_keep_the_rectangle_normals() {
+        let cube = Cube::new(Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 1.0, 1.0), grey());
+        // the max sides face out and the min sides face in, as with the rectangles
+        for axis in 0..3 {
+            let mut direction = Vec3::new(0.0, 0.0, 0.0);
+            direction[axis] = 1.0;
+            let center = Point3::new(0.5, 0.5, 0.5);
+            assert_eq!(side(&cube, center + 1.5 * direction, (-1.0) * direction), Some((1.0, true)));
+            assert_eq!(side(&cube, center - 1.5 * direction, direction), Some((1.0, false)));
+        }
+        // and u, v run along the axes of the rectangle, x then z on the top
+        let rec = hit(&cube, Point3::new(0.25, 2.0, 0.75), Vec3::new(0.0, -1.0, 0.0)).unwrap();
+        assert!((rec.u - 0.25).abs() < 1e-12 && (rec.v - 0.75).abs() < 1e-12);
+    }
+
+    #[test]
+    fn oriented_faces_point_out() {
+        // a left handed set of edges gives the same box
+        for (a, b) in [(Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0)), (Vec3::new(0.0, 1.0, 0.0), Vec3::new(1.0, 0.0, 0.0))] {
+            let cube = Cube::oriented(Point3::new(0.0, 0.0, 0.0), a, b, Vec3::new(0.0, 0.0, 1.0), grey());
+            for axis in 0..3 {
+                for sign in [-1.0, 1.0] {
+                    let mut origin = Point3::new(0.5, 0.5, 0.5);
+                    origin[axis] += 1.5 * sign;
+                    let mut direction = Vec3::new(0.0, 0.0, 0.0);
+                    direction[axis] = -sign;
+                    assert_eq!(side(&cube, origin, direction), Some((1.0, true)));
+                    // and from inside every side is seen from the back
+                    assert_eq!(side(&cube, Point3::new(0.5, 0.5, 0.5), (-1.0) * direction), Some((0.5, false)));
+                }
             }
-        )
+        }
     }
 }
@@ -0,0 +1,329 @@
+use std::f64;
+use super::vec::{Vec3, Point3};
+use super::ray::Ray;
+use super::sampler::Sampler;
+use super::hit::{Hittable, HitRecord};
+use super::mat::Material;
+use super::aabb::AABB;
+use super::onb::ONB;
+
+/// how a curve shows its width
+#[derive(Clone, Copy, PartialEq)]
+pub enum CurveKind {
+    /// a flat strip that always turns to face the ray
+    Flat,
+    /// the same strip shaded as a round tube, for hair and fur
+    Cylinder
+}
+
+/// how the control points of a strand make up its cubic segments
+#[derive(Clone, Copy, PartialEq)]
+pub enum Basis {
+    /// 3n + 1 points for n segments, every segment starts where the one before ends
+    Bezier,
+    /// uniform b-spline, n + 3 points for n segments, smooth where segments meet
+    BSpline
+}
+
+// the point and the derivative of a cubic bezier at u
+fn evaluate(p: &[Vec3; 4], u: f64) -> (Vec3, Vec3) {
+    let a = [(1.0 - u) * p[0] + u * p[1], (1.0 - u) * p[1] + u * p[2], (1.0 - u) * p[2] + u * p[3]];
+    let b = [(1.0 - u) * a[0] + u * a[1], (1.0 - u) * a[1] + u * a[2]];
+    ((1.0 - u) * b[0] + u * b[1], 3.0 * (b[1] - b[0]))
+}
+
+// the two halves of a cubic bezier
+fn split(p: &[Vec3; 4]) -> ([Vec3; 4], [Vec3; 4]) {
+    let a = [0.5 * (p[0] + p[1]), 0.5 * (p[1] + p[2]), 0.5 * (p[2] + p[3])];
+    let b = [0.5 * (a[0] + a[1]), 0.5 * (a[1] + a[2])];
+    let middle = 0.5 * (b[0] + b[1]);
+    ([p[0], a[0], b[0], middle], [middle, b[1], a[2], p[3]])
+}
+
+// the closest hit found so far, in the frame of the ray
+struct Crossing {
+    z: f64,
+    u: f64,
+    v: f64,
+    center: Vec3
+}
+
+/// one cubic bezier segment swept with a width that changes linearly along it
+///
+/// curves are open surfaces seen from both sides, see `strand` to build hair from many of them
+pub struct Curve<M: Material> {
+    points: [Point3; 4],
+    // full widths at the two ends
+    widths: (f64, f64),
+    // the part of the strand the segment covers, u goes over it
+    u_range: (f64, f64),
+    kind: CurveKind,
+    material: M
+}
+
+impl<M: Material> Curve<M> {
+    pub fn new(points: [Point3; 4], width0: f64, width1: f64, material: M) -> Curve<M> {
+        assert!(width0 >= 0.0 && width1 >= 0.0 && width0 + width1 > 0.0, "a curve needs a positive width");
+        Curve {
+            points,
+            widths: (width0, width1),
+            u_range: (0.0, 1.0),
+            kind: CurveKind::Cylinder,
+            material
+        }
+    }
+
+    pub fn with_kind(mut self, kind: CurveKind) -> Curve<M> {
+        self.kind = kind;
+        self
+    }
+
+    fn with_u_range(mut self, u0: f64, u1: f64) -> Curve<M> {
+        self.u_range = (u0, u1);
+        self
+    }
+
+    fn width(&self, u: f64) -> f64 {
+        (1.0 - u) * self.widths.0 + u * self.widths.1
+    }
+
+    // subdivides the piece of the curve between u0 and u1 while its box holds the ray, which runs
+    // along z through the origin, and tests the ray against the pieces as straight segments
+    fn intersect(&self, cp: &[Vec3; 4], (u0, u1): (f64, f64), depth: u32, z_min: f64, closest: &mut Option<Crossing>, z_max: &mut f64) {
+        let half_width = 0.5 * self.width(u0).max(self.width(u1));
+        for a in 0..3 {
+            let lo = cp.iter().map(|p| p[a]).fold(f64::INFINITY, f64::min) - half_width;
+            let hi = cp.iter().map(|p| p[a]).fold(f64::NEG_INFINITY, f64::max) + half_width;
+            let (range_lo, range_hi) = if a == 2 { (z_min, *z_max) } else { (0.0, 0.0) };
+            if lo > range_hi || hi < range_lo {
+                return
+            }
+        }
+
+        if depth > 0 {
+            let (first, second) = split(cp);
+            let middle = 0.5 * (u0 + u1);
+            self.intersect(&first, (u0, middle), depth - 1, z_min, closest, z_max);
+            self.intersect(&second, (middle, u1), depth - 1, z_min, closest, z_max);
+            return
+        }
+
+        // the ray has to pass between the lines through the ends, perpendicular to the curve
+        if (cp[1].y() - cp[0].y()) * -cp[0].y() + cp[0].x() * (cp[0].x() - cp[1].x()) < 0.0
+            || (cp[2].y() - cp[3].y()) * -cp[3].y() + cp[3].x() * (cp[3].x() - cp[2].x()) < 0.0 {
+            return
+        }
+
+        // the closest point of the segment to the ray
+        let segment = Vec3::new(cp[3].x() - cp[0].x(), cp[3].y() - cp[0].y(), 0.0);
+        let denominator = segment.dot(segment);
+        if denominator == 0.0 {
+            return
+        }
+        let w = (-(cp[0].x() * segment.x() + cp[0].y() * segment.y()) / denominator).clamp(0.0, 1.0);
+        let (center, derivative) = evaluate(cp, w);
+        let u = u0 + w * (u1 - u0);
+        let width = self.width(u);
+        let distance_squared = center.x() * center.x() + center.y() * center.y();
+        if distance_squared > 0.25 * width * width || center.z() < z_min || center.z() > *z_max {
+            return
+        }
+
+        // v runs across the width, 0.5 on the center line
+        let distance = distance_squared.sqrt();
+        let side = derivative.x() * -center.y() + center.x() * derivative.y();
+        let v = if side > 0.0 { 0.5 + distance / width } else { 0.5 - distance / width };
+        *z_max = center.z();
+        *closest = Some(Crossing { z: center.z(), u, v, center });
+    }
+}
+
+impl<M: Material> Hittable for Curve<M> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, _rng: &mut Sampler) -> Option<HitRecord<'_>> {
+        // a frame with the ray along z and x along the curve, so the boxes of the pieces are narrow
+        let length = r.direction().length();
+        let direction = r.direction() / length;
+        let chord = self.points[3] - self.points[0];
+        let across = chord - chord.dot(direction) * direction;
+        let x = if across.length() > 1.0e-9 * chord.length() {
+            across.normalized()
+        } else {
+            ONB::build_from_w(&direction).u()
+        };
+        let y = direction.cross(x);
+        let cp = self.points.map(|p| {
+            let d = p - r.origin();
+            Vec3::new(d.dot(x), d.dot(y), d.dot(direction))
+        });
+
+        // split until the pieces are straight to within a twentieth of the width
+        let curvature = (0..2).map(|i| {
+            let d = cp[i] - 2.0 * cp[i + 1] + cp[i + 2];
+            d.x().abs().max(d.y().abs()).max(d.z().abs())
+        }).fold(0.0, f64::max);
+        let tolerance = 0.05 * self.widths.0.max(self.widths.1);
+        let depth = if curvature > 0.0 {
+            ((f64::consts::SQRT_2 * 6.0 * curvature / (8.0 * tolerance)).log2() / 2.0).round().clamp(0.0, 10.0) as u32
+        } else {
+            0
+        };
+
+        let mut closest = None;
+        let mut z_max = t_max * length;
+        self.intersect(&cp, (0.0, 1.0), depth, t_min * length, &mut closest, &mut z_max);
+        let crossing = closest?;
+
+        let t = crossing.z / length;
+        let tangent = evaluate(&self.points, crossing.u).1;
+        // the strip faces the ray as far as the direction of the curve allows
+        let unit_tangent = tangent.normalized();
+        let facing = direction - direction.dot(unit_tangent) * unit_tangent;
+        let flat_normal = if facing.length() > 1.0e-9 { (-1.0) * facing.normalized() } else { (-1.0) * direction };
+        let outward_normal = match self.kind {
+            CurveKind::Flat => flat_normal,
+            CurveKind::Cylinder => {
+                // bend the normal across the width like that of a tube around the center line
+                let offset = (-1.0) * (crossing.center.x() * x + crossing.center.y() * y);
+                let sideways = offset - offset.dot(unit_tangent) * unit_tangent - offset.dot(flat_normal) * flat_normal;
+                let s = (2.0 * (crossing.v - 0.5)).abs().min(1.0);
+                if sideways.length() > 0.0 {
+                    (1.0 - s * s).sqrt() * flat_normal + s * sideways.normalized()
+                } else {
+                    flat_normal
+                }
+            }
+        };
+
+        let mut rec = HitRecord {
+            position: r.at(t),
+            normal: outward_normal,
+            t,
+            u: self.u_range.0 + crossing.u * (self.u_range.1 - self.u_range.0),
+            v: crossing.v,
+            tangent,
+            front_face: false,
+            material: &self.material
+        };
+        rec.set_face_normal(r, outward_normal);
+        Some(rec)
+    }
+
+    fn bounding_box(&self, _t0: f64, _t1: f64) -> Option<AABB> {
+        // the curve stays within the hull of its control points
+        let half_width = 0.5 * self.widths.0.max(self.widths.1);
+        let mut min = self.points[0];
+        let mut max = self.points[0];
+        for p in &self.points[1..] {
+            for a in 0..3 {
+                min[a] = min[a].min(p[a]);
+                max[a] = max[a].max(p[a]);
+            }
+        }
+        let pad = Vec3::new(half_width, half_width, half_width);
+        Some(AABB::new(min - pad, max + pad))
+    }
+}
+
+/// the segments of a strand of hair, with the width going linearly from `widths.0` at the root
+/// to `widths.1` at the tip, each with its own box so they can go into a `BVH`
+pub fn strand<M: Material + Clone>(points: &[Point3], basis: Basis, widths: (f64, f64), kind: CurveKind, material: M) -> Vec<Curve<M>> {
+    let segments: Vec<[Point3; 4]> = match basis {
+        Basis::Bezier => {
+            assert!(points.len() >= 4 && (points.len() - 1).is_multiple_of(3), "a bezier strand needs 3n + 1 points");
+            points.windows(4).step_by(3).map(|w| [w[0], w[1], w[2], w[3]]).collect()
+        },
+        Basis::BSpline => {
+            assert!(points.len() >= 4, "a b-spline strand needs at least 4 points");
+            points.windows(4).map(|b| [
+                (b[0] + 4.0 * b[1] + b[2]) / 6.0,
+                (2.0 * b[1] + b[2]) / 3.0,
+                (b[1] + 2.0 * b[2]) / 3.0,
+                (b[1] + 4.0 * b[2] + b[3]) / 6.0
+            ]).collect()
+        }
+    };
+
+    let n = segments.len() as f64;
+    let width = |u: f64| (1.0 - u) * widths.0 + u * widths.1;
+    segments.into_iter().enumerate().map(|(k, p)| {
+        let (u0, u1) = (k as f64 / n, (k + 1) as f64 / n);
+        Curve::new(p, width(u0), width(u1), material.clone())
+            .with_kind(kind)
+            .with_u_range(u0, u1)
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::{grey, hit, Grey};
+    use super::*;
+
+    // straight down onto the curve from y = 2
+    fn down_at(curve: &Curve<Grey>, x: f64, z: f64) -> Option<(f64, f64, f64)> {
+        hit(curve, Point3::new(x, 2.0, z), Vec3::new(0.0, -1.0, 0.0)).map(|rec| (rec.t, rec.u, rec.v))
+    }
+
+    // a straight curve along x from -1 to 1 with evenly spaced control points
+    fn straight(width0: f64, width1: f64) -> Curve<Grey> {
+        let points = [-1.0, -1.0 / 3.0, 1.0 / 3.0, 1.0].map(|x| Point3::new(x, 0.0, 0.0));
+        Curve::new(points, width0, width1, grey())
+    }
+
+    #[test]
+    fn straight_curve_across_its_width() {
+        let curve = straight(0.2, 0.2);
+        let (t, u, v) = down_at(&curve, 0.3, 0.0).unwrap();
+        assert!((t - 2.0).abs() < 1e-9);
+        assert!((u - 0.65).abs() < 1e-9);
+        assert!((v - 0.5).abs() < 1e-9);
+        // v goes from one edge to the other
+        let (_, _, v0) = down_at(&curve, 0.3, 0.05).unwrap();
+        let (_, _, v1) = down_at(&curve, 0.3, -0.05).unwrap();
+        assert!(((v0 - 0.5).abs() - 0.25).abs() < 1e-9 && ((v1 - 0.5).abs() - 0.25).abs() < 1e-9 && (v0 - v1).abs() > 0.4);
+        assert!(down_at(&curve, 0.3, 0.11).is_none());
+        assert!(down_at(&curve, 1.05, 0.0).is_none());
+    }
+
+    #[test]
+    fn width_tapers_along_the_curve() {
+        // 0.4 wide at x = -1 and 0 at x = 1, so 0.1 wide at x = 0.5
+        let curve = straight(0.4, 0.0);
+        assert!(down_at(&curve, -0.9, 0.15).is_some());
+        assert!(down_at(&curve, 0.5, 0.04).is_some());
+        assert!(down_at(&curve, 0.5, 0.06).is_none());
+    }
+
+    #[test]
+    fn bent_curve_is_hit_on_its_center_line() {
+        let points = [Point3::new(-1.0, 0.0, 0.0), Point3::new(-0.5, 0.0, 1.5), Point3::new(0.5, 0.0, -1.5), Point3::new(1.0, 0.0, 0.5)];
+        let curve = Curve::new(points, 0.05, 0.05, grey()).with_kind(CurveKind::Flat);
+        for k in 1..10 {
+            let (center, _) = evaluate(&points, k as f64 / 10.0);
+            let (t, u, _) = down_at(&curve, center.x(), center.z()).unwrap();
+            assert!((t - 2.0).abs() < 1e-6);
+            // the pieces are straight lines, so u is only as exact as the subdivision
+            assert!((u - k as f64 / 10.0).abs() < 0.05, "u {} at {}", u, k);
+        }
+    }
+
+    #[test]
+    fn strand_segments_join() {
+        let points: Vec<Point3> = (0..7).map(|i| Point3::new(i as f64, (i as f64).sin(), 0.1 * (i * i) as f64)).collect();
+        for basis in [Basis::Bezier, Basis::BSpline] {
+            let segments = strand(&points, basis, (0.1, 0.02), CurveKind::Cylinder, grey());
+            assert_eq!(segments.len(), if basis == Basis::Bezier { 2 } else { 4 });
+            for pair in segments.windows(2) {
+                assert!((pair[0].points[3] - pair[1].points[0]).length() < 1e-12);
+                assert!((pair[0].widths.1 - pair[1].widths.0).abs() < 1e-12);
+                assert!((pair[0].u_range.1 - pair[1].u_range.0).abs() < 1e-12);
+            }
+            if basis == Basis::BSpline {
+                // smooth where the segments meet
+                for pair in segments.windows(2) {
+                    let (a, b) = (evaluate(&pair[0].points, 1.0).1, evaluate(&pair[1].points, 0.0).1);
+                    assert!((a - b).length() < 1e-12);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,314 @@
+use std::collections::HashMap;
+use std::f64;
+use super::vec::{Vec3, Point3};
+use super::mat::Material;
+use super::texture::Texture;
+use super::triangle::TriangleMesh;
+use super::bvh::SplitMethod;
+
+// where a point of the grid over a triangle lies: on a corner, at a step along an edge or inside
+enum GridPoint {
+    Corner(usize),
+    Edge(usize, usize, usize),
+    Inside
+}
+
+/// triangles with a normal and a uv at every vertex, the geometry that displacement works on
+#[derive(Clone)]
+pub struct Tessellation {
+    positions: Vec<Point3>,
+    normals: Vec<Vec3>,
+    uvs: Vec<(f64, f64)>,
+    indices: Vec<[usize; 3]>
+}
+
+/// shapes that can be cut into triangles
+pub trait Tessellate {
+    /// about `resolution` segments along each direction of the surface
+    fn tessellate(&self, resolution: usize) -> Tessellation;
+}
+
+// area weighted normals of the faces around each vertex
+fn vertex_normals(positions: &[Point3], indices: &[[usize; 3]]) -> Vec<Vec3> {
+    let mut normals = vec![Vec3::new(0.0, 0.0, 0.0); positions.len()];
+    for &[a, b, c] in indices {
+        let n = (positions[b] - positions[a]).cross(positions[c] - positions[a]);
+        for v in [a, b, c] {
+            normals[v] += n;
+        }
+    }
+    normals.into_iter().map(|n| if n.near_zero() { n } else { n.normalized() }).collect()
+}
+
+impl Tessellation {
+    /// triangles listing their vertices counterclockwise seen from the outside, missing normals
+    /// are averaged from the faces and missing uvs are 0
+    pub fn new(positions: Vec<Point3>, indices: Vec<[usize; 3]>, normals: Option<Vec<Vec3>>, uvs: Option<Vec<(f64, f64)>>) -> Tessellation {
+        assert!(indices.iter().flatten().all(|&i| i < positions.len()), "tessellation index out of range");
+        let normals = normals.unwrap_or_else(|| vertex_normals(&positions, &indices));
+        let uvs = uvs.unwrap_or_else(|| vec![(0.0, 0.0); positions.len()]);
+        assert_eq!(normals.len(), positions.len(), "a tessellation needs one normal per vertex");
+        assert_eq!(uvs.len(), positions.len(), "a tessellation needs one uv per vertex");
+        Tessellation { positions, normals, uvs, indices }
+    }
+
+    pub fn triangles(&self) -> usize {
+        self.indices.len()
+    }
+
+    /// a latitude longitude grid with `resolution` rows and twice as many columns, with the uvs
+    /// of `Sphere`
+    pub fn sphere(center: Point3, radius: f64, resolution: usize) -> Tessellation {
+        assert!(resolution >= 2, "a sphere needs at least 2 rows");
+        let (columns, rows) = (2 * resolution, resolution);
+        let mut positions = Vec::with_capacity((columns + 1) * (rows + 1));
+        let mut normals = Vec::with_capacity((columns + 1) * (rows + 1));
+        let mut uvs = Vec::with_capacity((columns + 1) * (rows + 1));
+        for j in 0..=rows {
+            for i in 0..=columns {
+                // the last column and the poles are put exactly where the first column and the axis are
+                let phi = 2.0 * f64::consts::PI * (i % columns) as f64 / columns as f64;
+                let theta = f64::consts::PI * j as f64 / rows as f64;
+                let n = match j {
+                    0 => Vec3::new(0.0, -1.0, 0.0),
+                    _ if j == rows => Vec3::new(0.0, 1.0, 0.0),
+                    _ => Vec3::new(-phi.cos() * theta.sin(), -theta.cos(), phi.sin() * theta.sin())
+                };
+                positions.push(center + radius * n);
+                normals.push(n);
+                uvs.push((i as f64 / columns as f64, j as f64 / rows as f64));
+            }
+        }
+        // the triangles touching a pole with two corners are left out
+        let vertex = |i: usize, j: usize| j * (columns + 1) + i;
+        let mut indices = Vec::with_capacity(2 * columns * rows);
+        for j in 0..rows {
+            for i in 0..columns {
+                if j > 0 {
+                    indices.push([vertex(i, j), vertex(i + 1, j), vertex(i + 1, j + 1)]);
+                }
+                if j + 1 < rows {
+                    indices.push([vertex(i, j), vertex(i + 1, j + 1), vertex(i, j + 1)]);
+                }
+            }
+        }
+        Tessellation { positions, normals, uvs, indices }
+    }
+
+    /// a `resolution` x `resolution` grid over the parallelogram of `Quad`, with its uvs
+    pub fn quad(q: Point3, u: Vec3, v: Vec3, resolution: usize) -> Tessellation {
+        assert!(resolution >= 1, "a quad needs at least one segment along each edge");
+        let normal = u.cross(v).normalized();
+        let n = resolution;
+        let mut positions = Vec::with_capacity((n + 1) * (n + 1));
+        let mut uvs = Vec::with_capacity((n + 1) * (n + 1));
+        for j in 0..=n {
+            for i in 0..=n {
+                let (a, b) = (i as f64 / n as f64, j as f64 / n as f64);
+                positions.push(q + a * u + b * v);
+                uvs.push((a, b));
+            }
+        }
+        let vertex = |i: usize, j: usize| j * (n + 1) + i;
+        let mut indices = Vec::with_capacity(2 * n * n);
+        for j in 0..n {
+            for i in 0..n {
+                indices.push([vertex(i, j), vertex(i + 1, j), vertex(i + 1, j + 1)]);
+                indices.push([vertex(i, j), vertex(i + 1, j + 1), vertex(i, j + 1)]);
+            }
+        }
+        Tessellation { normals: vec![normal; positions.len()], positions, uvs, indices }
+    }
+
+    /// splits every edge into `segments` and every triangle into segments^2, interpolating the
+    /// vertices linearly. neighbouring triangles share the new vertices on their common edge
+    pub fn refined(&self, segments: usize) -> Tessellation {
+        assert!(segments >= 1, "a triangle needs at least one segment per edge");
+        if segments == 1 {
+            return self.clone()
+        }
+        let n = segments;
+        let mut refined = Tessellation {
+            positions: self.positions.clone(),
+            normals: self.normals.clone(),
+            uvs: self.uvs.clone(),
+            indices: Vec::with_capacity(self.indices.len() * n * n)
+        };
+        let mut edge_vertices: HashMap<(usize, usize, usize), usize> = HashMap::new();
+
+        // a new vertex from weights of old ones
+        let push = |refined: &mut Tessellation, weights: &[(usize, f64)]| {
+            let mut p = Vec3::new(0.0, 0.0, 0.0);
+            let mut normal = Vec3::new(0.0, 0.0, 0.0);
+            let mut uv = (0.0, 0.0);
+            for &(v, w) in weights {
+                p += w * self.positions[v];
+                normal += w * self.normals[v];
+                uv = (uv.0 + w * self.uvs[v].0, uv.1 + w * self.uvs[v].1);
+            }
+            refined.positions.push(p);
+            refined.normals.push(if normal.near_zero() { normal } else { normal.normalized() });
+            refined.uvs.push(uv);
+            refined.positions.len() - 1
+        };
+
+        for &[a, b, c] in &self.indices {
+            // the grid point i steps towards b and j steps towards c from a
+            let mut grid = Vec::with_capacity(n + 1);
+            for i in 0..=n {
+                let mut row = Vec::with_capacity(n + 1 - i);
+                for j in 0..=n - i {
+                    let point = match (i, j) {
+                        (0, 0) => GridPoint::Corner(a),
+                        _ if i == n => GridPoint::Corner(b),
+                        _ if j == n => GridPoint::Corner(c),
+                        (_, 0) => GridPoint::Edge(a, b, i),
+                        (0, _) => GridPoint::Edge(a, c, j),
+                        _ if i + j == n => GridPoint::Edge(b, c, j),
+                        _ => GridPoint::Inside
+                    };
+                    row.push(match point {
+                        GridPoint::Corner(v) => v,
+                        // points on an edge are made once, counting from its lower numbered end
+                        GridPoint::Edge(from, to, step) => {
+                            let key = if from < to { (from, to, step) } else { (to, from, n - step) };
+                            match edge_vertices.get(&key) {
+                                Some(&v) => v,
+                                None => {
+                                    let w = key.2 as f64 / n as f64;
+                                    let v = push(&mut refined, &[(key.0, 1.0 - w), (key.1, w)]);
+                                    edge_vertices.insert(key, v);
+                                    v
+                                }
+                            }
+                        },
+                        GridPoint::Inside => {
+                            let (wb, wc) = (i as f64 / n as f64, j as f64 / n as f64);
+                            push(&mut refined, &[(a, 1.0 - wb - wc), (b, wb), (c, wc)])
+                        }
+                    });
+                }
+                grid.push(row);
+            }
+            for i in 0..n {
+                for j in 0..n - i {
+                    refined.indices.push([grid[i][j], grid[i + 1][j], grid[i][j + 1]]);
+                    if i + j + 1 < n {
+                        refined.indices.push([grid[i + 1][j], grid[i + 1][j + 1], grid[i][j + 1]]);
+                    }
+                }
+            }
+        }
+        refined
+    }
+
+    /// moves every vertex along its normal by `scale` times the brightness of the texture there,
+    /// then shades the new surface with the normals of its own faces. vertices at the same place,
+    /// like those on a uv seam or a hard edge, move together so the surface does not tear
+    pub fn displaced(&self, texture: &dyn Texture, scale: f64) -> Tessellation {
+        // adding 0 turns -0 into 0 so both give the same key
+        let key = |p: Point3| [(p.x() + 0.0).to_bits(), (p.y() + 0.0).to_bits(), (p.z() + 0.0).to_bits()];
+        let mut shared: HashMap<[u64; 3], (f64, Vec3, usize)> = HashMap::new();
+        for v in 0..self.positions.len() {
+            let (u, w) = self.uvs[v];
+            let color = texture.mapping(u, w, &self.positions[v]);
+            let entry = shared.entry(key(self.positions[v])).or_insert((0.0, Vec3::new(0.0, 0.0, 0.0), 0));
+            entry.0 += (color.x() + color.y() + color.z()) / 3.0;
+            entry.1 += self.normals[v];
+            entry.2 += 1;
+        }
+        let positions: Vec<Point3> = self.positions.iter().map(|&p| {
+            let (height, normal, count) = shared[&key(p)];
+            if normal.near_zero() {
+                p
+            } else {
+                p + scale * height / count as f64 * normal.normalized()
+            }
+        }).collect();
+        Tessellation {
+            normals: vertex_normals(&positions, &self.indices),
+            positions,
+            uvs: self.uvs.clone(),
+            indices: self.indices.clone()
+        }
+    }
+
+    /// the triangles with their own bvh
+    pub fn into_mesh<M: Material + 'static>(self, material: M, split: SplitMethod) -> TriangleMesh {
+        TriangleMesh::with_split(self.positions, self.indices, Some(self.normals), Some(self.uvs), material, split)
+    }
+}
+
+impl Tessellate for Tessellation {
+    /// the triangles refined with `resolution` segments per edge
+    fn tessellate(&self, resolution: usize) -> Tessellation {
+        self.refined(resolution)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::texture::ConstantTexture;
+    use crate::vec::Color;
+    use super::*;
+
+    // brightness u, different on the two sides of the seam of a sphere
+    struct Stripes;
+
+    impl Texture for Stripes {
+        fn mapping(&self, u: f64, _v: f64, _p: &Vec3) -> Color {
+            Color::new(u, u, u)
+        }
+    }
+
+    // two triangles sharing the diagonal of the unit square
+    fn square() -> Tessellation {
+        let positions = vec![Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 0.0, 0.0), Point3::new(1.0, 1.0, 0.0), Point3::new(0.0, 1.0, 0.0)];
+        Tessellation::new(positions, vec![[0, 1, 2], [0, 2, 3]], None, None)
+    }
+
+    #[test]
+    fn refined_shares_edge_vertices() {
+        for n in 1..6 {
+            let refined = square().refined(n);
+            assert_eq!(refined.triangles(), 2 * n * n);
+            // an n x n grid of squares, the points on the diagonal are made once
+            assert_eq!(refined.positions.len(), (n + 1) * (n + 1));
+            // and every vertex is used
+            let mut used = vec![false; refined.positions.len()];
+            refined.indices.iter().flatten().for_each(|&v| used[v] = true);
+            assert!(used.iter().all(|&u| u));
+        }
+    }
+
+    #[test]
+    fn constant_texture_moves_a_sphere_out() {
+        let center = Point3::new(1.0, -2.0, 0.5);
+        let sphere = Tessellation::sphere(center, 2.0, 8);
+        let displaced = sphere.displaced(&ConstantTexture::new(Color::new(1.0, 1.0, 1.0)), 0.25);
+        assert_eq!(displaced.triangles(), sphere.triangles());
+        for p in &displaced.positions {
+            assert!(((*p - center).length() - 2.25).abs() < 1e-12);
+        }
+        // the faces still point away from the center
+        for &v in displaced.indices.iter().flatten() {
+            let (p, n) = (displaced.positions[v], displaced.normals[v]);
+            assert!(n.dot((p - center).normalized()) > 0.9, "{} at {}", n, p - center);
+        }
+    }
+
+    #[test]
+    fn seam_moves_together() {
+        let (rows, columns) = (6, 12);
+        let displaced = Tessellation::sphere(Point3::new(0.0, 0.0, 0.0), 1.0, rows).displaced(&Stripes, 0.5);
+        let vertex = |i: usize, j: usize| displaced.positions[j * (columns + 1) + i];
+        for j in 0..=rows {
+            // u is 0 on the first column and 1 on the last, which is at the same place
+            let (first, last) = (vertex(0, j), vertex(columns, j));
+            assert_eq!((first.x(), first.y(), first.z()), (last.x(), last.y(), last.z()), "row {}", j);
+        }
+        // and by the mean height of both sides
+        let p = vertex(0, rows / 2);
+        assert!((p.length() - 1.25).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,206 @@
+use std::io::{self, Cursor, Write};
+use std::path::Path;
+use image::{DynamicImage, ImageBuffer, ImageOutputFormat, ImageResult, Rgb};
+use image::codecs::hdr::HdrEncoder;
+use super::vec::Color;
+use super::tonemap::ToneMapper;
+
+/// file formats a framebuffer can be written as
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ImageFormat {
+    /// ascii ppm, 8 bits per channel
+    Ppm,
+    Png8,
+    Png16,
+    /// radiance rgbe, linear radiance after white balance and exposure
+    Hdr,
+    /// openexr with 32 bit float channels, linear radiance after white balance and exposure
+    Exr
+}
+
+impl ImageFormat {
+    pub const NAMES: [&'static str; 5] = ["ppm", "png", "png16", "hdr", "exr"];
+
+    pub fn from_name(name: &str) -> Option<ImageFormat> {
+        match name.to_ascii_lowercase().as_str() {
+            "ppm" => Some(ImageFormat::Ppm),
+            "png" | "png8" => Some(ImageFormat::Png8),
+            "png16" => Some(ImageFormat::Png16),
+            "hdr" => Some(ImageFormat::Hdr),
+            "exr" => Some(ImageFormat::Exr),
+            _ => None
+        }
+    }
+
+    /// the format matching the extension of a file name, .png files are 8 bit
+    pub fn from_path(path: &Path) -> Option<ImageFormat> {
+        ImageFormat::from_name(path.extension()?.to_str()?)
+    }
+}
+
+fn quantize(value: f64, levels: f64) -> f64 {
+    (levels * value).min(levels - 1.0)
+}
+
+/// linear rgb radiance of every pixel, rows are stored from top to bottom
+#[derive(Clone)]
+pub struct Framebuffer {
+    width: u64,
+    height: u64,
+    pixels: Vec<Color>
+}
+
+impl Framebuffer {
+    /// a black image
+    pub fn new(width: u64, height: u64) -> Framebuffer {
+        Framebuffer {
+            width,
+            height,
+            pixels: vec![Color::default(); (width * height) as usize]
+        }
+    }
+
+    pub fn width(&self) -> u64 {
+        self.width
+    }
+
+    pub fn height(&self) -> u64 {
+        self.height
+    }
+
+    pub fn get(&self, x: u64, y: u64) -> Color {
+        self.pixels[(y * self.width + x) as usize]
+    }
+
+    pub fn set(&mut self, x: u64, y: u64, color: Color) {
+        self.pixels[(y * self.width + x) as usize] = color;
+    }
+
+    pub fn pixels(&self) -> &[Color] {
+        &self.pixels
+    }
+
+    /// ascii ppm
+    pub fn write_ppm(&self, out: &mut impl Write, tone: &ToneMapper) -> io::Result<()> {
+        writeln!(out, "P3")?;
+        writeln!(out, "{} {}", self.width, self.height)?;
+        writeln!(out, "255")?;
+        for pixel in &self.pixels {
+            let c = tone.display(*pixel);
+            writeln!(out, "{} {} {}", quantize(c[0], 256.0) as u8, quantize(c[1], 256.0) as u8, quantize(c[2], 256.0) as u8)?;
+        }
+        Ok(())
+    }
+
+    /// tone mapped 8 bit rgb
+    pub fn to_rgb8(&self, tone: &ToneMapper) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
+        ImageBuffer::from_fn(self.width as u32, self.height as u32, |x, y| {
+            let c = tone.display(self.get(x as u64, y as u64));
+            Rgb([0, 1, 2].map(|i| quantize(c[i], 256.0) as u8))
+        })
+    }
+
+    /// tone mapped 16 bit rgb
+    pub fn to_rgb16(&self, tone: &ToneMapper) -> ImageBuffer<Rgb<u16>, Vec<u16>> {
+        ImageBuffer::from_fn(self.width as u32, self.height as u32, |x, y| {
+            let c = tone.display(self.get(x as u64, y as u64));
+            Rgb([0, 1, 2].map(|i| quantize(c[i], 65536.0) as u16))
+        })
+    }
+
+    /// linear rgb as 32 bit floats, only white balanced and exposed
+    pub fn to_rgb32f(&self, tone: &ToneMapper) -> ImageBuffer<Rgb<f32>, Vec<f32>> {
+        ImageBuffer::from_fn(self.width as u32, self.height as u32, |x, y| {
+            let c = tone.linear(self.get(x as u64, y as u64));
+            Rgb([c[0] as f32, c[1] as f32, c[2] as f32])
+        })
+    }
+
+    pub fn write(&self, out: &mut impl Write, format: ImageFormat, tone: &ToneMapper) -> ImageResult<()> {
+        // the encoders may need to seek, so the file is assembled in memory first
+        let mut bytes = Cursor::new(Vec::new());
+        match format {
+            ImageFormat::Ppm => self.write_ppm(&mut bytes, tone)?,
+            ImageFormat::Png8 => DynamicImage::ImageRgb8(self.to_rgb8(tone)).write_to(&mut bytes, ImageOutputFormat::Png)?,
+            ImageFormat::Png16 => DynamicImage::ImageRgb16(self.to_rgb16(tone)).write_to(&mut bytes, ImageOutputFormat::Png)?,
+            ImageFormat::Hdr => {
+                let pixels: Vec<Rgb<f32>> = self.to_rgb32f(tone).pixels().copied().collect();
+                HdrEncoder::new(&mut bytes).encode(&pixels, self.width as usize, self.height as usize)?
+            },
+            ImageFormat::Exr => DynamicImage::ImageRgb32F(self.to_rgb32f(tone)).write_to(&mut bytes, ImageOutputFormat::OpenExr)?
+        }
+        out.write_all(bytes.get_ref())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::codecs::hdr::HdrDecoder;
+    use super::*;
+
+    fn gradient() -> Framebuffer {
+        let mut framebuffer = Framebuffer::new(3, 2);
+        for y in 0..2 {
+            for x in 0..3 {
+                framebuffer.set(x, y, Color::new(0.1 * x as f64, 0.4 * y as f64, 2.5));
+            }
+        }
+        framebuffer
+    }
+
+    #[test]
+    fn format_from_extension() {
+        let format = |name: &str| ImageFormat::from_path(Path::new(name));
+        assert_eq!(format("out/image.png"), Some(ImageFormat::Png8));
+        assert_eq!(format("image.PNG16"), Some(ImageFormat::Png16));
+        assert_eq!(format("image.hdr"), Some(ImageFormat::Hdr));
+        assert_eq!(format("image.exr"), Some(ImageFormat::Exr));
+        assert_eq!(format("image.ppm"), Some(ImageFormat::Ppm));
+        assert_eq!(format("image.jpg"), None);
+        assert_eq!(format("image"), None);
+    }
+
+    #[test]
+    fn round_trip_by_extension() {
+        let framebuffer = gradient();
+        let tone = ToneMapper::default();
+        for name in ["image.png", "image.hdr", "image.exr"] {
+            let mut bytes = Vec::new();
+            framebuffer.write(&mut bytes, ImageFormat::from_path(Path::new(name)).unwrap(), &tone).unwrap();
+            let image = image::load_from_memory(&bytes).unwrap();
+            assert_eq!((image.width(), image.height()), (3, 2), "{}", name);
+            let written = framebuffer.to_rgb32f(&tone);
+            let (read, tolerance): (Vec<Rgb<f32>>, f32) = match name {
+                // tone mapped, 8 bits
+                "image.png" => {
+                    assert_eq!(image.to_rgb8(), framebuffer.to_rgb8(&tone));
+                    continue
+                },
+                // linear radiance, rgbe keeps 8 bits of mantissa. the generic loader turns it into 8 bit
+                // display values, so the radiance is read with the hdr decoder itself
+                "image.hdr" => (HdrDecoder::new(Cursor::new(&bytes)).unwrap().read_image_hdr().unwrap(), 0.01),
+                _ => (image.to_rgb32f().pixels().copied().collect(), 0.0)
+            };
+            for (a, b) in read.iter().zip(written.pixels()) {
+                // the channels of a pixel share the exponent of the brightest one
+                let brightest = b[0].max(b[1]).max(b[2]);
+                for i in 0..3 {
+                    assert!((a[i] - b[i]).abs() <= tolerance * brightest, "{}: {} instead of {}", name, a[i], b[i]);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn ppm_header_and_values() {
+        let mut bytes = Vec::new();
+        gradient().write(&mut bytes, ImageFormat::Ppm, &ToneMapper::default()).unwrap();
+        let text = String::from_utf8(bytes).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(&lines[..3], ["P3", "3 2", "255"]);
+        assert_eq!(lines.len(), 3 + 6);
+        // black and blue clipped to 1 before the srgb curve
+        assert_eq!(lines[3], "0 0 255");
+    }
+}
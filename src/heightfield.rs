@@ -0,0 +1,262 @@
+use std::path::Path;
+use rand::Rng;
+use super::vec::{Vec3, Point3};
+use super::ray::Ray;
+use super::sampler::Sampler;
+use super::hit::{Hittable, HitRecord};
+use super::mat::Material;
+use super::aabb::AABB;
+use super::perlin::Perlin;
+use super::triangle::hit_triangle;
+
+/// heights on a grid of the given size from octaves of perlin noise, stretched to fill [0, 1].
+/// `frequency` is the number of noise features across the grid
+pub fn noise_heights(columns: usize, rows: usize, frequency: f64, octaves: usize, rng: &mut impl Rng) -> Vec<f64> {
+    let perlin = Perlin::new(rng);
+    let mut heights = Vec::with_capacity(columns * rows);
+    for j in 0..rows {
+        for i in 0..columns {
+            // off the integer lattice in y, where perlin noise is always 0
+            let mut p = Point3::new(frequency * i as f64 / columns as f64, 0.37, frequency * j as f64 / rows as f64);
+            let (mut sum, mut weight, mut total) = (0.0, 1.0, 0.0);
+            for _ in 0..octaves {
+                sum += weight * perlin.perlin(&p, 1.0);
+                total += weight;
+                weight *= 0.5;
+                p *= 2.0;
+            }
+            heights.push(sum / total);
+        }
+    }
+    let (lo, hi) = heights.iter().fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), &h| (lo.min(h), hi.max(h)));
+    let range = if hi > lo { hi - lo } else { 1.0 };
+    heights.iter().map(|h| (h - lo) / range).collect()
+}
+
+// min and max height over blocks of 2^level x 2^level cells
+struct Level {
+    columns: usize,
+    bounds: Vec<(f64, f64)>
+}
+
+/// terrain over a grid of height samples, each cell split into two smoothly shaded triangles
+///
+/// the samples span `extent.x` along x and `extent.z` along z from `min`, a height h in [0, 1]
+/// sits at min.y + h * extent.y. rays walk a min max quadtree of the cells front to back
+pub struct Heightfield<M: Material> {
+    columns: usize,
+    rows: usize,
+    // world positions, row j along z holds the samples i along x
+    positions: Vec<Point3>,
+    normals: Vec<Vec3>,
+    levels: Vec<Level>,
+    bbox: AABB,
+    // keeps the boxes of flat regions from having no thickness
+    pad: f64,
+    material: M
+}
+
+impl<M: Material> Heightfield<M> {
+    /// `heights` holds rows of `columns` samples, going along x within a row and along z from row to row
+    pub fn new(heights: Vec<f64>, columns: usize, min: Point3, extent: Vec3, material: M) -> Heightfield<M> {
+        assert!(columns >= 2 && heights.len().is_multiple_of(columns) && heights.len() / columns >= 2,
+                "a heightfield needs at least 2 x 2 samples");
+        let rows = heights.len() / columns;
+        let (dx, dz) = (extent.x() / (columns - 1) as f64, extent.z() / (rows - 1) as f64);
+        let positions: Vec<Point3> = heights.iter().enumerate().map(|(k, &h)| {
+            Point3::new(min.x() + (k % columns) as f64 * dx, min.y() + h * extent.y(), min.z() + (k / columns) as f64 * dz)
+        }).collect();
+
+        // smooth normals from central differences, one sided at the border
+        let height = |i: usize, j: usize| positions[j * columns + i].y();
+        let mut normals = Vec::with_capacity(positions.len());
+        for j in 0..rows {
+            for i in 0..columns {
+                let (i0, i1) = (i.saturating_sub(1), (i + 1).min(columns - 1));
+                let (j0, j1) = (j.saturating_sub(1), (j + 1).min(rows - 1));
+                let slope_x = (height(i1, j) - height(i0, j)) / ((i1 - i0) as f64 * dx);
+                let slope_z = (height(i, j1) - height(i, j0)) / ((j1 - j0) as f64 * dz);
+                normals.push(Vec3::new(-slope_x, 1.0, -slope_z).normalized());
+            }
+        }
+
+        let mut cells = Vec::with_capacity((columns - 1) * (rows - 1));
+        for j in 0..rows - 1 {
+            for i in 0..columns - 1 {
+                let corners = [height(i, j), height(i + 1, j), height(i, j + 1), height(i + 1, j + 1)];
+                cells.push((corners.iter().cloned().fold(f64::INFINITY, f64::min), corners.iter().cloned().fold(f64::NEG_INFINITY, f64::max)));
+            }
+        }
+        let mut levels = vec![Level { columns: columns - 1, bounds: cells }];
+        let mut level_rows = rows - 1;
+        while levels.last().unwrap().bounds.len() > 1 {
+            let below = levels.last().unwrap();
+            let (next_columns, next_rows) = (below.columns.div_ceil(2), level_rows.div_ceil(2));
+            let mut bounds = vec![(f64::INFINITY, f64::NEG_INFINITY); next_columns * next_rows];
+            for (k, &(lo, hi)) in below.bounds.iter().enumerate() {
+                let parent = &mut bounds[(k / below.columns / 2) * next_columns + (k % below.columns) / 2];
+                *parent = (parent.0.min(lo), parent.1.max(hi));
+            }
+            levels.push(Level { columns: next_columns, bounds });
+            level_rows = next_rows;
+        }
+
+        let (lo, hi) = levels.last().unwrap().bounds[0];
+        let pad = 1.0e-9 * extent.x().abs().max(extent.y().abs()).max(extent.z().abs());
+        Heightfield {
+            columns,
+            rows,
+            positions,
+            normals,
+            levels,
+            bbox: AABB::new(Point3::new(min.x(), lo - pad, min.z()), Point3::new(min.x() + extent.x(), hi + pad, min.z() + extent.z())),
+            pad,
+            material
+        }
+    }
+
+    /// heights from the brightness of a grayscale image, its top row at min.z
+    pub fn from_image(path: impl AsRef<Path>, min: Point3, extent: Vec3, material: M) -> image::ImageResult<Heightfield<M>> {
+        let image = image::open(path)?.to_luma16();
+        let heights = image.pixels().map(|p| p.0[0] as f64 / u16::MAX as f64).collect();
+        Ok(Heightfield::new(heights, image.width() as usize, min, extent, material))
+    }
+
+    // the box of the block of cells at the given level
+    fn node_box(&self, level: usize, i: usize, j: usize) -> AABB {
+        let (lo, hi) = self.levels[level].bounds[j * self.levels[level].columns + i];
+        let cells = 1 << level;
+        let corner = |i: usize, j: usize| self.positions[j.min(self.rows - 1) * self.columns + i.min(self.columns - 1)];
+        let (near, far) = (corner(i * cells, j * cells), corner((i + 1) * cells, (j + 1) * cells));
+        AABB::new(Point3::new(near.x() - self.pad, lo - self.pad, near.z() - self.pad),
+                  Point3::new(far.x() + self.pad, hi + self.pad, far.z() + self.pad))
+    }
+
+    fn hit_cell(&self, r: &Ray, t_min: f64, t_max: f64, i: usize, j: usize) -> Option<HitRecord<'_>> {
+        let k = [j * self.columns + i, (j + 1) * self.columns + i, (j + 1) * self.columns + i + 1, j * self.columns + i + 1];
+        let uv = |k: usize| ((k % self.columns) as f64 / (self.columns - 1) as f64, 1.0 - (k / self.columns) as f64 / (self.rows - 1) as f64);
+        // both triangles wind counterclockwise seen from above
+        let mut closest = t_max;
+        let mut rec = None;
+        for triangle in [[k[0], k[1], k[2]], [k[0], k[2], k[3]]] {
+            let p = triangle.map(|k| self.positions[k]);
+            let n = triangle.map(|k| self.normals[k]);
+            if let Some(hit) = hit_triangle(r, t_min, closest, &p, Some(n), Some(triangle.map(uv)), &self.material) {
+                closest = hit.t;
+                rec = Some(hit);
+            }
+        }
+        rec
+    }
+}
+
+impl<M: Material> Hittable for Heightfield<M> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, _rng: &mut Sampler) -> Option<HitRecord<'_>> {
+        let mut closest = t_max;
+        let mut rec = None;
+
+        // each level adds at most three nodes waiting behind the one taken
+        let mut stack = [(0usize, 0usize, 0usize); 256];
+        stack[0] = (self.levels.len() - 1, 0, 0);
+        let mut top = 1;
+        while top > 0 {
+            top -= 1;
+            let (level, i, j) = stack[top];
+            if !self.node_box(level, i, j).hit(r, t_min, closest) {
+                continue
+            }
+            if level == 0 {
+                if let Some(hit) = self.hit_cell(r, t_min, closest, i, j) {
+                    closest = hit.t;
+                    rec = Some(hit);
+                }
+                continue
+            }
+
+            // push the children far to near so the near one is taken first
+            let below = level - 1;
+            let (columns, rows) = (self.levels[below].columns, self.levels[below].bounds.len() / self.levels[below].columns);
+            let xs = if r.direction().x() < 0.0 { [2 * i, 2 * i + 1] } else { [2 * i + 1, 2 * i] };
+            let zs = if r.direction().z() < 0.0 { [2 * j, 2 * j + 1] } else { [2 * j + 1, 2 * j] };
+            for cj in zs {
+                for ci in xs {
+                    if ci < columns && cj < rows {
+                        stack[top] = (below, ci, cj);
+                        top += 1;
+                    }
+                }
+            }
+        }
+        rec
+    }
+
+    fn bounding_box(&self, _t0: f64, _t1: f64) -> Option<AABB> {
+        Some(self.bbox)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+    use crate::test_util::{grey, hit, Grey};
+    use super::*;
+
+    type Terrain = Heightfield<Grey>;
+
+    fn terrain(heights: Vec<f64>, columns: usize) -> Terrain {
+        Heightfield::new(heights, columns, Point3::new(-2.0, 0.0, -1.0), Vec3::new(4.0, 1.0, 3.0), grey())
+    }
+
+    // the closest hit over every cell, without the quadtree
+    fn brute_force<'a>(field: &'a Terrain, r: &Ray) -> Option<HitRecord<'a>> {
+        let mut closest = f64::INFINITY;
+        let mut rec = None;
+        for j in 0..field.rows - 1 {
+            for i in 0..field.columns - 1 {
+                if let Some(hit) = field.hit_cell(r, 0.001, closest, i, j) {
+                    closest = hit.t;
+                    rec = Some(hit);
+                }
+            }
+        }
+        rec
+    }
+
+    #[test]
+    fn quadtree_matches_brute_force() {
+        let mut rng = StdRng::seed_from_u64(3);
+        // sizes that are not powers of two leave partial blocks at the borders
+        let field = terrain(noise_heights(13, 9, 3.0, 3, &mut rng), 13);
+        for _ in 0..2000 {
+            let origin = Point3::new(rng.gen_range(-4.0..4.0), rng.gen_range(0.5..3.0), rng.gen_range(-3.0..4.0));
+            let target = Point3::new(rng.gen_range(-2.0..2.0), rng.gen_range(0.0..1.0), rng.gen_range(-1.0..2.0));
+            let r = Ray::new(origin, target - origin, 0.0);
+            let expected = brute_force(&field, &r).map(|rec| rec.t);
+            let found = field.hit(&r, 0.001, f64::INFINITY, &mut Sampler::new(0)).map(|rec| rec.t);
+            assert_eq!(found, expected, "ray from {} to {}", origin, target);
+        }
+    }
+
+    #[test]
+    fn flat_field() {
+        let field = terrain(vec![0.5; 12], 4);
+        let down = Vec3::new(0.0, -1.0, 0.0);
+        let rec = hit(&field, Point3::new(0.3, 2.0, 0.5), down).unwrap();
+        assert!((rec.t - 1.5).abs() < 1e-12);
+        assert!((rec.normal - Vec3::new(0.0, 1.0, 0.0)).length() < 1e-12);
+        // u runs along x, v from the far row at the top of an image down to min.z
+        assert!((rec.u - 0.575).abs() < 1e-12 && (rec.v - 0.5).abs() < 1e-12);
+        assert!(hit(&field, Point3::new(2.5, 2.0, 0.7), down).is_none());
+    }
+
+    #[test]
+    fn grazing_ray_over_a_ridge() {
+        // a ridge along z in the middle column, a ray just above its top misses and one just below hits
+        let field = terrain(vec![0.0, 1.0, 0.0, 0.0, 1.0, 0.0], 3);
+        let along_x = Vec3::new(1.0, 0.0, 0.0);
+        assert!(hit(&field, Point3::new(-3.0, 1.001, 0.5), along_x).is_none());
+        let rec = hit(&field, Point3::new(-3.0, 0.5, 0.5), along_x).unwrap();
+        assert!((rec.t - 2.0).abs() < 1e-12);
+    }
+}
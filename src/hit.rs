@@ -1,11 +1,14 @@
+use std::sync::Arc;
 use rand::seq::SliceRandom;
 use super::vec::{Vec3, Point3};
 use super::ray::Ray;
 use super::mat::Material;
 use super::aabb;
 use super::aabb::AABB;
+use super::sampler::Sampler;
 
 // 'static denotes that the affected reference can live for the entire duration of the program
+/// where and how a ray met a surface
 pub struct HitRecord<'a> {
     pub position: Point3,
     pub normal: Vec3,
@@ -13,6 +16,8 @@ pub struct HitRecord<'a> {
 
     pub u: f64,
     pub v: f64,
+    /// direction of increasing u along the surface, not normalized, zero where the surface has none
+    pub tangent: Vec3,
 
     pub front_face: bool,
     
@@ -23,11 +28,28 @@ pub struct HitRecord<'a> {
     pub material: &'a dyn Material
 }
 
-pub trait Hittable: Sync {
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord>;
+/// anything a ray can hit
+pub trait Hittable: Send + Sync {
+    /// the closest hit with a ray parameter in [t_min, t_max]
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rng: &mut Sampler) -> Option<HitRecord>;
+    /// box enclosing the object between the times t0 and t1, None if it is unbounded
     fn bounding_box(&self, t0: f64, t1: f64) -> Option<AABB>;
-    fn pdf_value(&self, o: Point3, v: Vec3) -> f64 { 0.0 }
-    fn random(&self, o: Vec3) -> Vec3 { Vec3::new(1.0, 0.0, 0.0) }
+    /// density of `random` seen from the point o in the direction v, for objects sampled as lights
+    fn pdf_value(&self, o: Point3, v: Vec3, _rng: &mut Sampler) -> f64 { 0.0 }
+    /// a random direction from the point o towards the object
+    fn random(&self, o: Vec3, _rng: &mut Sampler) -> Vec3 { Vec3::new(1.0, 0.0, 0.0) }
+    /// every hit with a ray parameter in [t_min, t_max] in increasing order, along a closed object
+    /// they alternate between entering (front_face) and leaving
+    fn hit_all(&self, r: &Ray, t_min: f64, t_max: f64, rng: &mut Sampler) -> Vec<HitRecord<'_>> {
+        let mut hits = Vec::new();
+        let mut t = t_min;
+        while let Some(rec) = self.hit(r, t, t_max, rng) {
+            // step past the hit so it is not found again
+            t = rec.t + 1.0e-9 * rec.t.abs().max(1.0);
+            hits.push(rec);
+        }
+        hits
+    }
 }
 
 impl HitRecord<'_> {
@@ -41,9 +63,55 @@ impl HitRecord<'_> {
     }
 }
 
+impl<H: Hittable + ?Sized> Hittable for Box<H> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rng: &mut Sampler) -> Option<HitRecord<'_>> {
+        (**self).hit(r, t_min, t_max, rng)
+    }
+
+    fn bounding_box(&self, t0: f64, t1: f64) -> Option<AABB> {
+        (**self).bounding_box(t0, t1)
+    }
+
+    fn pdf_value(&self, o: Point3, v: Vec3, rng: &mut Sampler) -> f64 {
+        (**self).pdf_value(o, v, rng)
+    }
+
+    fn random(&self, o: Vec3, rng: &mut Sampler) -> Vec3 {
+        (**self).random(o, rng)
+    }
+
+    fn hit_all(&self, r: &Ray, t_min: f64, t_max: f64, rng: &mut Sampler) -> Vec<HitRecord<'_>> {
+        (**self).hit_all(r, t_min, t_max, rng)
+    }
+}
+
+// shared objects, like the lights of a scene that are part of its world as well
+impl<H: Hittable + ?Sized> Hittable for Arc<H> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rng: &mut Sampler) -> Option<HitRecord<'_>> {
+        (**self).hit(r, t_min, t_max, rng)
+    }
+
+    fn bounding_box(&self, t0: f64, t1: f64) -> Option<AABB> {
+        (**self).bounding_box(t0, t1)
+    }
+
+    fn pdf_value(&self, o: Point3, v: Vec3, rng: &mut Sampler) -> f64 {
+        (**self).pdf_value(o, v, rng)
+    }
+
+    fn random(&self, o: Vec3, rng: &mut Sampler) -> Vec3 {
+        (**self).random(o, rng)
+    }
+
+    fn hit_all(&self, r: &Ray, t_min: f64, t_max: f64, rng: &mut Sampler) -> Vec<HitRecord<'_>> {
+        (**self).hit_all(r, t_min, t_max, rng)
+    }
+}
+
 // explaination: smart pointer type of trait object, Box<T> pointer allow to store data on the heap, what remains on the stack is the pointer to the heap data
 // pub type World = Vec<Box<dyn Hit>>;
 
+/// a plain list of objects, every one of them is tested against each ray
 #[derive(Default)]
 pub struct HittableList {
     list: Vec<Box<dyn Hittable>>
@@ -53,15 +121,23 @@ impl HittableList {
     pub fn push(&mut self, hittable: impl Hittable + 'static) {
         self.list.push(Box::new(hittable))
     }
+
+    pub fn len(&self) -> usize {
+        self.list.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
 }
 
 impl Hittable for HittableList {
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rng: &mut Sampler) -> Option<HitRecord> {
         let mut temp_rec = None;
         let mut cloest_so_far = t_max;
 
         for object in &self.list {
-            if let Some(rec) = object.hit(r, t_min, cloest_so_far) {
+            if let Some(rec) = object.hit(r, t_min, cloest_so_far, rng) {
                 cloest_so_far = rec.t;
                 temp_rec = Some(rec);
             }
@@ -87,15 +163,16 @@ impl Hittable for HittableList {
         }
     }
 
-    fn pdf_value(&self, o: Vec3, v: Vec3) -> f64 {
-        self.list.iter().map(|h| h.pdf_value(o, v)).sum::<f64>() / self.list.len() as f64
+    fn pdf_value(&self, o: Vec3, v: Vec3, rng: &mut Sampler) -> f64 {
+        self.list.iter().map(|h| h.pdf_value(o, v, rng)).sum::<f64>() / self.list.len() as f64
     }
 
-    fn random(&self, o: Vec3) -> Vec3 {
-        self.list.choose(&mut rand::thread_rng()).unwrap().random(o)
+    fn random(&self, o: Vec3, rng: &mut Sampler) -> Vec3 {
+        self.list.choose(rng).unwrap().random(o, rng)
     }
 }
 
+/// turns an object inside out, e.g. to make a light face down
 #[derive(Clone)]
 pub struct FlipNormal<H: Hittable> {
     hittable: H
@@ -110,8 +187,8 @@ impl<H: Hittable> FlipNormal<H> {
 }
 
 impl<H: Hittable> Hittable for FlipNormal<H> {
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
-        self.hittable.hit(&r, t_min, t_max).map(
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rng: &mut Sampler) -> Option<HitRecord> {
+        self.hittable.hit(&r, t_min, t_max, rng).map(
             |mut rec| {
                 rec.front_face = !rec.front_face;
                 rec
@@ -123,11 +200,11 @@ impl<H: Hittable> Hittable for FlipNormal<H> {
         self.hittable.bounding_box(t0, t1)
     }
 
-    fn pdf_value(&self, o: Vec3, v: Vec3) -> f64 {
-        self.hittable.pdf_value(o, v)
+    fn pdf_value(&self, o: Vec3, v: Vec3, rng: &mut Sampler) -> f64 {
+        self.hittable.pdf_value(o, v, rng)
     }
 
-    fn random(&self, o: Vec3) -> Vec3 {
-        self.hittable.random(o)
+    fn random(&self, o: Vec3, rng: &mut Sampler) -> Vec3 {
+        self.hittable.random(o, rng)
     }
 }
\ No newline at end of file
@@ -0,0 +1,95 @@
+use std::sync::Arc;
+use super::vec::{Vec3, Point3};
+use super::ray::Ray;
+use super::sampler::Sampler;
+use super::hit::{Hittable, HitRecord};
+use super::mat::Material;
+use super::mat4::Mat4;
+use super::transform::Transform;
+use super::aabb::AABB;
+
+/// a placement of shared geometry
+///
+/// any number of instances can reference the same mesh, each with its own affine transform and
+/// optionally its own material. a bvh over instances makes a two level hierarchy: the top level
+/// sorts the instances, the geometry keeps its own bvh in object space
+pub struct Instance {
+    placement: Transform<Arc<dyn Hittable>>,
+    material: Option<Arc<dyn Material>>
+}
+
+impl Instance {
+    /// `transform` maps object space to world space and must be invertible
+    pub fn new(geometry: Arc<dyn Hittable>, transform: Mat4) -> Instance {
+        Instance {
+            placement: Transform::new(geometry, transform),
+            material: None
+        }
+    }
+
+    /// replaces the material of every surface of the geometry
+    pub fn with_material(mut self, material: Arc<dyn Material>) -> Instance {
+        self.material = Some(material);
+        self
+    }
+}
+
+impl Hittable for Instance {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rng: &mut Sampler) -> Option<HitRecord<'_>> {
+        let mut rec = self.placement.hit(r, t_min, t_max, rng)?;
+        if let Some(material) = &self.material {
+            rec.material = material.as_ref();
+        }
+        Some(rec)
+    }
+
+    fn bounding_box(&self, t0: f64, t1: f64) -> Option<AABB> {
+        self.placement.bounding_box(t0, t1)
+    }
+
+    fn pdf_value(&self, o: Point3, v: Vec3, rng: &mut Sampler) -> f64 {
+        self.placement.pdf_value(o, v, rng)
+    }
+
+    fn random(&self, o: Vec3, rng: &mut Sampler) -> Vec3 {
+        self.placement.random(o, rng)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mat::Lambertian;
+    use crate::sphere::Sphere;
+    use crate::test_util::{grey, hit};
+    use crate::texture::ConstantTexture;
+    use crate::vec::Color;
+    use super::*;
+
+    // the attenuation of the material at the nearest hit straight down from above `p`
+    fn albedo_below(instance: &Instance, p: Point3) -> Color {
+        let (origin, down) = (p + Vec3::new(0.0, 0.0, 10.0), Vec3::new(0.0, 0.0, -1.0));
+        let rec = hit(instance, origin, down).unwrap();
+        rec.material.scatter(&Ray::new(origin, down, 0.0), &rec, &mut Sampler::new(0)).unwrap().0
+    }
+
+    #[test]
+    fn instances_share_geometry() {
+        let geometry: Arc<dyn Hittable> = Arc::new(Sphere::new(Point3::new(0.0, 0.0, 0.0), 1.0, grey()));
+        let plain = Instance::new(geometry.clone(), Mat4::translation(Vec3::new(5.0, 0.0, 0.0)) * Mat4::scaling(Vec3::new(2.0, 2.0, 1.0)));
+        let red = Instance::new(geometry, Mat4::translation(Vec3::new(-5.0, 0.0, 0.0)))
+            .with_material(Arc::new(Lambertian::new(ConstantTexture::new(Color::new(0.9, 0.1, 0.1)))));
+
+        // the top of the flattened sphere is still at z = 1, its side at x = 5 +- 2
+        let rec = hit(&plain, Point3::new(5.0, 0.0, 10.0), Vec3::new(0.0, 0.0, -1.0)).unwrap();
+        assert!((rec.t - 9.0).abs() < 1e-9);
+        assert!(hit(&plain, Point3::new(6.9, 0.0, 10.0), Vec3::new(0.0, 0.0, -1.0)).is_some());
+        assert!(hit(&red, Point3::new(-3.1, 0.0, 10.0), Vec3::new(0.0, 0.0, -1.0)).is_none());
+
+        // the override replaces the material of the geometry
+        assert_eq!(albedo_below(&plain, Point3::new(5.0, 0.0, 0.0)).x(), 0.5);
+        assert_eq!(albedo_below(&red, Point3::new(-5.0, 0.0, 0.0)).x(), 0.9);
+
+        let bbox = plain.bounding_box(0.0, 1.0).unwrap();
+        assert!((bbox.min - Vec3::new(3.0, -2.0, -1.0)).length() < 1e-9 && (bbox.max - Vec3::new(7.0, 2.0, 1.0)).length() < 1e-9);
+    }
+}
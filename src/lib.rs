@@ -0,0 +1,63 @@
+//! Beauty, an offline ray tracing renderer
+//!
+//! a [`Scene`] is built by hand from the shapes, materials and textures of this crate,
+//! taken from [`builtin::Builtin`] or read from a json file with [`scene::load`],
+//! then a [`Renderer`] turns it into a [`Framebuffer`] of linear colors
+//! that a [`ToneMapper`] prepares for display when it is saved.
+
+pub mod vec;
+pub mod ray;
+pub mod translate;
+pub mod rotate;
+pub mod mat4;
+pub mod transform;
+pub mod instance;
+pub mod quat;
+pub mod animated;
+pub mod hit;
+pub mod sphere;
+pub mod quadric;
+pub mod torus;
+pub mod rect;
+pub mod quad;
+pub mod cube;
+pub mod csg;
+pub mod sdf;
+pub mod heightfield;
+pub mod curve;
+pub mod subdivision;
+pub mod displace;
+pub mod particles;
+pub mod camera;
+pub mod mat;
+pub mod aabb;
+pub mod bvh;
+pub mod perlin;
+pub mod texture;
+pub mod medium;
+pub mod onb;
+pub mod pdf;
+pub mod triangle;
+pub mod obj;
+mod json;
+pub mod scene;
+pub mod builtin;
+pub mod tonemap;
+pub mod framebuffer;
+pub mod sampler;
+pub mod checkpoint;
+pub mod render;
+#[cfg(test)]
+mod test_util;
+
+pub use vec::{Vec3, Point3, Color};
+pub use hit::{Hittable, HittableList};
+pub use mat::Material;
+pub use texture::Texture;
+pub use camera::Camera;
+pub use bvh::BVH;
+pub use scene::Scene;
+pub use tonemap::ToneMapper;
+pub use framebuffer::{Framebuffer, ImageFormat};
+pub use checkpoint::Accumulator;
+pub use render::Renderer;
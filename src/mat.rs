@@ -1,22 +1,28 @@
 use rand::Rng;
 use std::f64;
+use std::sync::Arc;
 use super::vec::{Vec3, Color};
 use super::ray::Ray;
 use super::hit::{HitRecord};
 use super::texture::Texture;
 use super::pdf::PDF;
+use super::sampler::Sampler;
+use super::onb::ONB;
 
-pub trait Material: Sync {
+/// how a surface scatters and emits light
+pub trait Material: Send + Sync {
     // old method
-    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, _rng: &mut Sampler) -> Option<(Color, Ray)> {
         None
     }
 
     //mc method
-    fn scatter_mc_method(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+    /// the scattering used by the renderer, None when the ray is absorbed
+    fn scatter_mc_method(&self, r_in: &Ray, rec: &HitRecord, _rng: &mut Sampler) -> Option<ScatterRecord> {
         None
     }
 
+    /// density of the scattered direction, for materials returning `ScatterRecord::Scatter`
     fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
         0.0
     }
@@ -26,11 +32,32 @@ pub trait Material: Sync {
     }
 }
 
+// materials shared between several objects, e.g. all the faces of a mesh
+impl<M: Material + ?Sized> Material for Arc<M> {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, rng: &mut Sampler) -> Option<(Color, Ray)> {
+        (**self).scatter(r_in, rec, rng)
+    }
+
+    fn scatter_mc_method(&self, r_in: &Ray, rec: &HitRecord, rng: &mut Sampler) -> Option<ScatterRecord<'_>> {
+        (**self).scatter_mc_method(r_in, rec, rng)
+    }
+
+    fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        (**self).scattering_pdf(r_in, rec, scattered)
+    }
+
+    fn emitted(&self, rec: &HitRecord) -> Color {
+        (**self).emitted(rec)
+    }
+}
+
+/// a scattered ray is either fully determined or drawn from a pdf
 pub enum ScatterRecord<'a> {
     Specular { specular_ray: Ray, attenuation: Color },
     Scatter { pdf: PDF<'a>, attenuation: Color }
 }
 
+/// ideal diffuse surface
 #[derive(Clone)]
 pub struct Lambertian<T: Texture> {
     albedo: T
@@ -45,8 +72,8 @@ impl<T: Texture> Lambertian<T> {
 }
 
 impl<T: Texture> Material for Lambertian<T> {
-    fn scatter(&self, _r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
-        let mut scatter_direction = rec.normal + Vec3::random_in_unit_sphere().normalized();
+    fn scatter(&self, _r_in: &Ray, rec: &HitRecord, rng: &mut Sampler) -> Option<(Color, Ray)> {
+        let mut scatter_direction = rec.normal + Vec3::random_in_unit_sphere(rng).normalized();
         if scatter_direction.near_zero() {
             // Catch degenerate scatter direction
             scatter_direction = rec.normal;
@@ -57,7 +84,7 @@ impl<T: Texture> Material for Lambertian<T> {
         Some((self.albedo.mapping(rec.u, rec.v, &rec.position), scattered))
     }
 
-    fn scatter_mc_method(&self, _r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+    fn scatter_mc_method(&self, _r_in: &Ray, rec: &HitRecord, _rng: &mut Sampler) -> Option<ScatterRecord> {
         // let mut scatter_direction = rec.normal + Vec3::random_in_unit_sphere();
         
         // if scatter_direction.near_zero() {
@@ -85,6 +112,7 @@ impl<T: Texture> Material for Lambertian<T> {
 }
 
 
+/// mirror, blurred by `fuzz` in [0, 1]
 #[derive(Clone)]
 pub struct Metal {
     albedo: Color,
@@ -101,9 +129,9 @@ impl Metal {
 }
 
 impl Material for Metal {
-    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, rng: &mut Sampler) -> Option<(Color, Ray)> {
         let reflected = r_in.direction().reflect(rec.normal).normalized();
-        let scattered = Ray::new(rec.position, reflected + self.fuzz * Vec3::random_in_unit_sphere(), r_in.time());
+        let scattered = Ray::new(rec.position, reflected + self.fuzz * Vec3::random_in_unit_sphere(rng), r_in.time());
 
         if scattered.direction().dot(rec.normal) > 0.0 {
             Some((self.albedo, scattered))
@@ -112,9 +140,9 @@ impl Material for Metal {
         }
     }
 
-    fn scatter_mc_method(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+    fn scatter_mc_method(&self, r_in: &Ray, rec: &HitRecord, rng: &mut Sampler) -> Option<ScatterRecord> {
         let reflected = r_in.direction().reflect(rec.normal).normalized();
-        let scattered = Ray::new(rec.position, reflected + self.fuzz * Vec3::random_in_unit_sphere(), r_in.time());
+        let scattered = Ray::new(rec.position, reflected + self.fuzz * Vec3::random_in_unit_sphere(rng), r_in.time());
 
         if scattered.direction().dot(rec.normal) > 0.0 {
             let rec = ScatterRecord::Specular { 
@@ -129,6 +157,7 @@ impl Material for Metal {
 }
 
 
+/// glass-like surface that reflects and refracts
 #[derive(Clone)]
 pub struct Dielectric {
     ir: f64
@@ -149,7 +178,7 @@ impl Dielectric {
 }
 
 impl Material for Dielectric {
-    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, rng: &mut Sampler) -> Option<(Color, Ray)> {
         let refraction_ratio = if rec.front_face {
             1.0 / self.ir
         } else {
@@ -161,7 +190,6 @@ impl Material for Dielectric {
         let cos_theta = ((-1.0) * unit_direction).dot(rec.normal).min(1.0);
         let sin_theta = (1.0 - cos_theta.powi(2)).sqrt();
 
-        let mut rng = rand::thread_rng();
         let cannot_refract = refraction_ratio * sin_theta > 1.0;
         let will_reflect = rng.gen::<f64>() < Self::reflectance(cos_theta, refraction_ratio);
 
@@ -175,7 +203,7 @@ impl Material for Dielectric {
         Some((Color::new(1.0, 1.0, 1.0), scattered))
     }
 
-    fn scatter_mc_method(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+    fn scatter_mc_method(&self, r_in: &Ray, rec: &HitRecord, rng: &mut Sampler) -> Option<ScatterRecord> {
         let attenuation = Color::new(1.0, 1.0, 1.0);
         let refraction_ratio = if rec.front_face {
             1.0 / self.ir
@@ -188,7 +216,6 @@ impl Material for Dielectric {
         let cos_theta = ((-1.0) * unit_direction).dot(rec.normal).min(1.0);
         let sin_theta = (1.0 - cos_theta.powi(2)).sqrt();
 
-        let mut rng = rand::thread_rng();
         let cannot_refract = refraction_ratio * sin_theta > 1.0;
         let will_reflect = rng.gen::<f64>() < Self::reflectance(cos_theta, refraction_ratio);
 
@@ -209,6 +236,7 @@ impl Material for Dielectric {
     }
 }
 
+/// emits light from its front face
 #[derive(Clone)]
 pub struct DiffuseLight<T: Texture> {
     emit: T
@@ -223,7 +251,7 @@ impl<T: Texture> DiffuseLight<T> {
 }
 
 impl<T: Texture> Material for DiffuseLight<T> {
-    fn scatter(&self, _r_in: &Ray, _rec: &HitRecord) -> Option<(Color, Ray)> {
+    fn scatter(&self, _r_in: &Ray, _rec: &HitRecord, _rng: &mut Sampler) -> Option<(Color, Ray)> {
         None
     }
 
@@ -236,6 +264,7 @@ impl<T: Texture> Material for DiffuseLight<T> {
     }
 }
 
+/// scatters uniformly in every direction, the phase function of participating media
 #[derive(Clone)]
 pub struct Isotropic<T: Texture> {
     albedo: T
@@ -250,8 +279,78 @@ impl<T: Texture> Isotropic<T> {
 }
 
 impl<T: Texture> Material for Isotropic<T> {
-    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
-        let scattered = Ray::new(rec.position, Vec3::random_in_unit_sphere(), r_in.time());
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, rng: &mut Sampler) -> Option<(Color, Ray)> {
+        let scattered = Ray::new(rec.position, Vec3::random_in_unit_sphere(rng), r_in.time());
         Some((self.albedo.mapping(rec.u, rec.v, &rec.position), scattered))
     }
-}
\ No newline at end of file
+}
+
+/// light scattering in a fiber of hair or fur, picks one of the lobes of a round fiber:
+/// reflection off the surface, transmission through the fiber, or an inner reflection back out
+///
+/// the fiber runs along the tangent of the hit, which curves always have
+#[derive(Clone)]
+pub struct Hair<T: Texture> {
+    // what is left of the light after one pass through the fiber
+    color: T,
+    roughness: f64,
+    shift: f64
+}
+
+impl<T: Texture> Hair<T> {
+    /// `roughness` is the spread of the lobes along the fiber in degrees, `shift` tilts them
+    /// towards the root like the scales on a real hair do, also in degrees
+    pub fn new(color: T, roughness: f64, shift: f64) -> Hair<T> {
+        assert!(roughness >= 0.0, "the roughness cannot be negative");
+        Hair {
+            color,
+            roughness: roughness.to_radians(),
+            shift: shift.to_radians()
+        }
+    }
+}
+
+// normal distribution from the box muller transform
+fn gaussian(rng: &mut impl Rng, deviation: f64) -> f64 {
+    let r1 = 1.0 - rng.gen::<f64>();
+    let r2 = rng.gen::<f64>();
+    deviation * (-2.0 * r1.ln()).sqrt() * (2.0 * f64::consts::PI * r2).cos()
+}
+
+impl<T: Texture> Material for Hair<T> {
+    fn scatter_mc_method(&self, r_in: &Ray, rec: &HitRecord, rng: &mut Sampler) -> Option<ScatterRecord<'_>> {
+        // a frame with the fiber along t and the plane across it spanned by the normal and b
+        let n = rec.normal;
+        let along = rec.tangent - rec.tangent.dot(n) * n;
+        let t = if along.near_zero() { ONB::build_from_w(&n).u() } else { along.normalized() };
+        let b = t.cross(n);
+
+        // angles of the direction back along the ray, out of the plane across the fiber and around it
+        let incoming = (-1.0) * r_in.direction().normalized();
+        let theta_in = incoming.dot(t).clamp(-1.0, 1.0).asin();
+        let phi_in = incoming.dot(b).atan2(incoming.dot(n));
+
+        let color = self.color.mapping(rec.u, rec.v, &rec.position);
+        let reflect = Dielectric::reflectance(incoming.dot(n).max(0.0), 1.55);
+        let (theta, phi, attenuation) = if rng.gen::<f64>() < reflect {
+            // off the surface, mirrored around the fiber
+            (-theta_in - 2.0 * self.shift + gaussian(rng, self.roughness), -phi_in, Color::new(1.0, 1.0, 1.0))
+        } else if rng.gen::<bool>() {
+            // through the fiber, coming out on the far side
+            (-theta_in + self.shift + gaussian(rng, 0.5 * self.roughness),
+             phi_in + f64::consts::PI + gaussian(rng, self.roughness),
+             color)
+        } else {
+            // reflected inside and back out towards the light, spread wide around the fiber
+            (-theta_in + 4.0 * self.shift + gaussian(rng, 2.0 * self.roughness),
+             -phi_in + rng.gen_range(-0.25..0.25) * f64::consts::PI,
+             color * color)
+        };
+
+        let direction = theta.sin() * t + theta.cos() * (phi.cos() * n + phi.sin() * b);
+        Some(ScatterRecord::Specular {
+            specular_ray: Ray::new(rec.position, direction, r_in.time()),
+            attenuation
+        })
+    }
+}
@@ -0,0 +1,257 @@
+use std::f64;
+use std::ops::Mul;
+use super::vec::{Vec3, Point3};
+use super::aabb::AABB;
+
+/// 4x4 matrix acting on column vectors, points have an implicit w of 1 and directions a w of 0
+#[derive(Clone, Copy, PartialEq)]
+pub struct Mat4 {
+    m: [[f64; 4]; 4]
+}
+
+impl Mat4 {
+    pub fn new(rows: [[f64; 4]; 4]) -> Mat4 {
+        Mat4 { m: rows }
+    }
+
+    pub fn identity() -> Mat4 {
+        Mat4::scaling(Vec3::new(1.0, 1.0, 1.0))
+    }
+
+    pub fn translation(offset: Vec3) -> Mat4 {
+        Mat4::new([
+            [1.0, 0.0, 0.0, offset.x()],
+            [0.0, 1.0, 0.0, offset.y()],
+            [0.0, 0.0, 1.0, offset.z()],
+            [0.0, 0.0, 0.0, 1.0]
+        ])
+    }
+
+    pub fn scaling(factors: Vec3) -> Mat4 {
+        Mat4::new([
+            [factors.x(), 0.0, 0.0, 0.0],
+            [0.0, factors.y(), 0.0, 0.0],
+            [0.0, 0.0, factors.z(), 0.0],
+            [0.0, 0.0, 0.0, 1.0]
+        ])
+    }
+
+    /// counterclockwise rotation around `axis` when looking against it, the angle is in degrees
+    pub fn rotation(axis: Vec3, angle: f64) -> Mat4 {
+        let a = axis.normalized();
+        let (sin, cos) = angle.to_radians().sin_cos();
+        let t = 1.0 - cos;
+        Mat4::new([
+            [t * a.x() * a.x() + cos, t * a.x() * a.y() - sin * a.z(), t * a.x() * a.z() + sin * a.y(), 0.0],
+            [t * a.x() * a.y() + sin * a.z(), t * a.y() * a.y() + cos, t * a.y() * a.z() - sin * a.x(), 0.0],
+            [t * a.x() * a.z() - sin * a.y(), t * a.y() * a.z() + sin * a.x(), t * a.z() * a.z() + cos, 0.0],
+            [0.0, 0.0, 0.0, 1.0]
+        ])
+    }
+
+    /// places the origin at `from` and turns +z towards `target`, keeping +y as close to `up` as
+    /// possible
+    pub fn look_at(from: Point3, target: Point3, up: Vec3) -> Mat4 {
+        let z = (target - from).normalized();
+        let x = up.cross(z).normalized();
+        let y = z.cross(x);
+        Mat4::new([
+            [x.x(), y.x(), z.x(), from.x()],
+            [x.y(), y.y(), z.y(), from.y()],
+            [x.z(), y.z(), z.z(), from.z()],
+            [0.0, 0.0, 0.0, 1.0]
+        ])
+    }
+
+    pub fn get(&self, row: usize, column: usize) -> f64 {
+        self.m[row][column]
+    }
+
+    pub fn transpose(&self) -> Mat4 {
+        let mut t = [[0.0; 4]; 4];
+        for (i, row) in t.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = self.m[j][i];
+            }
+        }
+        Mat4::new(t)
+    }
+
+    /// determinant of the upper left 3x3 block, the factor by which volumes are scaled
+    pub fn determinant3(&self) -> f64 {
+        let m = &self.m;
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    }
+
+    /// gauss-jordan elimination with partial pivoting, None for singular matrices
+    pub fn inverse(&self) -> Option<Mat4> {
+        let mut a = self.m;
+        let mut inv = Mat4::identity().m;
+        for column in 0..4 {
+            let pivot = (column..4).max_by(|&i, &j| a[i][column].abs().total_cmp(&a[j][column].abs())).unwrap();
+            if a[pivot][column].abs() < 1.0e-12 {
+                return None
+            }
+            a.swap(column, pivot);
+            inv.swap(column, pivot);
+
+            let p = a[column][column];
+            for j in 0..4 {
+                a[column][j] /= p;
+                inv[column][j] /= p;
+            }
+            for row in 0..4 {
+                if row != column {
+                    let f = a[row][column];
+                    for j in 0..4 {
+                        a[row][j] -= f * a[column][j];
+                        inv[row][j] -= f * inv[column][j];
+                    }
+                }
+            }
+        }
+        Some(Mat4::new(inv))
+    }
+
+    pub fn transform_point(&self, p: Point3) -> Point3 {
+        let m = &self.m;
+        Point3::new(
+            m[0][0] * p.x() + m[0][1] * p.y() + m[0][2] * p.z() + m[0][3],
+            m[1][0] * p.x() + m[1][1] * p.y() + m[1][2] * p.z() + m[1][3],
+            m[2][0] * p.x() + m[2][1] * p.y() + m[2][2] * p.z() + m[2][3]
+        )
+    }
+
+    pub fn transform_vector(&self, v: Vec3) -> Vec3 {
+        let m = &self.m;
+        Vec3::new(
+            m[0][0] * v.x() + m[0][1] * v.y() + m[0][2] * v.z(),
+            m[1][0] * v.x() + m[1][1] * v.y() + m[1][2] * v.z(),
+            m[2][0] * v.x() + m[2][1] * v.y() + m[2][2] * v.z()
+        )
+    }
+
+    /// the box around the eight transformed corners
+    pub fn transform_box(&self, b: &AABB) -> AABB {
+        let mut min = Vec3::new(f64::INFINITY, f64::INFINITY, f64::INFINITY);
+        let mut max = Vec3::new(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY);
+        for corner in 0..8 {
+            let p = self.transform_point(Point3::new(
+                if corner & 1 == 0 { b.min.x() } else { b.max.x() },
+                if corner & 2 == 0 { b.min.y() } else { b.max.y() },
+                if corner & 4 == 0 { b.min.z() } else { b.max.z() }
+            ));
+            for a in 0..3 {
+                min[a] = min[a].min(p[a]);
+                max[a] = max[a].max(p[a]);
+            }
+        }
+        AABB::new(min, max)
+    }
+}
+
+impl Mul for Mat4 {
+    type Output = Mat4;
+
+    /// `a * b` applies b first
+    fn mul(self, other: Mat4) -> Mat4 {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = (0..4).map(|k| self.m[i][k] * other.m[k][j]).sum();
+            }
+        }
+        Mat4::new(m)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < 1e-9, "{} instead of {}", a, b);
+    }
+
+    // translation, rotation about a skew axis, non-uniform scale and shear
+    fn affine() -> Mat4 {
+        let shear = Mat4::new([
+            [1.0, 0.5, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.3, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0]
+        ]);
+        Mat4::translation(Vec3::new(1.0, -2.0, 3.0)) * Mat4::rotation(Vec3::new(1.0, 2.0, -0.5), 37.0) * Mat4::scaling(Vec3::new(2.0, 0.5, 3.0)) * shear
+    }
+
+    #[test]
+    fn inverse_undoes_the_matrix() {
+        let m = affine();
+        let inverse = m.inverse().unwrap();
+        for product in [m * inverse, inverse * m] {
+            for i in 0..4 {
+                for j in 0..4 {
+                    let expected = if i == j { 1.0 } else { 0.0 };
+                    assert!((product.get(i, j) - expected).abs() < 1e-12);
+                }
+            }
+        }
+        let p = Point3::new(0.3, -1.2, 4.0);
+        assert_close(inverse.transform_point(m.transform_point(p)), p);
+        assert!(Mat4::scaling(Vec3::new(1.0, 0.0, 1.0)).inverse().is_none());
+    }
+
+    #[test]
+    fn normal_matrix_keeps_normals_perpendicular() {
+        let m = affine();
+        let normal_matrix = m.inverse().unwrap().transpose();
+        // two directions in a plane and its normal
+        let (a, b) = (Vec3::new(1.0, 2.0, 0.5), Vec3::new(-0.3, 0.1, 1.0));
+        let n = normal_matrix.transform_vector(a.cross(b));
+        assert!(n.dot(m.transform_vector(a)).abs() < 1e-12 && n.dot(m.transform_vector(b)).abs() < 1e-12);
+        assert!((m.determinant3() - 2.0 * 0.5 * 3.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn rotation_about_any_axis() {
+        // a third of a turn about the diagonal cycles the axes
+        let m = Mat4::rotation(Vec3::new(1.0, 1.0, 1.0), 120.0);
+        assert_close(m.transform_vector(Vec3::new(1.0, 0.0, 0.0)), Vec3::new(0.0, 1.0, 0.0));
+        assert_close(m.transform_vector(Vec3::new(0.0, 1.0, 0.0)), Vec3::new(0.0, 0.0, 1.0));
+        assert_close(m.transform_vector(Vec3::new(2.0, 2.0, 2.0)), Vec3::new(2.0, 2.0, 2.0));
+        // counterclockwise looking down the axis
+        assert_close(Mat4::rotation(Vec3::new(0.0, 0.0, 3.0), 90.0).transform_vector(Vec3::new(1.0, 0.0, 0.0)), Vec3::new(0.0, 1.0, 0.0));
+        assert!((m.determinant3() - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn look_at_frame() {
+        let (from, target, up) = (Point3::new(1.0, 2.0, 3.0), Point3::new(4.0, 2.0, -1.0), Vec3::new(0.0, 1.0, 0.0));
+        let m = Mat4::look_at(from, target, up);
+        assert_close(m.transform_point(Point3::new(0.0, 0.0, 0.0)), from);
+        assert_close(m.transform_vector(Vec3::new(0.0, 0.0, 5.0)), target - from);
+        // +y stays up since the view is level
+        assert_close(m.transform_vector(Vec3::new(0.0, 1.0, 0.0)), up);
+        assert!((m.determinant3() - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn transformed_box_holds_the_corners() {
+        let b = AABB::new(Vec3::new(-1.0, -1.0, -1.0), Vec3::new(1.0, 1.0, 1.0));
+        let rotated = Mat4::rotation(Vec3::new(0.0, 0.0, 1.0), 45.0).transform_box(&b);
+        let half = 2f64.sqrt();
+        assert_close(rotated.min, Vec3::new(-half, -half, -1.0));
+        assert_close(rotated.max, Vec3::new(half, half, 1.0));
+
+        let m = affine();
+        let moved = m.transform_box(&b);
+        for corner in 0..8 {
+            let p = m.transform_point(Point3::new([-1.0, 1.0][corner & 1], [-1.0, 1.0][(corner >> 1) & 1], [-1.0, 1.0][corner >> 2]));
+            for a in 0..3 {
+                assert!(p[a] >= moved.min[a] - 1e-12 && p[a] <= moved.max[a] + 1e-12);
+            }
+        }
+    }
+}
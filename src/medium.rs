@@ -6,7 +6,9 @@ use super::hit::{Hittable,HitRecord};
 use super::mat::Isotropic;
 use super::texture::Texture;
 use super::aabb::AABB;
+use super::sampler::Sampler;
 
+/// fog of constant density filling a closed boundary
 pub struct ConstantMedium<H: Hittable, T: Texture> {
     boundary: H,
     density: f64,
@@ -24,10 +26,9 @@ impl<H: Hittable, T: Texture> ConstantMedium<H, T> {
 }
 
 impl<H: Hittable, T: Texture> Hittable for ConstantMedium<H, T> {
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
-        let mut rng = rand::thread_rng();
-        if let Some(mut hit1) = self.boundary.hit(r, -f64::MAX, f64::MAX) {
-            if let Some(mut hit2) = self.boundary.hit(r, hit1.t + 0.0001, f64::MAX) {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rng: &mut Sampler) -> Option<HitRecord> {
+        if let Some(mut hit1) = self.boundary.hit(r, -f64::MAX, f64::MAX, rng) {
+            if let Some(mut hit2) = self.boundary.hit(r, hit1.t + 0.0001, f64::MAX, rng) {
 
                 if hit1.t < t_min {
                     hit1.t = t_min
@@ -47,6 +48,7 @@ impl<H: Hittable, T: Texture> Hittable for ConstantMedium<H, T> {
                                 position: r.at(t),
                                 u: 0.0,
                                 v: 0.0,
+                                tangent: Vec3::new(0.0, 0.0, 0.0),
                                 t,
                                 front_face: false, // arbitrary
                                 normal: Vec3::new(1.0, 0.0, 0.0), // arbitrary
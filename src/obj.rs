@@ -0,0 +1,459 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use super::vec::{Vec3, Point3, Color};
+use super::mat::{Material, Lambertian, Metal, Dielectric, DiffuseLight};
+use super::texture::{ConstantTexture, ImageTexture};
+use super::triangle::TriangleMesh;
+use super::bvh::SplitMethod;
+
+#[derive(Debug)]
+pub enum ObjError {
+    Io { path: PathBuf, error: io::Error },
+    Image { path: PathBuf, error: image::ImageError },
+    Parse { path: PathBuf, line: usize, message: String },
+    Empty { path: PathBuf }
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ObjError::Io { path, error } => write!(f, "{}: {}", path.display(), error),
+            ObjError::Image { path, error } => write!(f, "{}: {}", path.display(), error),
+            ObjError::Parse { path, line, message } => write!(f, "{}:{}: {}", path.display(), line, message),
+            ObjError::Empty { path } => write!(f, "{}: no faces in the obj file", path.display())
+        }
+    }
+}
+
+impl std::error::Error for ObjError {}
+
+fn parse_error(path: &Path, line: usize, message: impl Into<String>) -> ObjError {
+    ObjError::Parse { path: path.to_path_buf(), line, message: message.into() }
+}
+
+fn read(path: &Path) -> Result<String, ObjError> {
+    fs::read_to_string(path).map_err(|error| ObjError::Io { path: path.to_path_buf(), error })
+}
+
+fn parse_floats(path: &Path, line: usize, args: &[&str], min: usize) -> Result<Vec<f64>, ObjError> {
+    if args.len() < min {
+        return Err(parse_error(path, line, format!("expected {} numbers, found {}", min, args.len())))
+    }
+    args.iter().map(|a| {
+        a.parse::<f64>().map_err(|_| parse_error(path, line, format!("invalid number '{}'", a)))
+    }).collect()
+}
+
+fn parse_color(path: &Path, line: usize, args: &[&str]) -> Result<Color, ObjError> {
+    let c = parse_floats(path, line, args, 1)?;
+    // a single value is a grey level
+    if c.len() < 3 {
+        Ok(Color::new(c[0], c[0], c[0]))
+    } else {
+        Ok(Color::new(c[0], c[1], c[2]))
+    }
+}
+
+fn is_black(c: Color) -> bool {
+    c.x() <= 0.0 && c.y() <= 0.0 && c.z() <= 0.0
+}
+
+#[derive(Clone)]
+struct MtlDescription {
+    kd: Color,
+    ks: Color,
+    ke: Color,
+    ns: f64,
+    ni: f64,
+    d: f64,
+    illum: u32,
+    map_kd: Option<PathBuf>
+}
+
+impl Default for MtlDescription {
+    fn default() -> MtlDescription {
+        MtlDescription {
+            kd: Color::new(0.73, 0.73, 0.73),
+            ks: Color::new(0.0, 0.0, 0.0),
+            ke: Color::new(0.0, 0.0, 0.0),
+            ns: 0.0,
+            ni: 1.0,
+            d: 1.0,
+            illum: 2,
+            map_kd: None
+        }
+    }
+}
+
+impl MtlDescription {
+    fn build(&self) -> Result<Arc<dyn Material>, ObjError> {
+        if !is_black(self.ke) {
+            return Ok(Arc::new(DiffuseLight::new(ConstantTexture::new(self.ke))))
+        }
+
+        // illumination models 4, 6, 7 and 9 are the glass models
+        if self.d < 1.0 || matches!(self.illum, 4 | 6 | 7 | 9) {
+            let ir = if self.ni > 1.0 { self.ni } else { 1.5 };
+            return Ok(Arc::new(Dielectric::new(ir)))
+        }
+
+        // mirror when ray traced reflection is on or when there is no diffuse part at all
+        if !is_black(self.ks) && (self.illum == 3 || (is_black(self.kd) && self.map_kd.is_none())) {
+            // turn the phong exponent into a roughness
+            let fuzz = (2.0 / (self.ns + 2.0)).sqrt().clamp(0.0, 1.0);
+            return Ok(Arc::new(Metal::new(self.ks, fuzz)))
+        }
+
+        match &self.map_kd {
+            Some(path) => {
+                let image = image::open(path)
+                    .map_err(|error| ObjError::Image { path: path.clone(), error })?
+                    .to_rgb8();
+                let (width, height) = image.dimensions();
+                let texture = ImageTexture::new(image.into_raw(), width, height);
+                Ok(Arc::new(Lambertian::new(texture)))
+            },
+            None => Ok(Arc::new(Lambertian::new(ConstantTexture::new(self.kd))))
+        }
+    }
+}
+
+/// read the materials of a .mtl library, keyed by name
+pub fn load_mtl(path: impl AsRef<Path>) -> Result<HashMap<String, Arc<dyn Material>>, ObjError> {
+    let path = path.as_ref();
+    let source = read(path)?;
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+
+    let mut descriptions: Vec<(String, MtlDescription)> = Vec::new();
+    for (index, line) in source.lines().enumerate() {
+        let line_number = index + 1;
+        let line = line.split('#').next().unwrap_or("").trim();
+        let mut tokens = line.split_whitespace();
+        let keyword = match tokens.next() {
+            Some(keyword) => keyword,
+            None => continue
+        };
+        let args: Vec<&str> = tokens.collect();
+
+        if keyword == "newmtl" {
+            let name = args.join(" ");
+            if name.is_empty() {
+                return Err(parse_error(path, line_number, "newmtl without a name"))
+            }
+            descriptions.push((name, MtlDescription::default()));
+            continue
+        }
+
+        let current = match descriptions.last_mut() {
+            Some((_, current)) => current,
+            None => return Err(parse_error(path, line_number, format!("'{}' before any newmtl", keyword)))
+        };
+        match keyword {
+            "Kd" => current.kd = parse_color(path, line_number, &args)?,
+            "Ks" => current.ks = parse_color(path, line_number, &args)?,
+            "Ke" => current.ke = parse_color(path, line_number, &args)?,
+            "Ns" => current.ns = parse_floats(path, line_number, &args, 1)?[0],
+            "Ni" => current.ni = parse_floats(path, line_number, &args, 1)?[0],
+            "d" => current.d = parse_floats(path, line_number, &args, 1)?[0],
+            "Tr" => current.d = 1.0 - parse_floats(path, line_number, &args, 1)?[0],
+            "illum" => {
+                current.illum = args.first().and_then(|a| a.parse().ok())
+                    .ok_or_else(|| parse_error(path, line_number, "invalid illumination model"))?
+            },
+            "map_Kd" => {
+                // texture options come first, the file name is last
+                let file = args.last()
+                    .ok_or_else(|| parse_error(path, line_number, "map_Kd without a file name"))?;
+                current.map_kd = Some(dir.join(file))
+            },
+            // everything else has no equivalent in the renderer
+            _ => ()
+        }
+    }
+
+    let mut materials = HashMap::new();
+    for (name, description) in descriptions {
+        materials.insert(name, description.build()?);
+    }
+    Ok(materials)
+}
+
+// obj corners are (position, uv, normal) triples, each unique triple is one mesh vertex
+type Corner = (usize, Option<usize>, Option<usize>);
+
+// faces sharing a group and a material become one mesh
+#[derive(Default)]
+struct MeshBuilder {
+    positions: Vec<Point3>,
+    normals: Vec<Vec3>,
+    uvs: Vec<(f64, f64)>,
+    indices: Vec<[usize; 3]>,
+    has_normals: bool,
+    has_uvs: bool,
+    vertices: HashMap<Corner, usize>
+}
+
+impl MeshBuilder {
+    fn new() -> MeshBuilder {
+        MeshBuilder {
+            has_normals: true,
+            has_uvs: true,
+            ..MeshBuilder::default()
+        }
+    }
+
+    fn vertex(&mut self, corner: Corner, obj: &ObjData) -> usize {
+        if let Some(&index) = self.vertices.get(&corner) {
+            return index
+        }
+
+        let (v, vt, vn) = corner;
+        self.positions.push(obj.positions[v]);
+        match vt {
+            Some(vt) => self.uvs.push(obj.uvs[vt]),
+            None => self.has_uvs = false
+        }
+        match vn {
+            Some(vn) => self.normals.push(obj.normals[vn]),
+            None => self.has_normals = false
+        }
+        let index = self.positions.len() - 1;
+        self.vertices.insert(corner, index);
+        index
+    }
+
+    fn build(self, material: Arc<dyn Material>, split: SplitMethod) -> TriangleMesh {
+        let normals = if self.has_normals { Some(self.normals) } else { None };
+        let uvs = if self.has_uvs { Some(self.uvs) } else { None };
+        TriangleMesh::with_split(self.positions, self.indices, normals, uvs, material, split)
+    }
+}
+
+#[derive(Default)]
+struct ObjData {
+    positions: Vec<Point3>,
+    normals: Vec<Vec3>,
+    uvs: Vec<(f64, f64)>
+}
+
+// obj indices start at 1, negative ones count back from the last element read so far
+fn resolve_index(path: &Path, line: usize, token: &str, count: usize) -> Result<usize, ObjError> {
+    let index = token.parse::<i64>().map_err(|_| parse_error(path, line, format!("invalid index '{}'", token)))?;
+    let resolved = if index < 0 { count as i64 + index } else { index - 1 };
+    if resolved < 0 || resolved >= count as i64 {
+        return Err(parse_error(path, line, format!("index {} out of range", index)))
+    }
+    Ok(resolved as usize)
+}
+
+fn parse_corner(path: &Path, line: usize, token: &str, obj: &ObjData) -> Result<Corner, ObjError> {
+    let mut parts = token.split('/');
+    let v = resolve_index(path, line, parts.next().unwrap_or(""), obj.positions.len())?;
+    let vt = match parts.next() {
+        Some(vt) if !vt.is_empty() => Some(resolve_index(path, line, vt, obj.uvs.len())?),
+        _ => None
+    };
+    let vn = match parts.next() {
+        Some(vn) if !vn.is_empty() => Some(resolve_index(path, line, vn, obj.normals.len())?),
+        _ => None
+    };
+    Ok((v, vt, vn))
+}
+
+/// read a wavefront .obj file and the .mtl libraries it references,
+/// returning one triangle mesh for every group and material pair, each with a bvh split by `split`.
+/// faces without a material get a grey diffuse one, a material missing from the libraries is an error
+pub fn load_obj(path: impl AsRef<Path>, split: SplitMethod) -> Result<Vec<TriangleMesh>, ObjError> {
+    let path = path.as_ref();
+    let source = read(path)?;
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+
+    let default_material: Arc<dyn Material> = Arc::new(Lambertian::new(ConstantTexture::new(Color::new(0.73, 0.73, 0.73))));
+    let mut materials: HashMap<String, Arc<dyn Material>> = HashMap::new();
+
+    let mut obj = ObjData::default();
+    let mut group = String::new();
+    let mut material = String::new();
+    // the line each material is first used on, to report unknown ones
+    let mut material_lines: HashMap<String, usize> = HashMap::new();
+    // keep the meshes in the order they first appear
+    let mut keys: Vec<(String, String)> = Vec::new();
+    let mut meshes: HashMap<(String, String), MeshBuilder> = HashMap::new();
+
+    for (index, line) in source.lines().enumerate() {
+        let line_number = index + 1;
+        let line = line.split('#').next().unwrap_or("").trim();
+        let mut tokens = line.split_whitespace();
+        let keyword = match tokens.next() {
+            Some(keyword) => keyword,
+            None => continue
+        };
+        let args: Vec<&str> = tokens.collect();
+
+        match keyword {
+            "v" => {
+                let v = parse_floats(path, line_number, &args, 3)?;
+                obj.positions.push(Point3::new(v[0], v[1], v[2]));
+            },
+            "vn" => {
+                let n = parse_floats(path, line_number, &args, 3)?;
+                obj.normals.push(Vec3::new(n[0], n[1], n[2]));
+            },
+            "vt" => {
+                let uv = parse_floats(path, line_number, &args, 1)?;
+                obj.uvs.push((uv[0], uv.get(1).copied().unwrap_or(0.0)));
+            },
+            "g" | "o" => group = args.join(" "),
+            "usemtl" => {
+                material = args.join(" ");
+                material_lines.entry(material.clone()).or_insert(line_number);
+            },
+            "mtllib" => {
+                for file in &args {
+                    materials.extend(load_mtl(dir.join(file))?);
+                }
+            },
+            "f" => {
+                if args.len() < 3 {
+                    return Err(parse_error(path, line_number, "face with less than 3 vertices"))
+                }
+                let corners = args.iter()
+                    .map(|token| parse_corner(path, line_number, token, &obj))
+                    .collect::<Result<Vec<Corner>, ObjError>>()?;
+
+                let key = (group.clone(), material.clone());
+                let mesh = meshes.entry(key.clone()).or_insert_with(|| {
+                    keys.push(key);
+                    MeshBuilder::new()
+                });
+                let vertices: Vec<usize> = corners.into_iter().map(|corner| mesh.vertex(corner, &obj)).collect();
+
+                // triangulate polygons as a fan around the first vertex
+                for i in 1..vertices.len() - 1 {
+                    mesh.indices.push([vertices[0], vertices[i], vertices[i + 1]]);
+                }
+            },
+            // smoothing groups, lines, points and curves are not supported
+            _ => ()
+        }
+    }
+
+    if keys.is_empty() {
+        return Err(ObjError::Empty { path: path.to_path_buf() })
+    }
+    let mut list = Vec::with_capacity(keys.len());
+    for key in keys {
+        let mesh = meshes.remove(&key).unwrap();
+        let material = if key.1.is_empty() {
+            default_material.clone()
+        } else {
+            match materials.get(&key.1) {
+                Some(material) => material.clone(),
+                None => return Err(parse_error(path, material_lines[&key.1], format!("material '{}' not found", key.1)))
+            }
+        };
+        list.push(mesh.build(material, split));
+    }
+    Ok(list)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ray::Ray;
+    use crate::sampler::Sampler;
+    use crate::test_util::{hit, scratch_dir};
+    use super::*;
+
+    // writes the files into a directory of their own and loads the first one
+    fn load(name: &str, files: &[(&str, &str)]) -> Result<Vec<TriangleMesh>, ObjError> {
+        let dir = scratch_dir(name);
+        for (file, source) in files {
+            fs::write(dir.join(file), source).unwrap();
+        }
+        let meshes = load_obj(dir.join(files[0].0), SplitMethod::Sah);
+        fs::remove_dir_all(dir).unwrap();
+        meshes
+    }
+
+    fn parse_error_at(result: Result<Vec<TriangleMesh>, ObjError>) -> (usize, String) {
+        match result {
+            Err(ObjError::Parse { line, message, .. }) => (line, message),
+            Err(e) => panic!("expected a parse error, found {}", e),
+            Ok(_) => panic!("the file was accepted")
+        }
+    }
+
+    // straight down onto the plane z = 0
+    fn hits_at(mesh: &TriangleMesh, x: f64, y: f64) -> bool {
+        hit(mesh, Point3::new(x, y, 1.0), Vec3::new(0.0, 0.0, -1.0)).is_some()
+    }
+
+    const PENTAGON: &str = "v 0 0 0\nv 2 0 0\nv 3 1 0\nv 1 2 0\nv -1 1 0\n";
+
+    #[test]
+    fn polygons_are_fans() {
+        let meshes = load("fan", &[("fan.obj", &format!("{}f 1 2 3 4 5\n", PENTAGON))]).unwrap();
+        assert_eq!(meshes.len(), 1);
+        // one point inside each of the three triangles around the first vertex
+        for (x, y) in [(1.5, 0.3), (1.5, 1.0), (0.2, 1.0)] {
+            assert!(hits_at(&meshes[0], x, y), "miss at {} {}", x, y);
+        }
+        assert!(!hits_at(&meshes[0], 3.0, 2.0));
+    }
+
+    #[test]
+    fn negative_indices_count_back() {
+        let meshes = load("negative", &[("negative.obj", &format!("{}f -5 -4 -3 -2 -1\n", PENTAGON))]).unwrap();
+        assert!(hits_at(&meshes[0], 0.2, 1.0));
+
+        let (line, message) = parse_error_at(load("negative_range", &[("range.obj", &format!("{}f -6 -4 -3\n", PENTAGON))]));
+        assert_eq!((line, message.as_str()), (6, "index -6 out of range"));
+    }
+
+    #[test]
+    fn corner_forms() {
+        let header = "v 0 0 0\nv 3 0 0\nv 0 3 0\nvt 0 0\nvt 1 0\nvt 0 1\nvn 0 0.6 0.8\n";
+        let down = Vec3::new(0.0, 0.0, -1.0);
+        for (name, face, uv, normal) in [("v", "f 1 2 3", false, false),
+                                         ("v_vt", "f 1/1 2/2 3/3", true, false),
+                                         ("v_vn", "f 1//1 2//1 3//1", false, true),
+                                         ("v_vt_vn", "f 1/1/1 2/2/1 3/3/1", true, true)] {
+            let meshes = load(&format!("corner_{}", name), &[("corners.obj", &format!("{}{}\n", header, face))]).unwrap();
+            let rec = hit(&meshes[0], Point3::new(1.0, 1.0, 1.0), down).unwrap();
+            if uv {
+                assert!((rec.u - 1.0 / 3.0).abs() < 1e-12 && (rec.v - 1.0 / 3.0).abs() < 1e-12, "{}", name);
+            }
+            // the shading normal comes from vn, otherwise it is the face normal
+            let expected = if normal { Vec3::new(0.0, 0.6, 0.8) } else { Vec3::new(0.0, 0.0, 1.0) };
+            assert!((rec.normal - expected).length() < 1e-12, "{}: normal {}", name, rec.normal);
+        }
+    }
+
+    #[test]
+    fn usemtl_takes_the_diffuse_color() {
+        let meshes = load("usemtl", &[("red.obj", "mtllib red.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\nusemtl red\nf 1 2 3\n"),
+                                      ("red.mtl", "newmtl red\nKd 0.8 0.1 0.2\n")]).unwrap();
+        let rec = hit(&meshes[0], Point3::new(0.2, 0.2, 1.0), Vec3::new(0.0, 0.0, -1.0)).unwrap();
+        let r = Ray::new(Point3::new(0.2, 0.2, 1.0), Vec3::new(0.0, 0.0, -1.0), 0.0);
+        let (attenuation, _) = rec.material.scatter(&r, &rec, &mut Sampler::new(0)).unwrap();
+        assert_eq!((attenuation.x(), attenuation.y(), attenuation.z()), (0.8, 0.1, 0.2));
+    }
+
+    #[test]
+    fn errors_name_their_line() {
+        let unknown = "v 0 0 0\nv 1 0 0\nv 0 1 0\n\nusemtl blue\nf 1 2 3\n";
+        assert_eq!(parse_error_at(load("unknown", &[("unknown.obj", unknown)])), (5, "material 'blue' not found".to_string()));
+
+        let short = "v 0 0 0\nv 1 0 0\nf 1 2\n";
+        assert_eq!(parse_error_at(load("short", &[("short.obj", short)])), (3, "face with less than 3 vertices".to_string()));
+
+        match load("empty", &[("empty.obj", "# only vertices\nv 0 0 0\n")]) {
+            Err(ObjError::Empty { path }) => assert!(path.ends_with("empty.obj")),
+            _ => panic!("a file without faces was accepted")
+        }
+    }
+}
@@ -1,5 +1,6 @@
 use super::vec::Vec3;
 
+/// orthonormal basis
 pub struct ONB{
     axis:[Vec3;3]
 }
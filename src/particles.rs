@@ -0,0 +1,231 @@
+use std::collections::HashMap;
+use super::vec::{Vec3, Point3, Color};
+use super::ray::Ray;
+use super::sampler::Sampler;
+use super::hit::{Hittable, HitRecord};
+use super::mat::Material;
+use super::aabb;
+use super::aabb::AABB;
+use super::bvh::{LinearTree, BVHStats, SplitMethod};
+use super::sphere::get_sphere_uv;
+
+/// many spheres kept in flat arrays with a bvh of their own, for simulation output where a
+/// `Sphere` per particle would be too big
+///
+/// every particle has a position and a radius, and optionally a color, see `with_colors`, and a
+/// velocity for motion blur, see `with_velocities`
+pub struct ParticleSet<M: Material> {
+    positions: Vec<Point3>,
+    radii: Vec<f64>,
+    velocities: Option<Vec<Vec3>>,
+    // positions are those at time0, the boxes cover the motion until time1
+    time0: f64,
+    time1: f64,
+    // the material of each particle, empty when they all use the first one
+    material_index: Vec<u32>,
+    materials: Vec<M>,
+    split: SplitMethod,
+    tree: LinearTree,
+    // the particles in the order of the leaves of the tree
+    order: Vec<u32>
+}
+
+impl<M: Material> ParticleSet<M> {
+    /// the tree is split with the surface area heuristic
+    pub fn new(positions: Vec<Point3>, radii: Vec<f64>, material: M) -> ParticleSet<M> {
+        ParticleSet::with_split(positions, radii, material, SplitMethod::Sah)
+    }
+
+    pub fn with_split(positions: Vec<Point3>, radii: Vec<f64>, material: M, split: SplitMethod) -> ParticleSet<M> {
+        assert!(!positions.is_empty(), "a particle set needs at least one particle");
+        assert!(positions.len() <= u32::MAX as usize, "too many particles in one set");
+        assert_eq!(radii.len(), positions.len(), "a particle set needs one radius per particle");
+        assert!(radii.iter().all(|&r| r > 0.0), "the radius of a particle must be positive");
+        // the particles stand still until `with_velocities`
+        let boxes: Vec<AABB> = positions.iter().zip(&radii).map(|(&p, &r)| {
+            let r = Vec3::new(r, r, r);
+            AABB::new(p - r, p + r)
+        }).collect();
+        let (tree, order) = ParticleSet::<M>::tree(&boxes, split);
+        ParticleSet {
+            positions,
+            radii,
+            velocities: None,
+            time0: 0.0,
+            time1: 1.0,
+            material_index: Vec::new(),
+            materials: vec![material],
+            split,
+            tree,
+            order
+        }
+    }
+
+    /// gives every particle its own color, replacing the material of `new` with the ones made by
+    /// `material`. colors are rounded to 8 bits per channel and particles of the same color share
+    /// one material
+    pub fn with_colors(mut self, colors: &[Color], material: impl Fn(Color) -> M) -> ParticleSet<M> {
+        assert_eq!(colors.len(), self.positions.len(), "a particle set needs one color per particle");
+        let mut palette: HashMap<[u8; 3], u32> = HashMap::new();
+        let mut materials = Vec::new();
+        self.material_index = colors.iter().map(|c| {
+            let key = [0, 1, 2].map(|a| (c[a].clamp(0.0, 1.0) * 255.0).round() as u8);
+            *palette.entry(key).or_insert_with(|| {
+                materials.push(material(Color::new(key[0] as f64 / 255.0, key[1] as f64 / 255.0, key[2] as f64 / 255.0)));
+                materials.len() as u32 - 1
+            })
+        }).collect();
+        self.materials = materials;
+        self
+    }
+
+    /// moves each particle from its position at time0 with its velocity, the tree is rebuilt
+    /// around the paths of the particles until time1
+    pub fn with_velocities(mut self, velocities: Vec<Vec3>, time0: f64, time1: f64) -> ParticleSet<M> {
+        assert_eq!(velocities.len(), self.positions.len(), "a particle set needs one velocity per particle");
+        self.velocities = Some(velocities);
+        self.time0 = time0;
+        self.time1 = time1;
+        let boxes: Vec<AABB> = (0..self.positions.len()).map(|i| self.particle_box(i)).collect();
+        (self.tree, self.order) = ParticleSet::<M>::tree(&boxes, self.split);
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    fn center(&self, i: usize, time: f64) -> Point3 {
+        match &self.velocities {
+            Some(velocities) => self.positions[i] + (time - self.time0) * velocities[i],
+            None => self.positions[i]
+        }
+    }
+
+    // the box of a particle over the whole motion
+    fn particle_box(&self, i: usize) -> AABB {
+        let r = Vec3::new(self.radii[i], self.radii[i], self.radii[i]);
+        let (start, end) = (self.center(i, self.time0), self.center(i, self.time1));
+        aabb::surrounding_box(&AABB::new(start - r, start + r), &AABB::new(end - r, end + r))
+    }
+
+    fn tree(boxes: &[AABB], split: SplitMethod) -> (LinearTree, Vec<u32>) {
+        let (tree, order) = LinearTree::build(boxes, split);
+        (tree, order.into_iter().map(|i| i as u32).collect())
+    }
+
+    pub fn stats(&self) -> BVHStats {
+        self.tree.stats()
+    }
+
+    // the nearest root of the sphere of particle i within the range
+    fn hit_particle(&self, r: &Ray, i: usize, t_min: f64, t_max: f64) -> Option<f64> {
+        let oc = r.origin() - self.center(i, r.time());
+        let a = r.direction().dot(r.direction());
+        let half_b = oc.dot(r.direction());
+        let c = oc.dot(oc) - self.radii[i] * self.radii[i];
+        let discriminant = half_b * half_b - a * c;
+        if discriminant < 0.0 {
+            return None
+        }
+        let sqrt_d = discriminant.sqrt();
+        [(-half_b - sqrt_d) / a, (-half_b + sqrt_d) / a].into_iter().find(|&t| t >= t_min && t <= t_max)
+    }
+}
+
+impl<M: Material> Hittable for ParticleSet<M> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, _rng: &mut Sampler) -> Option<HitRecord<'_>> {
+        // only the nearest particle gets a hit record
+        let mut closest = None;
+        self.tree.traverse(r, t_min, t_max, |place, t_max| {
+            let i = self.order[place] as usize;
+            let t = self.hit_particle(r, i, t_min, t_max)?;
+            closest = Some((i, t));
+            Some(t)
+        });
+
+        let (i, t) = closest?;
+        let material = match self.material_index.get(i) {
+            Some(&m) => &self.materials[m as usize],
+            None => &self.materials[0]
+        };
+        let position = r.at(t);
+        let outward_normal = (position - self.center(i, r.time())) / self.radii[i];
+        let (u, v) = get_sphere_uv(&outward_normal);
+        let mut rec = HitRecord {
+            position,
+            normal: outward_normal,
+            t,
+            u,
+            v,
+            // u goes around the y axis like on a sphere
+            tangent: Vec3::new(outward_normal.z(), 0.0, -outward_normal.x()),
+            front_face: false,
+            material
+        };
+        rec.set_face_normal(r, outward_normal);
+        Some(rec)
+    }
+
+    fn bounding_box(&self, _t0: f64, _t1: f64) -> Option<AABB> {
+        Some(self.tree.bbox())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng};
+    use rand::rngs::StdRng;
+    use crate::test_util::{grey, Grey};
+    use super::*;
+
+    // the nearest particle by testing every one of them
+    fn brute_force<M: Material>(particles: &ParticleSet<M>, r: &Ray) -> Option<f64> {
+        (0..particles.len()).filter_map(|i| particles.hit_particle(r, i, 0.001, f64::INFINITY))
+            .min_by(|a, b| a.partial_cmp(b).unwrap())
+    }
+
+    fn cloud(rng: &mut StdRng, split: SplitMethod) -> ParticleSet<Grey> {
+        let positions: Vec<Point3> = (0..500).map(|_| Vec3::new(rng.gen_range(-10.0..10.0), rng.gen_range(-10.0..10.0), rng.gen_range(-10.0..10.0))).collect();
+        let radii = (0..500).map(|_| rng.gen_range(0.05..0.5)).collect();
+        ParticleSet::with_split(positions, radii, grey(), split)
+    }
+
+    fn check_rays<M: Material>(particles: &ParticleSet<M>, rng: &mut StdRng) {
+        let mut hits = 0;
+        for _ in 0..2000 {
+            let origin = Vec3::new(rng.gen_range(-15.0..15.0), rng.gen_range(-15.0..15.0), -20.0);
+            let target = Vec3::new(rng.gen_range(-10.0..10.0), rng.gen_range(-10.0..10.0), rng.gen_range(-10.0..10.0));
+            let r = Ray::new(origin, target - origin, rng.gen_range(0.0..1.0));
+            let expected = brute_force(particles, &r);
+            let rec = particles.hit(&r, 0.001, f64::INFINITY, &mut Sampler::new(0));
+            assert_eq!(rec.as_ref().map(|rec| rec.t), expected);
+            hits += rec.is_some() as usize;
+        }
+        assert!(hits > 100);
+    }
+
+    #[test]
+    fn tree_finds_the_nearest_particle() {
+        let mut rng = StdRng::seed_from_u64(5);
+        for split in [SplitMethod::Sah, SplitMethod::Median] {
+            let particles = cloud(&mut rng, split);
+            assert!(particles.stats().leaves > 1);
+            check_rays(&particles, &mut rng);
+        }
+    }
+
+    #[test]
+    fn moving_particles_are_found_along_their_paths() {
+        let mut rng = StdRng::seed_from_u64(6);
+        for split in [SplitMethod::Sah, SplitMethod::Median] {
+            let velocities = (0..500).map(|_| Vec3::new(rng.gen_range(-3.0..3.0), rng.gen_range(-3.0..3.0), 0.0)).collect();
+            let particles = cloud(&mut rng, split).with_velocities(velocities, 0.0, 1.0);
+            check_rays(&particles, &mut rng);
+        }
+    }
+}
@@ -3,9 +3,9 @@ use rand::Rng;
 use super::hit::Hittable;
 use super::vec::{Vec3, Point3};
 use super::onb::ONB;
+use super::sampler::Sampler;
 
-fn random_cosine_direction() -> Vec3 {
-    let mut rng = rand::thread_rng();
+fn random_cosine_direction(rng: &mut Sampler) -> Vec3 {
     let r1 = rng.gen::<f64>();
     let r2 = rng.gen::<f64>();
     let z = (1.0 - r2).sqrt();
@@ -16,9 +16,10 @@ fn random_cosine_direction() -> Vec3 {
     Vec3::new(x, y, z)
 }
 
+/// probability density over directions, used for importance sampling
 pub enum PDF<'a> {
     Cosine { uvw: ONB },
-    Hittable { origin: Point3, hittable: &'a Box<dyn Hittable> },
+    Hittable { origin: Point3, hittable: &'a dyn Hittable },
     Mixture { p0: &'a PDF<'a>, p1: &'a PDF<'a> }
 }
 
@@ -29,7 +30,7 @@ impl<'a> PDF<'a> {
         }
     }
 
-    pub fn hittable_pdf(origin: Point3, hittable: &'a Box<dyn Hittable>) -> PDF<'a> {
+    pub fn hittable_pdf(origin: Point3, hittable: &'a dyn Hittable) -> PDF<'a> {
         PDF::Hittable { origin, hittable }
     }
 
@@ -37,7 +38,7 @@ impl<'a> PDF<'a> {
         PDF::Mixture { p0, p1 }
     }
 
-    pub fn value(&self, direction: Vec3) -> f64 {
+    pub fn value(&self, direction: Vec3, rng: &mut Sampler) -> f64 {
         match self {
             PDF::Cosine { uvw } => {
                 let cosine = direction.normalized().dot(uvw.w());
@@ -49,28 +50,27 @@ impl<'a> PDF<'a> {
                 }
             },
             PDF::Hittable { origin, hittable } => {
-                hittable.pdf_value(*origin, direction)
+                hittable.pdf_value(*origin, direction, rng)
             },
             PDF::Mixture { p0, p1 } => {
-                0.5 * p0.value(direction) + 0.5 * p1.value(direction)
+                0.5 * p0.value(direction, rng) + 0.5 * p1.value(direction, rng)
             }
         }
     }
 
-    pub fn generate(&self) -> Vec3 {
+    pub fn generate(&self, rng: &mut Sampler) -> Vec3 {
         match self {
             PDF::Cosine { uvw } => {
-                uvw.local(&random_cosine_direction())
+                uvw.local(&random_cosine_direction(rng))
             },
             PDF::Hittable { origin, hittable } => {
-                hittable.random(*origin)
+                hittable.random(*origin, rng)
             },
             PDF::Mixture { p0, p1 } => {
-                let mut rng = rand::thread_rng();
                 if rng.gen::<bool>() {
-                    p0.generate()
+                    p0.generate(rng)
                 } else {
-                    p1.generate()
+                    p1.generate(rng)
                 }
             }
         }
@@ -1,25 +1,15 @@
 use rand::Rng;
 use super::vec::{Vec3, Point3};
 
-fn generate_float() -> Vec<f64> {
-    let mut rng = rand::thread_rng();
-    let mut f = Vec::with_capacity(256);
-    for _ in 0..256 {
-        f.push(rng.gen_range(0.0..1.0))
-    }
-    f
-}
-
-fn generate_vector() -> Vec<Vec3> {
+fn generate_vector(rng: &mut impl Rng) -> Vec<Vec3> {
     let mut v = Vec::with_capacity(256);
     for _ in 0..256 {
-        v.push(Vec3::random_in_unit_sphere())
+        v.push(Vec3::random_in_unit_sphere(rng))
     }
     v
 }
 
-fn permute(a: &mut [usize], n: usize) {
-    let mut rng = rand::thread_rng();
+fn permute(a: &mut [usize], n: usize, rng: &mut impl Rng) {
     for i in (0..n as usize).rev() {
         let target = rng.gen_range(0..=i);
         // swap the two elements in the slice
@@ -27,12 +17,12 @@ fn permute(a: &mut [usize], n: usize) {
     }
 }
 
-fn generate_perm() -> Vec<usize> {
+fn generate_perm(rng: &mut impl Rng) -> Vec<usize> {
     let mut p = Vec::with_capacity(256);
     for i in 0..256 {
         p.push(i);
     }
-    permute(&mut p, 256);
+    permute(&mut p, 256, rng);
     p
 }
 
@@ -55,6 +45,7 @@ fn perlin_interp(c: &[[[Vec3; 2]; 2]; 2], u: f64, v: f64, w: f64) -> f64 {
     accum
 }
 
+/// perlin noise generator
 #[derive(Clone)]
 pub struct Perlin {
     rd_vec: Vec<Vec3>,
@@ -64,12 +55,13 @@ pub struct Perlin {
 }
 
 impl Perlin {
-    pub fn new() -> Perlin {
+    /// the lattice is drawn from `rng`, so the same generator state gives the same noise
+    pub fn new(rng: &mut impl Rng) -> Perlin {
         Perlin {
-            rd_vec: generate_vector(),
-            perm_x: generate_perm(),
-            perm_y: generate_perm(),
-            perm_z: generate_perm()
+            rd_vec: generate_vector(rng),
+            perm_x: generate_perm(rng),
+            perm_y: generate_perm(rng),
+            perm_z: generate_perm(rng)
         }
     }
 
@@ -0,0 +1,183 @@
+use rand::Rng;
+use super::mat::Material;
+use super::hit::{Hittable, HitRecord};
+use super::aabb::AABB;
+use super::vec::{Vec3, Point3};
+use super::ray::Ray;
+use super::sampler::Sampler;
+use super::displace::{Tessellate, Tessellation};
+
+/// whether the edges u and v span a quad with an area
+pub fn spans_plane(u: Vec3, v: Vec3) -> bool {
+    let area = u.cross(v).length();
+    area > 0.0 && area.is_finite()
+}
+
+/// parallelogram spanned by the edges u and v from the corner q, in any orientation
+///
+/// the outward normal is u x v, so the corners should go counterclockwise when seen from the front
+#[derive(Clone)]
+pub struct Quad<M: Material> {
+    q: Point3,
+    u: Vec3,
+    v: Vec3,
+    normal: Vec3,
+    // the plane is normal . p = d
+    d: f64,
+    // u x v / |u x v|^2, turns a point of the plane into its coordinates along u and v
+    w: Vec3,
+    area: f64,
+    material: M
+}
+
+impl<M: Material> Quad<M> {
+    pub fn new(q: Point3, u: Vec3, v: Vec3, material: M) -> Quad<M> {
+        assert!(spans_plane(u, v), "the edges of a quad must not be parallel");
+        let n = u.cross(v);
+        let normal = n.normalized();
+        Quad {
+            q,
+            u,
+            v,
+            normal,
+            d: normal.dot(q),
+            w: n / n.dot(n),
+            area: n.length(),
+            material
+        }
+    }
+}
+
+impl<M: Material> Hittable for Quad<M> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, _rng: &mut Sampler) -> Option<HitRecord<'_>> {
+        let denominator = self.normal.dot(r.direction());
+        // parallel to the plane
+        if denominator.abs() < 1.0e-12 {
+            return None
+        }
+        let t = (self.d - self.normal.dot(r.origin())) / denominator;
+        if t < t_min || t > t_max {
+            return None
+        }
+
+        let p = r.at(t);
+        let planar = p - self.q;
+        let alpha = self.w.dot(planar.cross(self.v));
+        let beta = self.w.dot(self.u.cross(planar));
+        if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+            return None
+        }
+
+        let mut rec = HitRecord {
+            position: p,
+            normal: self.normal,
+            t,
+            u: alpha,
+            v: beta,
+            tangent: self.u,
+            front_face: false,
+            material: &self.material
+        };
+        rec.set_face_normal(r, self.normal);
+        Some(rec)
+    }
+
+    fn bounding_box(&self, _t0: f64, _t1: f64) -> Option<AABB> {
+        let corners = [self.q, self.q + self.u, self.q + self.v, self.q + self.u + self.v];
+        let mut min = corners[0];
+        let mut max = corners[0];
+        for c in &corners[1..] {
+            for a in 0..3 {
+                min[a] = min[a].min(c[a]);
+                max[a] = max[a].max(c[a]);
+            }
+        }
+        // pad the flat sides like the rectangles do
+        for a in 0..3 {
+            if max[a] - min[a] < 0.0002 {
+                min[a] -= 0.0001;
+                max[a] += 0.0001;
+            }
+        }
+        Some(AABB::new(min, max))
+    }
+
+    fn pdf_value(&self, o: Point3, v: Vec3, rng: &mut Sampler) -> f64 {
+        if let Some(rec) = self.hit(&Ray::new(o, v, 0.0), 0.001, f64::INFINITY, rng) {
+            let distance_squared = rec.t.powi(2) * v.length().powi(2);
+            let cosine = v.dot(rec.normal).abs() / v.length();
+            if cosine != 0.0 { distance_squared / (cosine * self.area) } else { 0.0 }
+        } else {
+            0.0
+        }
+    }
+
+    fn random(&self, o: Vec3, rng: &mut Sampler) -> Vec3 {
+        self.q + rng.gen::<f64>() * self.u + rng.gen::<f64>() * self.v - o
+    }
+}
+
+impl<M: Material> Tessellate for Quad<M> {
+    fn tessellate(&self, resolution: usize) -> Tessellation {
+        Tessellation::quad(self.q, self.u, self.v, resolution)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::{grey, hit, Grey};
+    use super::*;
+
+    // a quad in the plane x + y = 2 from (2, 0) to (0, 2) and 3 high, facing away from the origin
+    fn slanted() -> Quad<Grey> {
+        Quad::new(Point3::new(2.0, 0.0, 0.0), Vec3::new(-2.0, 2.0, 0.0), Vec3::new(0.0, 0.0, 3.0), grey())
+    }
+
+    #[test]
+    fn parallel_edges_span_no_plane() {
+        assert!(spans_plane(Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 2.0)));
+        assert!(!spans_plane(Vec3::new(1.0, 2.0, 3.0), Vec3::new(-2.0, -4.0, -6.0)));
+        assert!(!spans_plane(Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 0.0)));
+    }
+
+    #[test]
+    fn hit_inside_the_parallelogram() {
+        let quad = slanted();
+        let rec = hit(&quad, Point3::new(0.0, 0.0, 1.5), Vec3::new(1.0, 1.0, 0.0)).unwrap();
+        assert!((rec.t - 1.0).abs() < 1e-12);
+        assert!((rec.u - 0.5).abs() < 1e-12 && (rec.v - 0.5).abs() < 1e-12);
+        assert!(!rec.front_face);
+        assert!(hit(&quad, Point3::new(0.0, 0.0, 3.5), Vec3::new(1.0, 1.0, 0.0)).is_none());
+    }
+
+    #[test]
+    fn area_light_sampling() {
+        let quad = slanted();
+        let mut rng = Sampler::new(7);
+        let o = Point3::new(0.0, 0.0, 1.5);
+        // straight at the center the solid angle density is distance^2 / area
+        let area = 3.0 * 8.0f64.sqrt();
+        assert!((quad.pdf_value(o, Vec3::new(1.0, 1.0, 0.0), &mut rng) - 2.0 / area).abs() < 1e-12);
+        assert_eq!(quad.pdf_value(o, Vec3::new(-1.0, 0.0, 0.0), &mut rng), 0.0);
+        // the samples land on the quad, and their mean pdf estimates its solid angle
+        let mut solid_angle = 0.0;
+        let n = 20000;
+        for _ in 0..n {
+            let direction = quad.random(o, &mut rng);
+            let rec = hit(&quad, o, direction).expect("a sample off the quad");
+            assert!((rec.position.x() + rec.position.y() - 2.0).abs() < 1e-9);
+            solid_angle += 1.0 / quad.pdf_value(o, direction, &mut rng);
+        }
+        solid_angle /= n as f64;
+        // the uniform estimate of the solid angle agrees with sampling it by direction
+        let mut by_direction = 0.0;
+        for _ in 0..n {
+            let d = Vec3::random_in_unit_sphere(&mut rng);
+            if quad.pdf_value(o, d, &mut rng) > 0.0 {
+                by_direction += 4.0 * std::f64::consts::PI;
+            }
+        }
+        by_direction /= n as f64;
+        assert!((solid_angle - by_direction).abs() < 0.05 * by_direction, "{} {}", solid_angle, by_direction);
+    }
+}
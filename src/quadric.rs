@@ -0,0 +1,460 @@
+use std::f64;
+use rand::Rng;
+use super::vec::{Vec3, Point3};
+use super::ray::Ray;
+use super::hit::{Hittable, HitRecord};
+use super::mat::Material;
+use super::aabb::AABB;
+use super::sampler::Sampler;
+
+// the quadrics are built around the y axis, use a Transform to place them
+
+/// real roots of a t^2 + b t + c in increasing order, avoiding the cancellation of the textbook formula
+pub(crate) fn solve_quadratic(a: f64, b: f64, c: f64) -> Option<(f64, f64)> {
+    if a == 0.0 {
+        if b == 0.0 {
+            return None
+        }
+        return Some((-c / b, -c / b))
+    }
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return None
+    }
+    let q = -0.5 * (b + b.signum() * discriminant.sqrt());
+    if q == 0.0 {
+        return Some((0.0, 0.0))
+    }
+    let (t0, t1) = (q / a, c / q);
+    Some(if t0 < t1 { (t0, t1) } else { (t1, t0) })
+}
+
+// angle around the y axis from +x towards +z, in [0, 2pi)
+pub(crate) fn phi_of(p: Point3) -> f64 {
+    let phi = p.z().atan2(p.x());
+    if phi < 0.0 { phi + 2.0 * f64::consts::PI } else { phi }
+}
+
+fn sweep_radians(degrees: f64) -> f64 {
+    assert!(degrees > 0.0 && degrees <= 360.0, "the sweep angle must be in (0, 360] degrees");
+    degrees.to_radians()
+}
+
+pub(crate) fn record<'a>(r: &Ray, t: f64, outward_normal: Vec3, u: f64, v: f64, material: &'a dyn Material) -> HitRecord<'a> {
+    let position = r.at(t);
+    let mut rec = HitRecord {
+        position,
+        normal: outward_normal,
+        t,
+        u,
+        v,
+        // u follows phi around the y axis
+        tangent: Vec3::new(-position.z(), 0.0, position.x()),
+        front_face: false,
+        material
+    };
+    rec.set_face_normal(r, outward_normal);
+    rec
+}
+
+// the hit with the ring sector inner_radius..radius, 0..phi_max in the plane y = height,
+// as t, the point, its angle and its distance from the axis
+fn disk_hit(r: &Ray, height: f64, radius: f64, inner_radius: f64, phi_max: f64, t_min: f64, t_max: f64) -> Option<(f64, Point3, f64, f64)> {
+    if r.direction().y() == 0.0 {
+        return None
+    }
+    let t = (height - r.origin().y()) / r.direction().y();
+    if t < t_min || t > t_max {
+        return None
+    }
+    let p = r.at(t);
+    let distance = (p.x() * p.x() + p.z() * p.z()).sqrt();
+    if distance > radius || distance < inner_radius {
+        return None
+    }
+    let phi = phi_of(p);
+    if phi > phi_max {
+        return None
+    }
+    Some((t, p, phi, distance))
+}
+
+fn random_on_disk(height: f64, radius: f64, inner_radius: f64, phi_max: f64, rng: &mut Sampler) -> Point3 {
+    // uniform in area, so the square of the distance is uniform
+    let distance = (inner_radius.powi(2) + rng.gen::<f64>() * (radius.powi(2) - inner_radius.powi(2))).sqrt();
+    let phi = rng.gen::<f64>() * phi_max;
+    Point3::new(distance * phi.cos(), height, distance * phi.sin())
+}
+
+// solid angle density of a uniformly sampled point on a surface of the given area, summed over
+// every point of the surface the direction passes through
+fn area_pdf(h: &dyn Hittable, o: Point3, v: Vec3, area: f64, rng: &mut Sampler) -> f64 {
+    let mut pdf = 0.0;
+    let mut t_min = 0.001;
+    while let Some(rec) = h.hit(&Ray::new(o, v, 0.0), t_min, f64::INFINITY, rng) {
+        let distance_squared = rec.t.powi(2) * v.length().powi(2);
+        let cosine = v.dot(rec.normal).abs() / v.length();
+        if cosine != 0.0 {
+            pdf += distance_squared / (cosine * area);
+        }
+        t_min = rec.t * (1.0 + 1.0e-9) + 1.0e-9;
+    }
+    pdf
+}
+
+/// flat disk facing +y at the given height, optionally with a hole and cut to a sector
+#[derive(Clone)]
+pub struct Disk<M: Material> {
+    height: f64,
+    radius: f64,
+    inner_radius: f64,
+    phi_max: f64,
+    material: M
+}
+
+impl<M: Material> Disk<M> {
+    pub fn new(height: f64, radius: f64, material: M) -> Disk<M> {
+        assert!(radius > 0.0, "the disk radius must be positive");
+        Disk { height, radius, inner_radius: 0.0, phi_max: 2.0 * f64::consts::PI, material }
+    }
+
+    /// cuts a hole of the given radius out of the middle
+    pub fn with_inner_radius(mut self, inner_radius: f64) -> Disk<M> {
+        assert!(inner_radius >= 0.0 && inner_radius < self.radius, "the inner radius must be in [0, radius)");
+        self.inner_radius = inner_radius;
+        self
+    }
+
+    /// keeps the sector from +x towards +z up to the given angle in degrees
+    pub fn with_sweep(mut self, degrees: f64) -> Disk<M> {
+        self.phi_max = sweep_radians(degrees);
+        self
+    }
+
+    fn area(&self) -> f64 {
+        0.5 * self.phi_max * (self.radius.powi(2) - self.inner_radius.powi(2))
+    }
+}
+
+impl<M: Material> Hittable for Disk<M> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, _rng: &mut Sampler) -> Option<HitRecord<'_>> {
+        let (t, _, phi, distance) = disk_hit(r, self.height, self.radius, self.inner_radius, self.phi_max, t_min, t_max)?;
+        let u = phi / self.phi_max;
+        let v = (self.radius - distance) / (self.radius - self.inner_radius);
+        Some(record(r, t, Vec3::new(0.0, 1.0, 0.0), u, v, &self.material))
+    }
+
+    fn bounding_box(&self, _t0: f64, _t1: f64) -> Option<AABB> {
+        // padded like the rectangles so the box is not flat
+        Some(AABB::new(Point3::new(-self.radius, self.height - 0.0001, -self.radius),
+                       Point3::new(self.radius, self.height + 0.0001, self.radius)))
+    }
+
+    fn pdf_value(&self, o: Point3, v: Vec3, rng: &mut Sampler) -> f64 {
+        area_pdf(self, o, v, self.area(), rng)
+    }
+
+    fn random(&self, o: Vec3, rng: &mut Sampler) -> Vec3 {
+        random_on_disk(self.height, self.radius, self.inner_radius, self.phi_max, rng) - o
+    }
+}
+
+/// cylinder of the given radius between the heights y0 and y1
+#[derive(Clone)]
+pub struct Cylinder<M: Material> {
+    radius: f64,
+    y0: f64,
+    y1: f64,
+    phi_max: f64,
+    capped: bool,
+    material: M
+}
+
+impl<M: Material> Cylinder<M> {
+    /// an open tube, see `with_caps`
+    pub fn new(radius: f64, y0: f64, y1: f64, material: M) -> Cylinder<M> {
+        assert!(radius > 0.0, "the cylinder radius must be positive");
+        assert!(y0 < y1, "the cylinder needs y0 < y1");
+        Cylinder { radius, y0, y1, phi_max: 2.0 * f64::consts::PI, capped: false, material }
+    }
+
+    /// keeps the part from +x towards +z up to the given angle in degrees
+    pub fn with_sweep(mut self, degrees: f64) -> Cylinder<M> {
+        self.phi_max = sweep_radians(degrees);
+        self
+    }
+
+    /// closes both ends with disks
+    pub fn with_caps(mut self) -> Cylinder<M> {
+        self.capped = true;
+        self
+    }
+
+    fn side_area(&self) -> f64 {
+        self.phi_max * self.radius * (self.y1 - self.y0)
+    }
+
+    fn cap_area(&self) -> f64 {
+        if self.capped { 0.5 * self.phi_max * self.radius.powi(2) } else { 0.0 }
+    }
+}
+
+impl<M: Material> Hittable for Cylinder<M> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, _rng: &mut Sampler) -> Option<HitRecord<'_>> {
+        let (o, d) = (r.origin(), r.direction());
+        let mut closest = t_max;
+        let mut rec = None;
+
+        let a = d.x() * d.x() + d.z() * d.z();
+        let b = 2.0 * (o.x() * d.x() + o.z() * d.z());
+        let c = o.x() * o.x() + o.z() * o.z() - self.radius * self.radius;
+        if let Some((t0, t1)) = solve_quadratic(a, b, c) {
+            for t in [t0, t1] {
+                if t < t_min || t > closest {
+                    continue
+                }
+                let p = r.at(t);
+                let phi = phi_of(p);
+                if p.y() < self.y0 || p.y() > self.y1 || phi > self.phi_max {
+                    continue
+                }
+                let outward_normal = Vec3::new(p.x(), 0.0, p.z()) / self.radius;
+                closest = t;
+                rec = Some(record(r, t, outward_normal, phi / self.phi_max, (p.y() - self.y0) / (self.y1 - self.y0), &self.material));
+                break
+            }
+        }
+
+        if self.capped {
+            for (height, outward) in [(self.y0, -1.0), (self.y1, 1.0)] {
+                if let Some((t, _, phi, distance)) = disk_hit(r, height, self.radius, 0.0, self.phi_max, t_min, closest) {
+                    closest = t;
+                    rec = Some(record(r, t, Vec3::new(0.0, outward, 0.0), phi / self.phi_max, distance / self.radius, &self.material));
+                }
+            }
+        }
+        rec
+    }
+
+    fn bounding_box(&self, _t0: f64, _t1: f64) -> Option<AABB> {
+        Some(AABB::new(Point3::new(-self.radius, self.y0, -self.radius), Point3::new(self.radius, self.y1, self.radius)))
+    }
+
+    fn pdf_value(&self, o: Point3, v: Vec3, rng: &mut Sampler) -> f64 {
+        area_pdf(self, o, v, self.side_area() + 2.0 * self.cap_area(), rng)
+    }
+
+    fn random(&self, o: Vec3, rng: &mut Sampler) -> Vec3 {
+        // pick the side or a cap in proportion to their areas
+        let choice = rng.gen::<f64>() * (self.side_area() + 2.0 * self.cap_area());
+        let p = if choice < self.side_area() {
+            let phi = rng.gen::<f64>() * self.phi_max;
+            Point3::new(self.radius * phi.cos(), rng.gen_range(self.y0..self.y1), self.radius * phi.sin())
+        } else if choice < self.side_area() + self.cap_area() {
+            random_on_disk(self.y0, self.radius, 0.0, self.phi_max, rng)
+        } else {
+            random_on_disk(self.y1, self.radius, 0.0, self.phi_max, rng)
+        };
+        p - o
+    }
+}
+
+/// cone standing on a base of the given radius at y = 0 with its apex at y = height
+#[derive(Clone)]
+pub struct Cone<M: Material> {
+    radius: f64,
+    height: f64,
+    phi_max: f64,
+    capped: bool,
+    material: M
+}
+
+impl<M: Material> Cone<M> {
+    /// without a base, see `with_caps`
+    pub fn new(radius: f64, height: f64, material: M) -> Cone<M> {
+        assert!(radius > 0.0 && height > 0.0, "the cone radius and height must be positive");
+        Cone { radius, height, phi_max: 2.0 * f64::consts::PI, capped: false, material }
+    }
+
+    /// keeps the part from +x towards +z up to the given angle in degrees
+    pub fn with_sweep(mut self, degrees: f64) -> Cone<M> {
+        self.phi_max = sweep_radians(degrees);
+        self
+    }
+
+    /// closes the base with a disk
+    pub fn with_caps(mut self) -> Cone<M> {
+        self.capped = true;
+        self
+    }
+}
+
+impl<M: Material> Hittable for Cone<M> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, _rng: &mut Sampler) -> Option<HitRecord<'_>> {
+        let (o, d) = (r.origin(), r.direction());
+        let mut closest = t_max;
+        let mut rec = None;
+
+        // x^2 + z^2 = k^2 (height - y)^2
+        let k2 = (self.radius / self.height).powi(2);
+        let h = self.height - o.y();
+        let a = d.x() * d.x() + d.z() * d.z() - k2 * d.y() * d.y();
+        let b = 2.0 * (o.x() * d.x() + o.z() * d.z() + k2 * h * d.y());
+        let c = o.x() * o.x() + o.z() * o.z() - k2 * h * h;
+        if let Some((t0, t1)) = solve_quadratic(a, b, c) {
+            for t in [t0, t1] {
+                if t < t_min || t > closest {
+                    continue
+                }
+                let p = r.at(t);
+                let phi = phi_of(p);
+                if p.y() < 0.0 || p.y() > self.height || phi > self.phi_max {
+                    continue
+                }
+                let outward_normal = Vec3::new(p.x(), k2 * (self.height - p.y()), p.z()).normalized();
+                closest = t;
+                rec = Some(record(r, t, outward_normal, phi / self.phi_max, p.y() / self.height, &self.material));
+                break
+            }
+        }
+
+        if self.capped {
+            if let Some((t, _, phi, distance)) = disk_hit(r, 0.0, self.radius, 0.0, self.phi_max, t_min, closest) {
+                rec = Some(record(r, t, Vec3::new(0.0, -1.0, 0.0), phi / self.phi_max, distance / self.radius, &self.material));
+            }
+        }
+        rec
+    }
+
+    fn bounding_box(&self, _t0: f64, _t1: f64) -> Option<AABB> {
+        Some(AABB::new(Point3::new(-self.radius, 0.0, -self.radius), Point3::new(self.radius, self.height, self.radius)))
+    }
+}
+
+/// bowl y = y1 (x^2 + z^2) / radius^2 opening towards +y, cut between the heights y0 and y1
+#[derive(Clone)]
+pub struct Paraboloid<M: Material> {
+    radius: f64,
+    y0: f64,
+    y1: f64,
+    phi_max: f64,
+    material: M
+}
+
+impl<M: Material> Paraboloid<M> {
+    /// `radius` is the radius of the rim at y1
+    pub fn new(radius: f64, y0: f64, y1: f64, material: M) -> Paraboloid<M> {
+        assert!(radius > 0.0, "the paraboloid radius must be positive");
+        assert!(0.0 <= y0 && y0 < y1, "the paraboloid needs 0 <= y0 < y1");
+        Paraboloid { radius, y0, y1, phi_max: 2.0 * f64::consts::PI, material }
+    }
+
+    /// keeps the part from +x towards +z up to the given angle in degrees
+    pub fn with_sweep(mut self, degrees: f64) -> Paraboloid<M> {
+        self.phi_max = sweep_radians(degrees);
+        self
+    }
+}
+
+impl<M: Material> Hittable for Paraboloid<M> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, _rng: &mut Sampler) -> Option<HitRecord<'_>> {
+        let (o, d) = (r.origin(), r.direction());
+        // x^2 + z^2 = k y
+        let k = self.radius * self.radius / self.y1;
+        let a = d.x() * d.x() + d.z() * d.z();
+        let b = 2.0 * (o.x() * d.x() + o.z() * d.z()) - k * d.y();
+        let c = o.x() * o.x() + o.z() * o.z() - k * o.y();
+        let (t0, t1) = solve_quadratic(a, b, c)?;
+        for t in [t0, t1] {
+            if t < t_min || t > t_max {
+                continue
+            }
+            let p = r.at(t);
+            let phi = phi_of(p);
+            if p.y() < self.y0 || p.y() > self.y1 || phi > self.phi_max {
+                continue
+            }
+            // points away from the inside of the bowl
+            let outward_normal = Vec3::new(2.0 * p.x(), -k, 2.0 * p.z()).normalized();
+            return Some(record(r, t, outward_normal, phi / self.phi_max, (p.y() - self.y0) / (self.y1 - self.y0), &self.material))
+        }
+        None
+    }
+
+    fn bounding_box(&self, _t0: f64, _t1: f64) -> Option<AABB> {
+        Some(AABB::new(Point3::new(-self.radius, self.y0, -self.radius), Point3::new(self.radius, self.y1, self.radius)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::{grey, hit};
+    use super::*;
+
+    #[test]
+    fn quadratic_keeps_the_small_root() {
+        // the textbook formula loses every digit of the small root here
+        let (t0, t1) = solve_quadratic(1.0, -1.0e8, 1.0).unwrap();
+        assert!((t0 - 1.0e-8).abs() < 1.0e-20);
+        assert!((t1 - 1.0e8).abs() < 1.0e-6);
+        assert!(solve_quadratic(1.0, 0.0, 1.0).is_none());
+        assert_eq!(solve_quadratic(0.0, 2.0, -4.0), Some((2.0, 2.0)));
+    }
+
+    #[test]
+    fn cylinder_side_and_caps() {
+        let tube = Cylinder::new(1.0, -1.0, 1.0, grey());
+        let rec = hit(&tube, Point3::new(-3.0, 0.5, 0.0), Vec3::new(1.0, 0.0, 0.0)).unwrap();
+        assert!((rec.t - 2.0).abs() < 1e-12);
+        assert!((rec.normal - Vec3::new(-1.0, 0.0, 0.0)).length() < 1e-12);
+        assert!((rec.v - 0.75).abs() < 1e-12);
+
+        // an open tube is seen through from above, the caps close it
+        let (above, down) = (Point3::new(0.2, 3.0, 0.1), Vec3::new(0.0, -1.0, 0.0));
+        assert!(hit(&tube, above, down).is_none());
+        let closed = tube.with_caps();
+        let rec = hit(&closed, above, down).unwrap();
+        assert!((rec.t - 2.0).abs() < 1e-12);
+        assert!(rec.front_face);
+    }
+
+    #[test]
+    fn sweep_cuts_the_surface() {
+        // the half from +x towards +z is kept, a ray from -z sees the inside of the far side
+        let half = Cylinder::new(1.0, -1.0, 1.0, grey()).with_sweep(180.0);
+        let rec = hit(&half, Point3::new(0.0, 0.0, -3.0), Vec3::new(0.0, 0.0, 1.0)).unwrap();
+        assert!((rec.t - 4.0).abs() < 1e-12);
+        assert!(!rec.front_face);
+    }
+
+    #[test]
+    fn disk_with_hole() {
+        let ring = Disk::new(0.0, 2.0, grey()).with_inner_radius(1.0);
+        let down = Vec3::new(0.0, -1.0, 0.0);
+        assert!(hit(&ring, Point3::new(0.5, 1.0, 0.0), down).is_none());
+        let rec = hit(&ring, Point3::new(0.0, 1.0, 1.5), down).unwrap();
+        assert!((rec.v - 0.5).abs() < 1e-12);
+        assert!(hit(&ring, Point3::new(0.0, 1.0, 2.5), down).is_none());
+    }
+
+    #[test]
+    fn cone_side_and_base() {
+        let cone = Cone::new(1.0, 2.0, grey()).with_caps();
+        // halfway up the radius is 0.5
+        let rec = hit(&cone, Point3::new(-3.0, 1.0, 0.0), Vec3::new(1.0, 0.0, 0.0)).unwrap();
+        assert!((rec.t - 2.5).abs() < 1e-12);
+        assert!((rec.normal - Vec3::new(-2.0, 1.0, 0.0).normalized()).length() < 1e-12);
+        let rec = hit(&cone, Point3::new(0.3, -1.0, 0.0), Vec3::new(0.0, 1.0, 0.0)).unwrap();
+        assert!((rec.t - 1.0).abs() < 1e-12);
+        assert!((rec.normal - Vec3::new(0.0, -1.0, 0.0)).length() < 1e-12);
+    }
+
+    #[test]
+    fn paraboloid_rim() {
+        let bowl = Paraboloid::new(2.0, 0.0, 1.0, grey());
+        // y = (x^2 + z^2) / 4, so x = 1 is at y = 0.25
+        let rec = hit(&bowl, Point3::new(1.0, 3.0, 0.0), Vec3::new(0.0, -1.0, 0.0)).unwrap();
+        assert!((rec.t - 2.75).abs() < 1e-12);
+        assert!(hit(&bowl, Point3::new(2.5, 3.0, 0.0), Vec3::new(0.0, -1.0, 0.0)).is_none());
+    }
+}
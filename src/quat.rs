@@ -0,0 +1,120 @@
+use super::vec::Vec3;
+use super::mat4::Mat4;
+
+/// unit quaternion for rotations that can be interpolated
+#[derive(Clone, Copy, PartialEq)]
+pub struct Quat {
+    w: f64,
+    x: f64,
+    y: f64,
+    z: f64
+}
+
+impl Quat {
+    pub fn identity() -> Quat {
+        Quat { w: 1.0, x: 0.0, y: 0.0, z: 0.0 }
+    }
+
+    /// the same rotation as `Mat4::rotation(axis, angle)`, the angle is in degrees
+    pub fn from_axis_angle(axis: Vec3, angle: f64) -> Quat {
+        let a = axis.normalized();
+        let (sin, cos) = (0.5 * angle.to_radians()).sin_cos();
+        Quat { w: cos, x: sin * a.x(), y: sin * a.y(), z: sin * a.z() }
+    }
+
+    pub fn dot(self, other: Quat) -> f64 {
+        self.w * other.w + self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    fn normalized(self) -> Quat {
+        let length = self.dot(self).sqrt();
+        Quat { w: self.w / length, x: self.x / length, y: self.y / length, z: self.z / length }
+    }
+
+    /// angle in radians of the rotation that takes self to other, along the shorter way
+    pub fn angle_to(self, other: Quat) -> f64 {
+        2.0 * self.dot(other).abs().min(1.0).acos()
+    }
+
+    /// spherical interpolation at constant angular speed, along the shorter way
+    pub fn slerp(self, other: Quat, t: f64) -> Quat {
+        let mut cos = self.dot(other);
+        // q and -q are the same rotation
+        let sign = if cos < 0.0 { -1.0 } else { 1.0 };
+        cos *= sign;
+
+        let (a, b) = if cos > 0.9995 {
+            // nearly the same rotation, a normalized lerp avoids dividing by a tiny sine
+            (1.0 - t, t)
+        } else {
+            let theta = cos.acos();
+            let sin = theta.sin();
+            (((1.0 - t) * theta).sin() / sin, (t * theta).sin() / sin)
+        };
+        let b = sign * b;
+        Quat {
+            w: a * self.w + b * other.w,
+            x: a * self.x + b * other.x,
+            y: a * self.y + b * other.y,
+            z: a * self.z + b * other.z
+        }.normalized()
+    }
+
+    pub fn to_mat4(self) -> Mat4 {
+        let Quat { w, x, y, z } = self;
+        Mat4::new([
+            [1.0 - 2.0 * (y * y + z * z), 2.0 * (x * y - w * z), 2.0 * (x * z + w * y), 0.0],
+            [2.0 * (x * y + w * z), 1.0 - 2.0 * (x * x + z * z), 2.0 * (y * z - w * x), 0.0],
+            [2.0 * (x * z - w * y), 2.0 * (y * z + w * x), 1.0 - 2.0 * (x * x + y * y), 0.0],
+            [0.0, 0.0, 0.0, 1.0]
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_same_rotation(a: Quat, b: Quat) {
+        let (a, b) = (a.to_mat4(), b.to_mat4());
+        for i in 0..3 {
+            for j in 0..3 {
+                assert!((a.get(i, j) - b.get(i, j)).abs() < 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn matches_the_rotation_matrix() {
+        let axis = Vec3::new(1.0, -2.0, 0.5);
+        let (q, m) = (Quat::from_axis_angle(axis, 73.0).to_mat4(), Mat4::rotation(axis, 73.0));
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((q.get(i, j) - m.get(i, j)).abs() < 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn slerp_at_constant_speed() {
+        let axis = Vec3::new(0.0, 0.0, 1.0);
+        let (a, b) = (Quat::identity(), Quat::from_axis_angle(axis, 120.0));
+        assert_same_rotation(a.slerp(b, 0.0), a);
+        assert_same_rotation(a.slerp(b, 1.0), b);
+        assert_same_rotation(a.slerp(b, 0.25), Quat::from_axis_angle(axis, 30.0));
+        for t in [0.1, 0.5, 0.8] {
+            assert!((a.angle_to(a.slerp(b, t)) - t * 120f64.to_radians()).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn slerp_takes_the_shorter_way() {
+        // 270 degrees one way is 90 the other
+        let axis = Vec3::new(1.0, 1.0, 0.0);
+        let (a, b) = (Quat::identity(), Quat::from_axis_angle(axis, 270.0));
+        assert_same_rotation(a.slerp(b, 0.5), Quat::from_axis_angle(axis, -45.0));
+        // nearly equal rotations are interpolated without dividing by zero
+        let c = Quat::from_axis_angle(axis, 0.01);
+        assert_same_rotation(a.slerp(c, 0.5), Quat::from_axis_angle(axis, 0.005));
+    }
+}
@@ -1,5 +1,6 @@
 use super::vec::{Vec3, Point3};
 
+/// half line origin + t * direction, at a moment of the camera shutter
 pub struct Ray {
     orig: Point3,
     dir: Vec3,
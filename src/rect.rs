@@ -4,6 +4,7 @@ use super::hit::{Hittable, HitRecord};
 use super::aabb::AABB;
 use super::vec::{Vec3, Point3};
 use super::ray::Ray;
+use super::sampler::Sampler;
 
 #[derive(Clone)]
 pub enum Plane {
@@ -12,6 +13,7 @@ pub enum Plane {
     YZ
 }
 
+/// axis aligned rectangle [a0, a1] x [b0, b1] at the height k on the axis normal to the plane
 #[derive(Clone)]
 pub struct AARect<M: Material> {
     plane: Plane,
@@ -46,7 +48,7 @@ impl<M: Material> AARect<M> {
 }
 
 impl<M: Material> Hittable for AARect<M> {
-    fn hit(&self, r: &crate::ray::Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+    fn hit(&self, r: &crate::ray::Ray, t_min: f64, t_max: f64, _rng: &mut Sampler) -> Option<HitRecord> {
         let (k_axis_index, a_axis_index, b_axis_index) = get_axis_index(&self.plane);
 
         let t = (self.k - r.origin()[k_axis_index]) / r.direction()[k_axis_index];
@@ -63,6 +65,8 @@ impl<M: Material> Hittable for AARect<M> {
                 let p = r.at(t);
                 let mut normal = Vec3::new(0.0, 0.0, 0.0);
                 normal[k_axis_index] = 1.0;
+                let mut tangent = Vec3::new(0.0, 0.0, 0.0);
+                tangent[a_axis_index] = 1.0;
 
                 let mut rec = HitRecord {
                     position: p,
@@ -70,6 +74,7 @@ impl<M: Material> Hittable for AARect<M> {
                     t,
                     u,
                     v,
+                    tangent,
                     front_face: false,
                     material: &self.material
                 };
@@ -88,8 +93,8 @@ impl<M: Material> Hittable for AARect<M> {
 
         Some(AABB::new(min, max))
     }
-    fn pdf_value(&self, o: Point3, v: Vec3) -> f64 {
-        if let Some(rec) = self.hit(&Ray::new(o, v, 0.0), 0.001, f64::INFINITY) {
+    fn pdf_value(&self, o: Point3, v: Vec3, rng: &mut Sampler) -> f64 {
+        if let Some(rec) = self.hit(&Ray::new(o, v, 0.0), 0.001, f64::INFINITY, rng) {
             // integration by substitution
             let area = (self.a1 - self.a0) * (self.b1 - self.b0);
             let distance_squared = rec.t.powi(2) * v.length().powi(2);
@@ -100,8 +105,7 @@ impl<M: Material> Hittable for AARect<M> {
         }
     }
 
-    fn random(&self, o: Vec3) -> Vec3 {
-        let mut rng = rand::thread_rng();
+    fn random(&self, o: Vec3, rng: &mut Sampler) -> Vec3 {
         let (k_axis, a_axis, b_axis) = get_axis_index(&self.plane);
         let mut random_point = Vec3::new(0.0, 0.0, 0.0);
         random_point[a_axis] = rng.gen_range(self.a0..self.a1);
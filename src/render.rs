@@ -0,0 +1,262 @@
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use rand::Rng;
+use rayon::prelude::*;
+use super::vec::Color;
+use super::ray::Ray;
+use super::hit::{Hittable, HittableList};
+use super::mat::ScatterRecord;
+use super::pdf::PDF;
+use super::scene::Scene;
+use super::framebuffer::Framebuffer;
+use super::checkpoint::Accumulator;
+use super::sampler;
+use super::sampler::Sampler;
+
+fn ray_color(ray: &Ray, background: Color, world: &dyn Hittable, lights: &HittableList, depth: u64, rng: &mut Sampler) -> Color {
+    if depth == 0 {
+        // if we've exceeded the ray bounce limit, no more light is gathered
+        return Color::new(0.0, 0.0, 0.0)
+    }
+
+    // 0.001 t_min fixs shadow acne
+    if let Some(rec) = world.hit(ray, 0.00001, f64::INFINITY, rng) {
+        let emitted: Color = rec.material.emitted(&rec);
+
+        if let Some(srec) = rec.material.scatter_mc_method(ray, &rec, rng) {
+            match srec {
+                ScatterRecord::Specular { specular_ray, attenuation } => {
+                    return attenuation * ray_color(&specular_ray, background, world, lights, depth - 1, rng)
+                }
+                ScatterRecord::Scatter { pdf, attenuation } => {
+                    let hittable_pdf = PDF::hittable_pdf(rec.position, lights);
+                    let mixture_pdf = PDF::mixture_pdf(&hittable_pdf, &pdf);
+                    // without lights to aim at, only the material distribution is sampled
+                    let sampling_pdf = if lights.is_empty() { &pdf } else { &mixture_pdf };
+                    let scattered = Ray::new(rec.position, sampling_pdf.generate(rng), ray.time());
+                    let pdf_value = sampling_pdf.value(scattered.direction(), rng);
+                    return emitted + attenuation *  rec.material.scattering_pdf(ray, &rec, &scattered) * ray_color(&scattered, background, world, lights, depth - 1, rng) / pdf_value
+                }
+            }
+        } else {
+            emitted
+        }
+    } else {
+        background
+    }
+}
+
+/// path traces a scene into a framebuffer
+///
+/// the image is split into square tiles rendered in parallel, every tile draws its random numbers
+/// from a generator seeded by the render seed, the pass and the tile index, so a seed always gives the same image.
+/// samples are taken in passes added to an [`Accumulator`], which can be saved between passes and resumed
+pub struct Renderer {
+    width: u64,
+    height: u64,
+    samples_per_pixel: u64,
+    pass_samples: u64,
+    max_depth: u64,
+    tile_size: u64,
+    seed: u64
+}
+
+impl Renderer {
+    pub fn new(width: u64, height: u64, samples_per_pixel: u64, max_depth: u64) -> Renderer {
+        assert!(width > 1 && height > 1, "the image must be at least 2x2 pixels");
+        assert!(samples_per_pixel > 0, "at least one sample per pixel is needed");
+        Renderer {
+            width,
+            height,
+            samples_per_pixel,
+            pass_samples: samples_per_pixel,
+            max_depth,
+            tile_size: 16,
+            seed: 0
+        }
+    }
+
+    /// side of the square tiles in pixels, 16 by default
+    pub fn with_tile_size(mut self, tile_size: u64) -> Renderer {
+        assert!(tile_size > 0, "tiles must be at least one pixel wide");
+        self.tile_size = tile_size;
+        self
+    }
+
+    /// samples per pixel of each pass, by default everything is rendered in a single pass
+    pub fn with_pass_samples(mut self, pass_samples: u64) -> Renderer {
+        assert!(pass_samples > 0, "a pass needs at least one sample per pixel");
+        self.pass_samples = pass_samples;
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Renderer {
+        self.seed = seed;
+        self
+    }
+
+    /// the aspect ratio the scene camera should be built with
+    pub fn aspect_ratio(&self) -> f64 {
+        self.width as f64 / self.height as f64
+    }
+
+    pub fn render(&self, scene: &Scene) -> Framebuffer {
+        self.render_with_progress(scene, |_, _| ())
+    }
+
+    /// `progress` is called after every tile with the number of finished tiles and the total
+    pub fn render_with_progress(&self, scene: &Scene, progress: impl FnMut(u64, u64) + Send) -> Framebuffer {
+        let mut accumulator = Accumulator::new(self.width, self.height, self.seed);
+        self.resume(scene, &mut accumulator, progress, |_| ());
+        accumulator.image()
+    }
+
+    // top left corners of the tiles, in framebuffer rows from top to bottom
+    fn tiles(&self) -> Vec<(u64, u64)> {
+        let mut tiles = Vec::new();
+        for y in (0..self.height).step_by(self.tile_size as usize) {
+            for x in (0..self.width).step_by(self.tile_size as usize) {
+                tiles.push((x, y));
+            }
+        }
+        tiles
+    }
+
+    // radiance summed over `samples` samples of every pixel in the tile
+    fn render_tile(&self, scene: &Scene, (x0, y0): (u64, u64), samples: u64, seed: u64) -> Vec<Color> {
+        let mut rng = Sampler::new(seed);
+
+        let x1 = (x0 + self.tile_size).min(self.width);
+        let y1 = (y0 + self.tile_size).min(self.height);
+        let mut pixels = Vec::with_capacity(((x1 - x0) * (y1 - y0)) as usize);
+        for y in y0..y1 {
+            // the camera counts rows from the bottom
+            let j = self.height - 1 - y;
+            for i in x0..x1 {
+                let mut pixel_color = Color::new(0.0, 0.0, 0.0);
+                for _sample in 0..samples {
+                    let u = ((i as f64) + rng.gen::<f64>()) / ((self.width - 1) as f64);
+                    let v = ((j as f64) + rng.gen::<f64>()) / ((self.height - 1) as f64);
+
+                    let r = scene.camera.get_ray(u, v, &mut rng);
+                    pixel_color += ray_color(&r, scene.background, scene.world.as_ref(), &scene.lights, self.max_depth, &mut rng);
+                }
+                pixels.push(pixel_color);
+            }
+        }
+        pixels
+    }
+
+    /// adds passes to `accumulator` until it holds the requested samples per pixel.
+    /// `progress` is called after every tile, `checkpoint` after every pass
+    pub fn resume(&self,
+                  scene: &Scene,
+                  accumulator: &mut Accumulator,
+                  progress: impl FnMut(u64, u64) + Send,
+                  mut checkpoint: impl FnMut(&Accumulator)) {
+        assert!(accumulator.width() == self.width && accumulator.height() == self.height,
+                "the accumulator does not match the image size");
+
+        let tiles = self.tiles();
+        let remaining = self.samples_per_pixel.saturating_sub(accumulator.samples());
+        let passes = remaining.div_ceil(self.pass_samples);
+        let total = tiles.len() as u64 * passes;
+        let finished = AtomicU64::new(0);
+        let progress = Mutex::new(progress);
+
+        while accumulator.samples() < self.samples_per_pixel {
+            let samples = self.pass_samples.min(self.samples_per_pixel - accumulator.samples());
+            let pass_seed = sampler::mix(accumulator.seed(), accumulator.passes());
+
+            let rendered: Vec<Vec<Color>> = tiles.par_iter().enumerate().map(|(index, &tile)| {
+                let pixels = self.render_tile(scene, tile, samples, sampler::mix(pass_seed, index as u64));
+                let done = finished.fetch_add(1, Ordering::Relaxed) + 1;
+                (progress.lock().unwrap())(done, total);
+                pixels
+            }).collect();
+
+            for (&(x0, y0), pixels) in tiles.iter().zip(rendered) {
+                let width = (x0 + self.tile_size).min(self.width) - x0;
+                for (k, color) in pixels.into_iter().enumerate() {
+                    let k = k as u64;
+                    accumulator.add(x0 + k % width, y0 + k / width, color);
+                }
+            }
+            accumulator.finish_pass(samples);
+            checkpoint(accumulator);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+    use crate::builtin::Builtin;
+    use crate::bvh::SplitMethod;
+    use crate::camera::Camera;
+    use crate::vec::{Point3, Vec3};
+    use super::*;
+
+    // the smoke box draws from the sampler in the camera, the materials, the lights and the media
+    fn render(seed: u64) -> Vec<u8> {
+        let renderer = Renderer::new(24, 24, 4, 8).with_tile_size(8).with_seed(seed);
+        let scene = Builtin::CornellSmoke.build(renderer.aspect_ratio(), SplitMethod::Sah, &mut StdRng::seed_from_u64(0));
+        let image = renderer.render(&scene);
+        image.pixels().iter().flat_map(|c| (0..3).flat_map(|a| c[a].to_bits().to_le_bytes())).collect()
+    }
+
+    #[test]
+    fn same_seed_renders_identically() {
+        assert!(render(7) == render(7));
+    }
+
+    #[test]
+    fn seed_changes_the_noise() {
+        assert!(render(7) != render(8));
+    }
+
+    #[test]
+    fn thread_count_does_not_change_the_image() {
+        let in_pool = |threads| rayon::ThreadPoolBuilder::new().num_threads(threads).build().unwrap().install(|| render(7));
+        assert!(in_pool(1) == in_pool(4));
+    }
+
+    #[test]
+    fn tiles_cover_the_image_once() {
+        for tile_size in [1, 5, 7, 16, 30] {
+            let renderer = Renderer::new(23, 17, 1, 1).with_tile_size(tile_size);
+            let mut covered = vec![0; 23 * 17];
+            for (x0, y0) in renderer.tiles() {
+                for y in y0..(y0 + tile_size).min(17) {
+                    for x in x0..(x0 + tile_size).min(23) {
+                        covered[(y * 23 + x) as usize] += 1;
+                    }
+                }
+            }
+            assert!(covered.iter().all(|&c| c == 1), "tile size {}", tile_size);
+        }
+    }
+
+    #[test]
+    fn partial_tiles_fill_their_pixels() {
+        // every ray escapes, so every pixel is the background
+        let background = Color::new(0.25, 0.5, 0.75);
+        let renderer = Renderer::new(23, 17, 4, 4).with_tile_size(7);
+        let scene = Scene {
+            world: Box::new(HittableList::default()),
+            lights: HittableList::default(),
+            background,
+            camera: Camera::new(Point3::new(0.0, 0.0, 1.0), Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0),
+                                40.0, renderer.aspect_ratio(), 0.0, 1.0, 0.0, 1.0),
+            bvh_stats: Vec::new()
+        };
+        let image = renderer.render(&scene);
+        for y in 0..17 {
+            for x in 0..23 {
+                let c = image.get(x, y);
+                assert_eq!((c.x(), c.y(), c.z()), (0.25, 0.5, 0.75), "pixel {} {}", x, y);
+            }
+        }
+    }
+}
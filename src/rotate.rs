@@ -3,6 +3,7 @@ use std::f64;
 
 use super::vec::Vec3;
 use super::ray::Ray;
+use super::sampler::Sampler;
 use super::hit::{Hittable, HitRecord};
 use super::aabb::AABB;
 
@@ -20,61 +21,31 @@ fn get_axis_index(axis: &Axis) -> (usize, usize, usize) {
     }
 }
 
+/// rotates an object around one of the axes, the angle is in degrees
 pub struct Rotate<H: Hittable> {
     axis: Axis,
     sin_theta: f64,
     cos_theta: f64,
-    hittable: H,
-    aabb: Option<AABB>
+    hittable: H
 }
 
 impl<H: Hittable> Rotate<H> {
     pub fn new(axis: Axis, hittable: H, angle: f64) -> Rotate<H> {
-        let (r_axis, a_axis, b_axis) = get_axis_index(&axis);
         let radiants = (f64::consts::PI / 180.0) * angle;
         let sin_theta = f64::sin(radiants);
         let cos_theta = f64::cos(radiants);
 
-        let aabb = hittable.bounding_box(0.0, 1.0).map(
-            |mut aabb| {
-                let mut min = Vec3::new(f64::MIN, f64::MIN, f64::MIN);
-                let mut max = Vec3::new(f64::MAX, f64::MAX, f64::MAX);
-                for i in 0..2 {
-                    for j in 0..2 {
-                        for k in 0..2 {
-                            let r = k as f64 * aabb.max[r_axis] + (1 - k) as f64 * aabb.min[r_axis];
-                            let a = i as f64 * aabb.max[a_axis] + (1 - i) as f64 * aabb.min[a_axis];
-                            let b = j as f64 * aabb.max[b_axis] + (1 - j) as f64 * aabb.min[b_axis];
-                            let new_a = cos_theta * a + sin_theta * b;
-                            let new_b = -sin_theta * a + cos_theta * b;
-    
-                            if new_a < min[a_axis] { min[a_axis] = new_a }
-                            if new_b < min[b_axis] { min[b_axis] = new_b }
-                            if r < min[r_axis] { min[r_axis] = r }
-    
-                            if new_a > max[a_axis] { max[a_axis] = new_a }
-                            if new_b > max[b_axis] { max[b_axis] = new_b }
-                            if r > max[r_axis] { max[r_axis] = r }
-                        }
-                    }
-                }
-                aabb.min = min;
-                aabb.max = max;
-                aabb
-            }
-        );
         Rotate {
             axis,
             sin_theta,
             cos_theta,
-            hittable,
-            aabb
+            hittable
         }
     }
 }
 
 impl<H: Hittable> Hittable for Rotate<H> {
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rng: &mut Sampler) -> Option<HitRecord> {
         let (_, a_axis, b_axis) = get_axis_index(&self.axis);
         let mut origin = r.origin();
         let mut direction = r.direction();
@@ -87,10 +58,11 @@ impl<H: Hittable> Hittable for Rotate<H> {
 
         let rotated_ray = Ray::new(origin, direction, r.time());
 
-        self.hittable.hit(&rotated_ray, t_min, t_max).map(
+        self.hittable.hit(&rotated_ray, t_min, t_max, rng).map(
             |mut hit| {
                 let mut position = hit.position;
                 let mut normal = hit.normal;
+                let mut tangent = hit.tangent;
 
                 position[a_axis] = &self.cos_theta * hit.position[a_axis] + &self.sin_theta * hit.position[b_axis];
                 position[b_axis] = -&self.sin_theta * hit.position[a_axis] + &self.cos_theta * hit.position[b_axis];
@@ -98,7 +70,11 @@ impl<H: Hittable> Hittable for Rotate<H> {
                 normal[a_axis] = &self.cos_theta * hit.normal[a_axis] + &self.sin_theta * hit.normal[b_axis];
                 normal[b_axis] = -&self.sin_theta * hit.normal[a_axis] + &self.cos_theta * hit.normal[b_axis];
 
+                tangent[a_axis] = self.cos_theta * hit.tangent[a_axis] + self.sin_theta * hit.tangent[b_axis];
+                tangent[b_axis] = -self.sin_theta * hit.tangent[a_axis] + self.cos_theta * hit.tangent[b_axis];
+
                 hit.position = position;
+                hit.tangent = tangent;
                 hit.set_face_normal(&rotated_ray, normal);
                 hit
             }
@@ -106,6 +82,35 @@ impl<H: Hittable> Hittable for Rotate<H> {
     }
 
     fn bounding_box(&self, t0: f64, t1: f64) -> Option<AABB> {
-        self.aabb.clone()
+        let (r_axis, a_axis, b_axis) = get_axis_index(&self.axis);
+        self.hittable.bounding_box(t0, t1).map(
+            |mut aabb| {
+                // the box around the eight rotated corners
+                let mut min = Vec3::new(f64::INFINITY, f64::INFINITY, f64::INFINITY);
+                let mut max = Vec3::new(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY);
+                for i in 0..2 {
+                    for j in 0..2 {
+                        for k in 0..2 {
+                            let r = k as f64 * aabb.max[r_axis] + (1 - k) as f64 * aabb.min[r_axis];
+                            let a = i as f64 * aabb.max[a_axis] + (1 - i) as f64 * aabb.min[a_axis];
+                            let b = j as f64 * aabb.max[b_axis] + (1 - j) as f64 * aabb.min[b_axis];
+                            let new_a = self.cos_theta * a + self.sin_theta * b;
+                            let new_b = -self.sin_theta * a + self.cos_theta * b;
+
+                            if new_a < min[a_axis] { min[a_axis] = new_a }
+                            if new_b < min[b_axis] { min[b_axis] = new_b }
+                            if r < min[r_axis] { min[r_axis] = r }
+
+                            if new_a > max[a_axis] { max[a_axis] = new_a }
+                            if new_b > max[b_axis] { max[b_axis] = new_b }
+                            if r > max[r_axis] { max[r_axis] = r }
+                        }
+                    }
+                }
+                aabb.min = min;
+                aabb.max = max;
+                aabb
+            }
+        )
     }
 }
\ No newline at end of file
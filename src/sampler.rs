@@ -0,0 +1,45 @@
+use rand::{Error, RngCore, SeedableRng};
+use rand::rngs::StdRng;
+
+/// the random numbers of a render, passed to everything that draws them: the camera, the
+/// materials, the pdfs, the lights and the media
+///
+/// the renderer gives every tile of every pass its own sampler seeded from the render seed,
+/// so the image only depends on the seed and not on which thread rendered which tile
+pub struct Sampler {
+    rng: StdRng
+}
+
+impl Sampler {
+    pub fn new(seed: u64) -> Sampler {
+        Sampler {
+            rng: StdRng::seed_from_u64(seed)
+        }
+    }
+}
+
+/// combines a seed with a value into a new well spread seed (the splitmix64 finalizer)
+pub fn mix(seed: u64, value: u64) -> u64 {
+    let mut z = seed ^ value.wrapping_add(0x9e3779b97f4a7c15).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+impl RngCore for Sampler {
+    fn next_u32(&mut self) -> u32 {
+        self.rng.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.rng.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.rng.fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.rng.try_fill_bytes(dest)
+    }
+}
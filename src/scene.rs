@@ -0,0 +1,1155 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use rand::rngs::StdRng;
+use super::json::{self, Kind, Value};
+use super::vec::{Vec3, Point3, Color};
+use super::hit::{Hittable, HittableList, FlipNormal};
+use super::sphere::{Sphere, MovingSphere};
+use super::quadric::{Disk, Cylinder, Cone, Paraboloid};
+use super::torus::Torus;
+use super::rect::{Plane, AARect};
+use super::quad::{self, Quad};
+use super::cube::Cube;
+use super::csg::{Csg, Operation};
+use super::sdf::{self, Distance, SdfShape};
+use super::heightfield::{self, Heightfield};
+use super::curve::{self, Basis, CurveKind};
+use super::subdivision::{ControlMesh, Scheme};
+use super::displace::{Tessellate, Tessellation};
+use super::particles::ParticleSet;
+use super::aabb::AABB;
+use super::triangle::{Triangle, TriangleMesh};
+use super::translate::Translate;
+use super::rotate::{Axis, Rotate};
+use super::medium::ConstantMedium;
+use super::bvh::{BVH, BVHStats, SplitMethod};
+use super::camera::Camera;
+use super::mat::{Material, Lambertian, Metal, Dielectric, DiffuseLight, Hair};
+use super::texture::{Texture, ConstantTexture, CheckTexture, NoiseTexture, ImageTexture};
+use super::obj;
+use super::mat4::Mat4;
+use super::transform::Transform;
+use super::instance::Instance;
+use super::animated::{Animated, Keyframe};
+
+// positions, faces, normals and uvs
+type MeshData = (Vec<Point3>, Vec<[usize; 3]>, Option<Vec<Vec3>>, Option<Vec<(f64, f64)>>);
+
+#[derive(Debug)]
+pub struct SceneError {
+    pub path: PathBuf,
+    // 0 when the error is not tied to a position in the file
+    pub line: usize,
+    pub column: usize,
+    pub message: String
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.line == 0 {
+            write!(f, "{}: {}", self.path.display(), self.message)
+        } else {
+            write!(f, "{}:{}:{}: {}", self.path.display(), self.line, self.column, self.message)
+        }
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+/// everything needed to render a scene
+pub struct Scene {
+    pub world: Box<dyn Hittable>,
+    /// emitters sampled directly by the renderer, they must also be part of the world
+    pub lights: HittableList,
+    /// radiance of the rays that escape the world
+    pub background: Color,
+    pub camera: Camera,
+    /// the shape of every bvh built for the scene, in the order they were built
+    pub bvh_stats: Vec<BVHStats>
+}
+
+struct Builder<'a> {
+    path: &'a Path,
+    dir: &'a Path,
+    textures: HashMap<String, Arc<dyn Texture>>,
+    materials: HashMap<String, Arc<dyn Material>>,
+    geometry: HashMap<String, Arc<dyn Hittable>>,
+    // draws the lattices of the noise textures
+    rng: RefCell<&'a mut StdRng>,
+    // how every bvh of the scene is split, and what the trees look like
+    split: SplitMethod,
+    bvh_stats: RefCell<Vec<BVHStats>>
+}
+
+impl<'a> Builder<'a> {
+    fn error(&self, value: &Value, message: impl Into<String>) -> SceneError {
+        SceneError {
+            path: self.path.to_path_buf(),
+            line: value.line,
+            column: value.column,
+            message: message.into()
+        }
+    }
+
+    fn fields<'v>(&self, value: &'v Value) -> Result<&'v [(String, Value)], SceneError> {
+        match &value.kind {
+            Kind::Object(fields) => Ok(fields),
+            _ => Err(self.error(value, format!("expected an object, found {}", value.type_name())))
+        }
+    }
+
+    // reject keys that would otherwise be silently ignored
+    fn check_keys(&self, value: &Value, allowed: &[&str]) -> Result<(), SceneError> {
+        for (key, field) in self.fields(value)? {
+            if !allowed.contains(&key.as_str()) {
+                return Err(self.error(field, format!("unknown key '{}'", key)))
+            }
+        }
+        Ok(())
+    }
+
+    fn required<'v>(&self, value: &'v Value, key: &str) -> Result<&'v Value, SceneError> {
+        value.get(key).ok_or_else(|| self.error(value, format!("missing key '{}'", key)))
+    }
+
+    fn number(&self, value: &Value) -> Result<f64, SceneError> {
+        match value.kind {
+            Kind::Number(n) => Ok(n),
+            _ => Err(self.error(value, format!("expected a number, found {}", value.type_name())))
+        }
+    }
+
+    fn number_field(&self, value: &Value, key: &str) -> Result<f64, SceneError> {
+        self.number(self.required(value, key)?)
+    }
+
+    fn number_or(&self, value: &Value, key: &str, default: f64) -> Result<f64, SceneError> {
+        value.get(key).map_or(Ok(default), |v| self.number(v))
+    }
+
+    fn bool_or(&self, value: &Value, key: &str, default: bool) -> Result<bool, SceneError> {
+        match value.get(key) {
+            Some(Value { kind: Kind::Bool(b), .. }) => Ok(*b),
+            Some(other) => Err(self.error(other, format!("expected a boolean, found {}", other.type_name()))),
+            None => Ok(default)
+        }
+    }
+
+    // a number that must be above zero
+    fn positive(&self, value: &Value, key: &str) -> Result<f64, SceneError> {
+        let field = self.required(value, key)?;
+        let n = self.number(field)?;
+        if n <= 0.0 {
+            return Err(self.error(field, format!("{} must be positive", key)))
+        }
+        Ok(n)
+    }
+
+    // the sweep in degrees, a full turn when it is missing
+    fn sweep(&self, value: &Value) -> Result<f64, SceneError> {
+        match value.get("sweep") {
+            Some(sweep) => {
+                let degrees = self.number(sweep)?;
+                if degrees <= 0.0 || degrees > 360.0 {
+                    return Err(self.error(sweep, "sweep must be in (0, 360] degrees"))
+                }
+                Ok(degrees)
+            },
+            None => Ok(360.0)
+        }
+    }
+
+    fn index(&self, value: &Value, len: usize) -> Result<usize, SceneError> {
+        let n = self.number(value)?;
+        if n < 0.0 || n.fract() != 0.0 || n as usize >= len {
+            return Err(self.error(value, format!("index {} out of range", n)))
+        }
+        Ok(n as usize)
+    }
+
+    fn string<'v>(&self, value: &'v Value) -> Result<&'v str, SceneError> {
+        match &value.kind {
+            Kind::String(s) => Ok(s),
+            _ => Err(self.error(value, format!("expected a string, found {}", value.type_name())))
+        }
+    }
+
+    fn array<'v>(&self, value: &'v Value) -> Result<&'v [Value], SceneError> {
+        match &value.kind {
+            Kind::Array(items) => Ok(items),
+            _ => Err(self.error(value, format!("expected an array, found {}", value.type_name())))
+        }
+    }
+
+    fn numbers<const N: usize>(&self, value: &Value) -> Result<[f64; N], SceneError> {
+        let items = self.array(value)?;
+        if items.len() != N {
+            return Err(self.error(value, format!("expected {} numbers, found {}", N, items.len())))
+        }
+        let mut numbers = [0.0; N];
+        for (n, item) in numbers.iter_mut().zip(items) {
+            *n = self.number(item)?;
+        }
+        Ok(numbers)
+    }
+
+    fn vec3(&self, value: &Value) -> Result<Vec3, SceneError> {
+        let [x, y, z] = self.numbers::<3>(value)?;
+        Ok(Vec3::new(x, y, z))
+    }
+
+    fn vec3_field(&self, value: &Value, key: &str) -> Result<Vec3, SceneError> {
+        self.vec3(self.required(value, key)?)
+    }
+
+    fn vec3_list(&self, value: &Value) -> Result<Vec<Vec3>, SceneError> {
+        self.array(value)?.iter().map(|v| self.vec3(v)).collect()
+    }
+
+    fn uv_list(&self, value: &Value) -> Result<Vec<(f64, f64)>, SceneError> {
+        self.array(value)?.iter().map(|v| self.numbers::<2>(v).map(|[u, v]| (u, v))).collect()
+    }
+
+    fn type_of<'v>(&self, value: &'v Value) -> Result<&'v str, SceneError> {
+        self.fields(value)?;
+        self.string(self.required(value, "type")?)
+    }
+
+    // a texture is a name, a constant color or an inline definition
+    fn texture(&self, value: &Value) -> Result<Arc<dyn Texture>, SceneError> {
+        match &value.kind {
+            Kind::String(name) => self.textures.get(name).cloned()
+                .ok_or_else(|| self.error(value, format!("unknown texture '{}'", name))),
+            Kind::Array(_) => Ok(Arc::new(ConstantTexture::new(self.vec3(value)?))),
+            _ => self.build_texture(value)
+        }
+    }
+
+    fn build_texture(&self, value: &Value) -> Result<Arc<dyn Texture>, SceneError> {
+        let texture: Arc<dyn Texture> = match self.type_of(value)? {
+            "constant" => {
+                self.check_keys(value, &["type", "color"])?;
+                Arc::new(ConstantTexture::new(self.vec3_field(value, "color")?))
+            },
+            "checker" => {
+                self.check_keys(value, &["type", "odd", "even"])?;
+                let odd = self.texture(self.required(value, "odd")?)?;
+                let even = self.texture(self.required(value, "even")?)?;
+                Arc::new(CheckTexture::new(odd, even))
+            },
+            "noise" => {
+                self.check_keys(value, &["type", "scale"])?;
+                Arc::new(NoiseTexture::new(self.number_field(value, "scale")?, *self.rng.borrow_mut()))
+            },
+            "image" => {
+                self.check_keys(value, &["type", "path"])?;
+                let file = self.required(value, "path")?;
+                let path = self.dir.join(self.string(file)?);
+                let image = image::open(&path)
+                    .map_err(|e| self.error(file, format!("{}: {}", path.display(), e)))?
+                    .to_rgb8();
+                let (width, height) = image.dimensions();
+                Arc::new(ImageTexture::new(image.into_raw(), width, height))
+            },
+            other => return Err(self.error(value, format!("unknown texture type '{}'", other)))
+        };
+        Ok(texture)
+    }
+
+    // a material is a name or an inline definition
+    fn material(&self, value: &Value) -> Result<Arc<dyn Material>, SceneError> {
+        match &value.kind {
+            Kind::String(name) => self.materials.get(name).cloned()
+                .ok_or_else(|| self.error(value, format!("unknown material '{}'", name))),
+            _ => self.build_material(value)
+        }
+    }
+
+    fn build_material(&self, value: &Value) -> Result<Arc<dyn Material>, SceneError> {
+        let material: Arc<dyn Material> = match self.type_of(value)? {
+            "lambertian" => {
+                self.check_keys(value, &["type", "albedo"])?;
+                Arc::new(Lambertian::new(self.texture(self.required(value, "albedo")?)?))
+            },
+            "metal" => {
+                self.check_keys(value, &["type", "albedo", "fuzz"])?;
+                Arc::new(Metal::new(self.vec3_field(value, "albedo")?, self.number_or(value, "fuzz", 0.0)?))
+            },
+            "dielectric" => {
+                self.check_keys(value, &["type", "ir"])?;
+                Arc::new(Dielectric::new(self.number_field(value, "ir")?))
+            },
+            "diffuse_light" => {
+                self.check_keys(value, &["type", "emit"])?;
+                Arc::new(DiffuseLight::new(self.texture(self.required(value, "emit")?)?))
+            },
+            "hair" => {
+                self.check_keys(value, &["type", "color", "roughness", "shift"])?;
+                let roughness = self.number_or(value, "roughness", 10.0)?;
+                if roughness < 0.0 {
+                    return Err(self.error(self.required(value, "roughness")?, "roughness cannot be negative"))
+                }
+                Arc::new(Hair::new(self.texture(self.required(value, "color")?)?, roughness, self.number_or(value, "shift", 2.0)?))
+            },
+            other => return Err(self.error(value, format!("unknown material type '{}'", other)))
+        };
+        Ok(material)
+    }
+
+    fn material_field(&self, value: &Value) -> Result<Arc<dyn Material>, SceneError> {
+        self.material(self.required(value, "material")?)
+    }
+
+    // shared geometry is a name or an inline object
+    fn geometry(&self, value: &Value) -> Result<Arc<dyn Hittable>, SceneError> {
+        match &value.kind {
+            Kind::String(name) => self.geometry.get(name).cloned()
+                .ok_or_else(|| self.error(value, format!("unknown geometry '{}'", name))),
+            _ => Ok(Arc::from(self.object(value)?))
+        }
+    }
+
+    // a list of steps applied in order, or a 4x4 matrix given as 16 numbers row by row
+    fn transform(&self, value: &Value) -> Result<Mat4, SceneError> {
+        let steps = self.array(value)?;
+        if steps.len() == 16 && steps.iter().all(|v| matches!(v.kind, Kind::Number(_))) {
+            let m = self.numbers::<16>(value)?;
+            return Ok(Mat4::new([[m[0], m[1], m[2], m[3]], [m[4], m[5], m[6], m[7]], [m[8], m[9], m[10], m[11]], [m[12], m[13], m[14], m[15]]]))
+        }
+
+        let mut transform = Mat4::identity();
+        for step in steps {
+            let fields = self.fields(step)?;
+            let step_transform = match fields.first().map(|(key, _)| key.as_str()) {
+                Some("translate") => {
+                    self.check_keys(step, &["translate"])?;
+                    Mat4::translation(self.vec3_field(step, "translate")?)
+                },
+                Some("scale") => {
+                    self.check_keys(step, &["scale"])?;
+                    Mat4::scaling(self.scale_factors(self.required(step, "scale")?)?)
+                },
+                Some("rotate") => {
+                    self.check_keys(step, &["rotate", "angle"])?;
+                    Mat4::rotation(self.vec3_field(step, "rotate")?, self.number_field(step, "angle")?)
+                },
+                Some("look_at") => {
+                    self.check_keys(step, &["look_at", "from", "up"])?;
+                    let target = self.vec3_field(step, "look_at")?;
+                    let from = self.vec3_field(step, "from")?;
+                    let up = match step.get("up") {
+                        Some(up) => self.vec3(up)?,
+                        None => Vec3::new(0.0, 1.0, 0.0)
+                    };
+                    if up.cross(target - from).length() < 1.0e-12 {
+                        return Err(self.error(step, "look_at needs distinct from and target points, not in line with up"))
+                    }
+                    Mat4::look_at(from, target, up)
+                },
+                _ => return Err(self.error(step, "a transform step starts with translate, scale, rotate or look_at"))
+            };
+            transform = step_transform * transform;
+        }
+        Ok(transform)
+    }
+
+    // a single number scales uniformly
+    fn scale_factors(&self, value: &Value) -> Result<Vec3, SceneError> {
+        match value.kind {
+            Kind::Number(s) => Ok(Vec3::new(s, s, s)),
+            _ => self.vec3(value)
+        }
+    }
+
+    // the widths at the root and the tip of a strand, or one width for both
+    fn strand_widths(&self, value: &Value) -> Result<(f64, f64), SceneError> {
+        let (root, tip) = match value.kind {
+            Kind::Number(w) => (w, w),
+            _ => self.numbers::<2>(value).map(|[root, tip]| (root, tip))?
+        };
+        if root < 0.0 || tip < 0.0 || root + tip <= 0.0 {
+            return Err(self.error(value, "widths cannot be negative and not both 0"))
+        }
+        Ok((root, tip))
+    }
+
+    // the positions, faces and optional normals and uvs of a triangle mesh
+    fn mesh_data(&self, value: &Value) -> Result<MeshData, SceneError> {
+        let positions = self.vec3_list(self.required(value, "positions")?)?;
+        let indices_value = self.required(value, "indices")?;
+        let indices = self.array(indices_value)?.iter().map(|face| {
+            let items = self.array(face)?;
+            if items.len() != 3 {
+                return Err(self.error(face, "a face needs 3 indices"))
+            }
+            Ok([self.index(&items[0], positions.len())?,
+                self.index(&items[1], positions.len())?,
+                self.index(&items[2], positions.len())?])
+        }).collect::<Result<Vec<[usize; 3]>, SceneError>>()?;
+        if indices.is_empty() {
+            return Err(self.error(indices_value, "a mesh needs at least one face"))
+        }
+        let normals = match value.get("normals") {
+            Some(normals_value) => {
+                let normals = self.vec3_list(normals_value)?;
+                if normals.len() != positions.len() {
+                    return Err(self.error(normals_value, "a mesh needs one normal per position"))
+                }
+                Some(normals)
+            },
+            None => None
+        };
+        let uvs = match value.get("uvs") {
+            Some(uvs_value) => {
+                let uvs = self.uv_list(uvs_value)?;
+                if uvs.len() != positions.len() {
+                    return Err(self.error(uvs_value, "a mesh needs one uv per position"))
+                }
+                Some(uvs)
+            },
+            None => None
+        };
+        Ok((positions, indices, normals, uvs))
+    }
+
+    // a control mesh with its creases, subdivided by its scheme and levels
+    fn subdivided(&self, value: &Value) -> Result<ControlMesh, SceneError> {
+        let scheme_value = self.required(value, "scheme")?;
+        let scheme = match self.string(scheme_value)? {
+            "loop" => Scheme::Loop,
+            "catmull_clark" => Scheme::CatmullClark,
+            other => return Err(self.error(scheme_value, format!("unknown subdivision scheme '{}', expected loop or catmull_clark", other)))
+        };
+        let levels = self.number_or(value, "levels", 2.0)?;
+        if !(0.0..=8.0).contains(&levels) || levels.fract() != 0.0 {
+            return Err(self.error(self.required(value, "levels")?, "levels must be a whole number from 0 to 8"))
+        }
+
+        let positions = self.vec3_list(self.required(value, "positions")?)?;
+        let faces_value = self.required(value, "faces")?;
+        let faces = self.array(faces_value)?.iter().map(|face| {
+            let items = self.array(face)?;
+            if items.len() < 3 || scheme == Scheme::Loop && items.len() != 3 {
+                let expected = if scheme == Scheme::Loop { "loop subdivision needs triangles" } else { "a face needs at least 3 indices" };
+                return Err(self.error(face, expected))
+            }
+            items.iter().map(|item| self.index(item, positions.len())).collect()
+        }).collect::<Result<Vec<Vec<usize>>, SceneError>>()?;
+        if faces.is_empty() {
+            return Err(self.error(faces_value, "a control mesh needs at least one face"))
+        }
+
+        let mut mesh = ControlMesh::new(positions, faces);
+        if let Some(uvs_value) = value.get("uvs") {
+            let uvs = self.uv_list(uvs_value)?;
+            if uvs.len() != mesh.positions().len() {
+                return Err(self.error(uvs_value, "a control mesh needs one uv per position"))
+            }
+            mesh = mesh.with_uvs(uvs);
+        }
+        // creases are sharp at every level unless given a sharpness
+        if let Some(creases) = value.get("creases") {
+            for crease in self.array(creases)? {
+                self.check_keys(crease, &["edge", "sharpness"])?;
+                let edge = self.required(crease, "edge")?;
+                let items = self.array(edge)?;
+                if items.len() != 2 {
+                    return Err(self.error(edge, "an edge needs 2 indices"))
+                }
+                let (a, b) = (self.index(&items[0], mesh.positions().len())?, self.index(&items[1], mesh.positions().len())?);
+                let is_edge = mesh.faces().iter().any(|face| (0..face.len()).any(|i| {
+                    let (x, y) = (face[i], face[(i + 1) % face.len()]);
+                    (x, y) == (a, b) || (x, y) == (b, a)
+                }));
+                if !is_edge {
+                    return Err(self.error(edge, format!("{} and {} are not the ends of an edge", a, b)))
+                }
+                let sharpness = match crease.get("sharpness") {
+                    Some(_) => self.positive(crease, "sharpness")?,
+                    None => f64::INFINITY
+                };
+                mesh = mesh.with_crease(a, b, sharpness);
+            }
+        }
+        Ok(mesh.subdivide(scheme, levels as usize))
+    }
+
+    fn bvh(&self, objects: Vec<Box<dyn Hittable>>, time0: f64, time1: f64, split: SplitMethod) -> BVH {
+        let bvh = BVH::with_split(objects, time0, time1, split);
+        self.bvh_stats.borrow_mut().push(bvh.stats());
+        bvh
+    }
+
+    // keeps the statistics of the tree of a mesh
+    fn mesh(&self, mesh: TriangleMesh) -> TriangleMesh {
+        self.bvh_stats.borrow_mut().push(mesh.stats());
+        mesh
+    }
+
+    fn keyframe(&self, value: &Value) -> Result<Keyframe, SceneError> {
+        self.check_keys(value, &["time", "translate", "rotate", "angle", "scale"])?;
+        let mut keyframe = Keyframe::new(self.number_field(value, "time")?);
+        if let Some(offset) = value.get("translate") {
+            keyframe = keyframe.with_translation(self.vec3(offset)?);
+        }
+        if let Some(axis) = value.get("rotate") {
+            keyframe = keyframe.with_rotation(self.vec3(axis)?, self.number_field(value, "angle")?);
+        }
+        if let Some(factors_value) = value.get("scale") {
+            let factors = self.scale_factors(factors_value)?;
+            if (0..3).any(|a| factors[a] == 0.0) {
+                return Err(self.error(factors_value, "scale factors must not be zero"))
+            }
+            keyframe = keyframe.with_scale(factors);
+        }
+        Ok(keyframe)
+    }
+
+    fn sdf(&self, value: &Value) -> Result<Box<dyn Distance>, SceneError> {
+        let shape: Box<dyn Distance> = match self.type_of(value)? {
+            "sphere" => {
+                self.check_keys(value, &["type", "radius"])?;
+                Box::new(sdf::sphere(self.positive(value, "radius")?))
+            },
+            "box" => {
+                self.check_keys(value, &["type", "half_extents"])?;
+                Box::new(sdf::cuboid(self.vec3_field(value, "half_extents")?))
+            },
+            "torus" => {
+                self.check_keys(value, &["type", "major_radius", "minor_radius"])?;
+                Box::new(sdf::torus(self.positive(value, "major_radius")?, self.positive(value, "minor_radius")?))
+            },
+            "capsule" => {
+                self.check_keys(value, &["type", "a", "b", "radius"])?;
+                Box::new(sdf::capsule(self.vec3_field(value, "a")?, self.vec3_field(value, "b")?, self.positive(value, "radius")?))
+            },
+            "translate" => {
+                self.check_keys(value, &["type", "offset", "shape"])?;
+                Box::new(sdf::translate(self.sdf(self.required(value, "shape")?)?, self.vec3_field(value, "offset")?))
+            },
+            kind @ ("union" | "intersection" | "subtraction") => {
+                self.check_keys(value, &["type", "a", "b"])?;
+                let (a, b) = (self.sdf(self.required(value, "a")?)?, self.sdf(self.required(value, "b")?)?);
+                match kind {
+                    "union" => Box::new(sdf::union(a, b)),
+                    "intersection" => Box::new(sdf::intersection(a, b)),
+                    _ => Box::new(sdf::subtraction(a, b))
+                }
+            },
+            kind @ ("smooth_union" | "smooth_subtraction") => {
+                self.check_keys(value, &["type", "a", "b", "k"])?;
+                let (a, b) = (self.sdf(self.required(value, "a")?)?, self.sdf(self.required(value, "b")?)?);
+                let k = self.positive(value, "k")?;
+                if kind == "smooth_union" { Box::new(sdf::smooth_union(a, b, k)) } else { Box::new(sdf::smooth_subtraction(a, b, k)) }
+            },
+            "repeat" => {
+                self.check_keys(value, &["type", "period", "shape"])?;
+                Box::new(sdf::repeat(self.sdf(self.required(value, "shape")?)?, self.vec3_field(value, "period")?))
+            },
+            "twist" => {
+                self.check_keys(value, &["type", "angle", "shape"])?;
+                Box::new(sdf::twist(self.sdf(self.required(value, "shape")?)?, self.number_field(value, "angle")?))
+            },
+            other => return Err(self.error(value, format!("unknown sdf shape '{}'", other)))
+        };
+        Ok(shape)
+    }
+
+    fn object_list(&self, value: &Value) -> Result<Vec<Box<dyn Hittable>>, SceneError> {
+        self.array(value)?.iter().map(|v| self.object(v)).collect()
+    }
+
+    fn object(&self, value: &Value) -> Result<Box<dyn Hittable>, SceneError> {
+        let object: Box<dyn Hittable> = match self.type_of(value)? {
+            "sphere" => {
+                self.check_keys(value, &["type", "center", "radius", "material"])?;
+                Box::new(Sphere::new(self.vec3_field(value, "center")?,
+                                     self.number_field(value, "radius")?,
+                                     self.material_field(value)?))
+            },
+            "quad" => {
+                self.check_keys(value, &["type", "q", "u", "v", "material"])?;
+                let (u, v) = (self.vec3_field(value, "u")?, self.vec3_field(value, "v")?);
+                if !quad::spans_plane(u, v) {
+                    return Err(self.error(value, "the edges u and v of a quad must not be parallel"))
+                }
+                Box::new(Quad::new(self.vec3_field(value, "q")?, u, v, self.material_field(value)?))
+            },
+            "disk" => {
+                self.check_keys(value, &["type", "height", "radius", "inner_radius", "sweep", "material"])?;
+                let radius = self.positive(value, "radius")?;
+                let inner_radius = self.number_or(value, "inner_radius", 0.0)?;
+                if inner_radius < 0.0 || inner_radius >= radius {
+                    return Err(self.error(self.required(value, "inner_radius")?, "inner_radius must be in [0, radius)"))
+                }
+                Box::new(Disk::new(self.number_or(value, "height", 0.0)?, radius, self.material_field(value)?)
+                    .with_inner_radius(inner_radius)
+                    .with_sweep(self.sweep(value)?))
+            },
+            "cylinder" => {
+                self.check_keys(value, &["type", "radius", "y0", "y1", "sweep", "caps", "material"])?;
+                let (y0, y1) = (self.number_field(value, "y0")?, self.number_field(value, "y1")?);
+                if y0 >= y1 {
+                    return Err(self.error(value, "a cylinder needs y0 < y1"))
+                }
+                let cylinder = Cylinder::new(self.positive(value, "radius")?, y0, y1, self.material_field(value)?)
+                    .with_sweep(self.sweep(value)?);
+                if self.bool_or(value, "caps", false)? { Box::new(cylinder.with_caps()) } else { Box::new(cylinder) }
+            },
+            "cone" => {
+                self.check_keys(value, &["type", "radius", "height", "sweep", "caps", "material"])?;
+                let cone = Cone::new(self.positive(value, "radius")?, self.positive(value, "height")?, self.material_field(value)?)
+                    .with_sweep(self.sweep(value)?);
+                if self.bool_or(value, "caps", false)? { Box::new(cone.with_caps()) } else { Box::new(cone) }
+            },
+            "paraboloid" => {
+                self.check_keys(value, &["type", "radius", "y0", "y1", "sweep", "material"])?;
+                let (y0, y1) = (self.number_or(value, "y0", 0.0)?, self.number_field(value, "y1")?);
+                if y0 < 0.0 || y0 >= y1 {
+                    return Err(self.error(value, "a paraboloid needs 0 <= y0 < y1"))
+                }
+                Box::new(Paraboloid::new(self.positive(value, "radius")?, y0, y1, self.material_field(value)?)
+                    .with_sweep(self.sweep(value)?))
+            },
+            "torus" => {
+                self.check_keys(value, &["type", "major_radius", "minor_radius", "material"])?;
+                let major_radius = self.positive(value, "major_radius")?;
+                let minor_radius = self.positive(value, "minor_radius")?;
+                if minor_radius >= major_radius {
+                    return Err(self.error(value, "a torus needs minor_radius < major_radius"))
+                }
+                Box::new(Torus::new(major_radius, minor_radius, self.material_field(value)?))
+            },
+            "moving_sphere" => {
+                self.check_keys(value, &["type", "center0", "center1", "time0", "time1", "radius", "material"])?;
+                Box::new(MovingSphere::new(self.vec3_field(value, "center0")?,
+                                           self.vec3_field(value, "center1")?,
+                                           self.number_or(value, "time0", 0.0)?,
+                                           self.number_or(value, "time1", 1.0)?,
+                                           self.number_field(value, "radius")?,
+                                           self.material_field(value)?))
+            },
+            "rect" => {
+                self.check_keys(value, &["type", "plane", "a0", "a1", "b0", "b1", "k", "material"])?;
+                let plane_value = self.required(value, "plane")?;
+                let plane = match self.string(plane_value)? {
+                    "xy" => Plane::XY,
+                    "xz" => Plane::XZ,
+                    "yz" => Plane::YZ,
+                    other => return Err(self.error(plane_value, format!("unknown plane '{}', expected xy, xz or yz", other)))
+                };
+                Box::new(AARect::new(plane,
+                                     self.number_field(value, "a0")?,
+                                     self.number_field(value, "a1")?,
+                                     self.number_field(value, "b0")?,
+                                     self.number_field(value, "b1")?,
+                                     self.number_field(value, "k")?,
+                                     self.material_field(value)?))
+            },
+            "cube" => {
+                // either axis aligned between min and max, or spanned by three edges from a corner
+                if value.get("corner").is_some() {
+                    self.check_keys(value, &["type", "corner", "edges", "material"])?;
+                    let edges_value = self.required(value, "edges")?;
+                    let edges = self.vec3_list(edges_value)?;
+                    // two of them spanning a plane give at least a flat panel
+                    if edges.len() != 3 || !(0..3).any(|i| quad::spans_plane(edges[i], edges[(i + 1) % 3])) {
+                        return Err(self.error(edges_value, "edges must be three vectors that span at least a plane"))
+                    }
+                    Box::new(Cube::oriented(self.vec3_field(value, "corner")?, edges[0], edges[1], edges[2], self.material_field(value)?))
+                } else {
+                    self.check_keys(value, &["type", "min", "max", "material"])?;
+                    let (min, max) = (self.vec3_field(value, "min")?, self.vec3_field(value, "max")?);
+                    if (0..3).filter(|&axis| max[axis] != min[axis]).count() < 2 {
+                        return Err(self.error(value, "min and max of a box must differ along at least two axes"))
+                    }
+                    Box::new(Cube::new(min, max, self.material_field(value)?))
+                }
+            },
+            "triangle" => {
+                self.check_keys(value, &["type", "vertices", "normals", "uvs", "material"])?;
+                let vertices_value = self.required(value, "vertices")?;
+                let vertices = self.vec3_list(vertices_value)?;
+                if vertices.len() != 3 {
+                    return Err(self.error(vertices_value, "a triangle needs 3 vertices"))
+                }
+                let mut triangle = Triangle::new(vertices[0], vertices[1], vertices[2], self.material_field(value)?);
+                if let Some(normals_value) = value.get("normals") {
+                    let normals = self.vec3_list(normals_value)?;
+                    if normals.len() != 3 {
+                        return Err(self.error(normals_value, "a triangle needs 3 normals"))
+                    }
+                    triangle = triangle.with_normals([normals[0], normals[1], normals[2]]);
+                }
+                if let Some(uvs_value) = value.get("uvs") {
+                    let uvs = self.uv_list(uvs_value)?;
+                    if uvs.len() != 3 {
+                        return Err(self.error(uvs_value, "a triangle needs 3 uvs"))
+                    }
+                    triangle = triangle.with_uvs([uvs[0], uvs[1], uvs[2]]);
+                }
+                Box::new(triangle)
+            },
+            "mesh" => {
+                self.check_keys(value, &["type", "positions", "indices", "normals", "uvs", "material"])?;
+                let (positions, indices, normals, uvs) = self.mesh_data(value)?;
+                Box::new(self.mesh(TriangleMesh::with_split(positions, indices, normals, uvs, self.material_field(value)?, self.split)))
+            },
+            "subdivision" => {
+                self.check_keys(value, &["type", "scheme", "levels", "positions", "faces", "uvs", "creases", "material"])?;
+                Box::new(self.mesh(self.subdivided(value)?.to_mesh(self.material_field(value)?, self.split)))
+            },
+            "displace" => {
+                self.check_keys(value, &["type", "shape", "texture", "scale", "resolution", "material"])?;
+                let resolution = self.number_or(value, "resolution", 16.0)?;
+                if !(1.0..=1024.0).contains(&resolution) || resolution.fract() != 0.0 {
+                    return Err(self.error(self.required(value, "resolution")?, "resolution must be a whole number from 1 to 1024"))
+                }
+                let resolution = resolution as usize;
+                // the shapes are given without a material, the displaced surface has one
+                let shape = self.required(value, "shape")?;
+                let tessellation = match self.type_of(shape)? {
+                    "sphere" => {
+                        self.check_keys(shape, &["type", "center", "radius"])?;
+                        if resolution < 2 {
+                            return Err(self.error(self.required(value, "resolution")?, "a sphere needs a resolution of at least 2"))
+                        }
+                        Tessellation::sphere(self.vec3_field(shape, "center")?, self.positive(shape, "radius")?, resolution)
+                    },
+                    "quad" => {
+                        self.check_keys(shape, &["type", "q", "u", "v"])?;
+                        let (u, v) = (self.vec3_field(shape, "u")?, self.vec3_field(shape, "v")?);
+                        if !quad::spans_plane(u, v) {
+                            return Err(self.error(shape, "the edges u and v of a quad must not be parallel"))
+                        }
+                        Tessellation::quad(self.vec3_field(shape, "q")?, u, v, resolution)
+                    },
+                    "mesh" => {
+                        self.check_keys(shape, &["type", "positions", "indices", "normals", "uvs"])?;
+                        let (positions, indices, normals, uvs) = self.mesh_data(shape)?;
+                        Tessellation::new(positions, indices, normals, uvs).tessellate(resolution)
+                    },
+                    "subdivision" => {
+                        self.check_keys(shape, &["type", "scheme", "levels", "positions", "faces", "uvs", "creases"])?;
+                        self.subdivided(shape)?.tessellate(resolution)
+                    },
+                    other => return Err(self.error(shape, format!("cannot displace a '{}', expected sphere, quad, mesh or subdivision", other)))
+                };
+                let texture = self.texture(self.required(value, "texture")?)?;
+                let scale = self.number_field(value, "scale")?;
+                Box::new(self.mesh(tessellation.displaced(&texture, scale).into_mesh(self.material_field(value)?, self.split)))
+            },
+            "particles" => {
+                self.check_keys(value, &["type", "positions", "radius", "colors", "velocities", "time0", "time1", "material"])?;
+                let positions_value = self.required(value, "positions")?;
+                let positions = self.vec3_list(positions_value)?;
+                if positions.is_empty() {
+                    return Err(self.error(positions_value, "particles need at least one position"))
+                }
+                // one radius for all or one per particle
+                let radius_value = self.required(value, "radius")?;
+                let radii = match radius_value.kind {
+                    Kind::Number(r) => vec![r; positions.len()],
+                    _ => self.array(radius_value)?.iter().map(|r| self.number(r)).collect::<Result<Vec<f64>, SceneError>>()?
+                };
+                if radii.len() != positions.len() {
+                    return Err(self.error(radius_value, "particles need one radius per position"))
+                }
+                if radii.iter().any(|&r| r <= 0.0) {
+                    return Err(self.error(radius_value, "the radius of a particle must be positive"))
+                }
+
+                // colored particles are lambertian in their own color
+                let particles = match (value.get("colors"), value.get("material")) {
+                    (Some(colors_value), None) => {
+                        let colors = self.vec3_list(colors_value)?;
+                        if colors.len() != positions.len() {
+                            return Err(self.error(colors_value, "particles need one color per position"))
+                        }
+                        let white: Arc<dyn Material> = Arc::new(Lambertian::new(ConstantTexture::new(Color::new(1.0, 1.0, 1.0))));
+                        ParticleSet::with_split(positions, radii, white, self.split)
+                            .with_colors(&colors, |c| Arc::new(Lambertian::new(ConstantTexture::new(c))) as Arc<dyn Material>)
+                    },
+                    (None, Some(_)) => ParticleSet::with_split(positions, radii, self.material_field(value)?, self.split),
+                    (Some(_), Some(_)) => return Err(self.error(value, "particles take either \"colors\" or a \"material\", not both")),
+                    (None, None) => return Err(self.error(value, "particles need \"colors\" or a \"material\""))
+                };
+                let particles = match value.get("velocities") {
+                    Some(velocities_value) => {
+                        let velocities = self.vec3_list(velocities_value)?;
+                        if velocities.len() != particles.len() {
+                            return Err(self.error(velocities_value, "particles need one velocity per position"))
+                        }
+                        particles.with_velocities(velocities, self.number_or(value, "time0", 0.0)?, self.number_or(value, "time1", 1.0)?)
+                    },
+                    None => particles
+                };
+                self.bvh_stats.borrow_mut().push(particles.stats());
+                Box::new(particles)
+            },
+            "obj" => {
+                self.check_keys(value, &["type", "path"])?;
+                let file = self.required(value, "path")?;
+                let meshes = obj::load_obj(self.dir.join(self.string(file)?), self.split)
+                    .map_err(|e| self.error(file, e.to_string()))?;
+                let meshes = meshes.into_iter().map(|mesh| Box::new(self.mesh(mesh)) as Box<dyn Hittable>).collect();
+                Box::new(self.bvh(meshes, 0.0, 1.0, self.split))
+            },
+            "list" => {
+                self.check_keys(value, &["type", "objects"])?;
+                let mut list = HittableList::default();
+                for object in self.object_list(self.required(value, "objects")?)? {
+                    list.push(object);
+                }
+                Box::new(list)
+            },
+            "bvh" => {
+                self.check_keys(value, &["type", "objects", "time0", "time1", "split"])?;
+                let objects_value = self.required(value, "objects")?;
+                let objects = self.object_list(objects_value)?;
+                if objects.is_empty() {
+                    return Err(self.error(objects_value, "a bvh needs at least one object"))
+                }
+                let split = match value.get("split") {
+                    Some(split) => SplitMethod::from_name(self.string(split)?)
+                        .ok_or_else(|| self.error(split, "split must be \"sah\" or \"median\""))?,
+                    None => self.split
+                };
+                Box::new(self.bvh(objects, self.number_or(value, "time0", 0.0)?, self.number_or(value, "time1", 1.0)?, split))
+            },
+            "translate" => {
+                self.check_keys(value, &["type", "offset", "object"])?;
+                Box::new(Translate::new(self.object(self.required(value, "object")?)?, self.vec3_field(value, "offset")?))
+            },
+            "rotate" => {
+                self.check_keys(value, &["type", "axis", "angle", "object"])?;
+                let axis_value = self.required(value, "axis")?;
+                let axis = match self.string(axis_value)? {
+                    "x" => Axis::X,
+                    "y" => Axis::Y,
+                    "z" => Axis::Z,
+                    other => return Err(self.error(axis_value, format!("unknown axis '{}', expected x, y or z", other)))
+                };
+                Box::new(Rotate::new(axis, self.object(self.required(value, "object")?)?, self.number_field(value, "angle")?))
+            },
+            "transform" => {
+                self.check_keys(value, &["type", "transform", "object"])?;
+                let transform_value = self.required(value, "transform")?;
+                let transform = self.transform(transform_value)?;
+                if transform.inverse().is_none() {
+                    return Err(self.error(transform_value, "the transform cannot be inverted"))
+                }
+                Box::new(Transform::new(self.object(self.required(value, "object")?)?, transform))
+            },
+            "animated" => {
+                self.check_keys(value, &["type", "keyframes", "object"])?;
+                let keyframes_value = self.required(value, "keyframes")?;
+                let keyframes = self.array(keyframes_value)?.iter().map(|k| self.keyframe(k)).collect::<Result<Vec<_>, _>>()?;
+                if keyframes.is_empty() {
+                    return Err(self.error(keyframes_value, "an animation needs at least one keyframe"))
+                }
+                Box::new(Animated::new(self.object(self.required(value, "object")?)?, keyframes))
+            },
+            "instance" => {
+                self.check_keys(value, &["type", "geometry", "transform", "material"])?;
+                let transform_value = self.required(value, "transform")?;
+                let transform = self.transform(transform_value)?;
+                if transform.inverse().is_none() {
+                    return Err(self.error(transform_value, "the transform cannot be inverted"))
+                }
+                let mut instance = Instance::new(self.geometry(self.required(value, "geometry")?)?, transform);
+                if let Some(material) = value.get("material") {
+                    instance = instance.with_material(self.material(material)?);
+                }
+                Box::new(instance)
+            },
+            "curves" => {
+                self.check_keys(value, &["type", "strands", "basis", "kind", "width", "material"])?;
+                let basis = match value.get("basis") {
+                    Some(basis) => match self.string(basis)? {
+                        "bezier" => Basis::Bezier,
+                        "bspline" => Basis::BSpline,
+                        other => return Err(self.error(basis, format!("unknown basis '{}', expected bezier or bspline", other)))
+                    },
+                    None => Basis::Bezier
+                };
+                let kind = match value.get("kind") {
+                    Some(kind) => match self.string(kind)? {
+                        "flat" => CurveKind::Flat,
+                        "cylinder" => CurveKind::Cylinder,
+                        other => return Err(self.error(kind, format!("unknown curve kind '{}', expected flat or cylinder", other)))
+                    },
+                    None => CurveKind::Cylinder
+                };
+                let width = self.strand_widths(self.required(value, "width")?)?;
+                let material = self.material_field(value)?;
+
+                // a strand is a list of points, or an object with its own width
+                let strands_value = self.required(value, "strands")?;
+                let mut segments: Vec<Box<dyn Hittable>> = Vec::new();
+                for strand in self.array(strands_value)? {
+                    let (points_value, width) = match strand.kind {
+                        Kind::Object(_) => {
+                            self.check_keys(strand, &["points", "width"])?;
+                            let width = match strand.get("width") {
+                                Some(w) => self.strand_widths(w)?,
+                                None => width
+                            };
+                            (self.required(strand, "points")?, width)
+                        },
+                        _ => (strand, width)
+                    };
+                    let points = self.vec3_list(points_value)?;
+                    let valid = match basis {
+                        Basis::Bezier => points.len() >= 4 && (points.len() - 1).is_multiple_of(3),
+                        Basis::BSpline => points.len() >= 4
+                    };
+                    if !valid {
+                        let expected = if basis == Basis::Bezier { "3n + 1 points, at least 4" } else { "at least 4 points" };
+                        return Err(self.error(points_value, format!("a strand needs {}, found {}", expected, points.len())))
+                    }
+                    for segment in curve::strand(&points, basis, width, kind, material.clone()) {
+                        segments.push(Box::new(segment));
+                    }
+                }
+                if segments.is_empty() {
+                    return Err(self.error(strands_value, "curves need at least one strand"))
+                }
+                Box::new(self.bvh(segments, 0.0, 1.0, self.split))
+            },
+            "heightfield" => {
+                self.check_keys(value, &["type", "image", "noise", "min", "extent", "material"])?;
+                let (min, extent) = (self.vec3_field(value, "min")?, self.vec3_field(value, "extent")?);
+                if extent.x() <= 0.0 || extent.z() <= 0.0 {
+                    return Err(self.error(self.required(value, "extent")?, "the extent along x and z must be positive"))
+                }
+                let material = self.material_field(value)?;
+                match (value.get("image"), value.get("noise")) {
+                    (Some(file), None) => {
+                        let path = self.dir.join(self.string(file)?);
+                        Box::new(Heightfield::from_image(&path, min, extent, material)
+                            .map_err(|e| self.error(file, format!("{}: {}", path.display(), e)))?)
+                    },
+                    (None, Some(noise)) => {
+                        self.check_keys(noise, &["columns", "rows", "frequency", "octaves"])?;
+                        let columns = self.number_field(noise, "columns")?;
+                        let rows = self.number_field(noise, "rows")?;
+                        if columns < 2.0 || rows < 2.0 || columns.fract() != 0.0 || rows.fract() != 0.0 {
+                            return Err(self.error(noise, "columns and rows must be whole numbers of at least 2"))
+                        }
+                        let octaves = self.number_or(noise, "octaves", 4.0)?.max(1.0) as usize;
+                        let heights = heightfield::noise_heights(columns as usize, rows as usize, self.positive(noise, "frequency")?, octaves, *self.rng.borrow_mut());
+                        Box::new(Heightfield::new(heights, columns as usize, min, extent, material))
+                    },
+                    _ => return Err(self.error(value, "a heightfield needs either \"image\" or \"noise\""))
+                }
+            },
+            "sdf" => {
+                self.check_keys(value, &["type", "shape", "min", "max", "lipschitz", "epsilon", "material"])?;
+                let (min, max) = (self.vec3_field(value, "min")?, self.vec3_field(value, "max")?);
+                if (0..3).any(|a| min[a] >= max[a]) {
+                    return Err(self.error(value, "the bounds need min < max on every axis"))
+                }
+                let lipschitz = self.number_or(value, "lipschitz", 1.0)?;
+                if lipschitz < 1.0 {
+                    return Err(self.error(self.required(value, "lipschitz")?, "lipschitz cannot be below 1"))
+                }
+                let mut shape = SdfShape::new(self.sdf(self.required(value, "shape")?)?, AABB::new(min, max), self.material_field(value)?)
+                    .with_lipschitz(lipschitz);
+                if value.get("epsilon").is_some() {
+                    shape = shape.with_epsilon(self.positive(value, "epsilon")?);
+                }
+                Box::new(shape)
+            },
+            "union" | "intersection" | "difference" => {
+                self.check_keys(value, &["type", "a", "b"])?;
+                let operation = match self.type_of(value)? {
+                    "union" => Operation::Union,
+                    "intersection" => Operation::Intersection,
+                    _ => Operation::Difference
+                };
+                Box::new(Csg::new(operation, self.object(self.required(value, "a")?)?, self.object(self.required(value, "b")?)?))
+            },
+            "flip_normal" => {
+                self.check_keys(value, &["type", "object"])?;
+                Box::new(FlipNormal::new(self.object(self.required(value, "object")?)?))
+            },
+            "constant_medium" => {
+                self.check_keys(value, &["type", "boundary", "density", "albedo"])?;
+                Box::new(ConstantMedium::new(self.object(self.required(value, "boundary")?)?,
+                                             self.number_field(value, "density")?,
+                                             self.texture(self.required(value, "albedo")?)?))
+            },
+            other => return Err(self.error(value, format!("unknown object type '{}'", other)))
+        };
+        Ok(object)
+    }
+
+    fn camera(&self, value: &Value, aspect_ratio: f64) -> Result<Camera, SceneError> {
+        self.check_keys(value, &["lookfrom", "lookat", "vup", "vfov", "aperture", "focus_dist", "time0", "time1"])?;
+        let vup = match value.get("vup") {
+            Some(vup) => self.vec3(vup)?,
+            None => Vec3::new(0.0, 1.0, 0.0)
+        };
+        Ok(Camera::new(self.vec3_field(value, "lookfrom")?,
+                       self.vec3_field(value, "lookat")?,
+                       vup,
+                       self.number_field(value, "vfov")?,
+                       aspect_ratio,
+                       self.number_or(value, "aperture", 0.0)?,
+                       self.number_or(value, "focus_dist", 10.0)?,
+                       self.number_or(value, "time0", 0.0)?,
+                       self.number_or(value, "time1", 1.0)?))
+    }
+}
+
+/// parse a json scene description, relative paths inside it are resolved from `dir`,
+/// its bvhs are split with `split` unless they name their own method and the noise textures are drawn from `rng`
+pub fn parse(source: &str, path: &Path, dir: &Path, aspect_ratio: f64, split: SplitMethod, rng: &mut StdRng) -> Result<Scene, SceneError> {
+    let root = json::parse(source).map_err(|e| SceneError {
+        path: path.to_path_buf(),
+        line: e.line,
+        column: e.column,
+        message: e.message
+    })?;
+
+    let mut builder = Builder {
+        path,
+        dir,
+        textures: HashMap::new(),
+        materials: HashMap::new(),
+        geometry: HashMap::new(),
+        rng: RefCell::new(rng),
+        split,
+        bvh_stats: RefCell::new(Vec::new())
+    };
+    builder.check_keys(&root, &["camera", "background", "textures", "materials", "geometry", "objects", "lights"])?;
+
+    // definitions may refer to the ones written before them
+    if let Some(textures) = root.get("textures") {
+        for (name, value) in builder.fields(textures)? {
+            let texture = builder.build_texture(value)?;
+            builder.textures.insert(name.clone(), texture);
+        }
+    }
+    if let Some(materials) = root.get("materials") {
+        for (name, value) in builder.fields(materials)? {
+            let material = builder.build_material(value)?;
+            builder.materials.insert(name.clone(), material);
+        }
+    }
+    // shared by the instances, not part of the world on their own
+    if let Some(geometry) = root.get("geometry") {
+        for (name, value) in builder.fields(geometry)? {
+            let object: Arc<dyn Hittable> = Arc::from(builder.object(value)?);
+            builder.geometry.insert(name.clone(), object);
+        }
+    }
+
+    let mut world = HittableList::default();
+    let mut lights = HittableList::default();
+    if let Some(objects) = root.get("objects") {
+        for object in builder.object_list(objects)? {
+            world.push(object);
+        }
+    }
+    // lights are part of the world and are also sampled directly
+    if let Some(objects) = root.get("lights") {
+        for object in builder.object_list(objects)? {
+            let object: Arc<dyn Hittable> = Arc::from(object);
+            world.push(object.clone());
+            lights.push(object);
+        }
+    }
+
+    let background = match root.get("background") {
+        Some(background) => builder.vec3(background)?,
+        None => Color::new(0.0, 0.0, 0.0)
+    };
+    let camera = builder.camera(builder.required(&root, "camera")?, aspect_ratio)?;
+
+    Ok(Scene {
+        world: Box::new(world),
+        lights,
+        background,
+        camera,
+        bvh_stats: builder.bvh_stats.into_inner()
+    })
+}
+
+/// read a json scene description from a file
+pub fn load(path: impl AsRef<Path>, aspect_ratio: f64, split: SplitMethod, rng: &mut StdRng) -> Result<Scene, SceneError> {
+    let path = path.as_ref();
+    let source = fs::read_to_string(path).map_err(|e| SceneError {
+        path: path.to_path_buf(),
+        line: 0,
+        column: 0,
+        message: e.to_string()
+    })?;
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+    parse(&source, path, dir, aspect_ratio, split, rng)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use super::*;
+
+    const CAMERA: &str = r#""camera": { "lookfrom": [0, 0, 5], "lookat": [0, 0, 0], "vfov": 40 }"#;
+
+    fn parse_str(source: &str) -> Result<Scene, SceneError> {
+        parse(source, Path::new("test.json"), Path::new(""), 1.0, SplitMethod::Sah, &mut StdRng::seed_from_u64(0))
+    }
+
+    fn error_at(source: &str) -> (usize, usize, String) {
+        match parse_str(source) {
+            Ok(_) => panic!("the scene was accepted"),
+            Err(e) => (e.line, e.column, e.message)
+        }
+    }
+
+    #[test]
+    fn minimal_scene() {
+        let scene = parse_str(&format!(r#"{{
+            {},
+            "materials": {{ "light": {{ "type": "diffuse_light", "emit": [4, 4, 4] }} }},
+            "objects": [{{ "type": "sphere", "center": [0, 0, 0], "radius": 1, "material": {{ "type": "lambertian", "albedo": [0.5, 0.5, 0.5] }} }}],
+            "lights": [{{ "type": "rect", "plane": "xy", "a0": -1, "a1": 1, "b0": 3, "b1": 4, "k": 0, "material": "light" }}]
+        }}"#, CAMERA)).unwrap();
+        assert_eq!(scene.lights.len(), 1);
+        assert_eq!(scene.background.y(), 0.0);
+        // the light is in the world too
+        assert!(scene.world.bounding_box(0.0, 1.0).unwrap().max.y() >= 3.0);
+    }
+
+    #[test]
+    fn unknown_key() {
+        let source = format!("{{\n  {},\n  \"objects\": [\n    {{ \"type\": \"sphere\", \"centre\": [0, 0, 0] }}\n  ]\n}}", CAMERA);
+        assert_eq!(error_at(&source), (4, 35, "unknown key 'centre'".to_string()));
+    }
+
+    #[test]
+    fn missing_required_field() {
+        let source = format!("{{\n  {},\n  \"objects\": [\n    {{ \"type\": \"sphere\", \"center\": [0, 0, 0], \"material\": \"red\" }}\n  ]\n}}", CAMERA);
+        // reported at the object that lacks it
+        assert_eq!(error_at(&source), (4, 5, "missing key 'radius'".to_string()));
+
+        assert_eq!(error_at("{\n  \"objects\": []\n}"), (1, 1, "missing key 'camera'".to_string()));
+    }
+
+    #[test]
+    fn wrong_type() {
+        let source = format!("{{\n  {},\n  \"objects\": [\n    {{ \"type\": \"sphere\", \"center\": [0, 0, 0],\n      \"radius\": \"big\" }}\n  ]\n}}", CAMERA);
+        assert_eq!(error_at(&source), (5, 17, "expected a number, found a string".to_string()));
+    }
+
+    #[test]
+    fn json_syntax_error() {
+        let (line, column, _) = error_at("{\n  \"camera\": [1, 2,, 3]\n}");
+        assert_eq!((line, column), (2, 19));
+    }
+}
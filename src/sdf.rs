@@ -0,0 +1,305 @@
+use std::f64;
+use super::vec::{Vec3, Point3};
+use super::ray::Ray;
+use super::sampler::Sampler;
+use super::hit::{Hittable, HitRecord};
+use super::mat::Material;
+use super::aabb::AABB;
+
+/// signed distance: negative inside, positive outside, and never more than the distance to the
+/// surface (or a constant times it, see `SdfShape::with_lipschitz`)
+pub trait Distance: Fn(Point3) -> f64 + Send + Sync {}
+
+impl<F: Fn(Point3) -> f64 + Send + Sync> Distance for F {}
+
+pub fn sphere(radius: f64) -> impl Distance {
+    move |p: Point3| p.length() - radius
+}
+
+/// box centered at the origin
+pub fn cuboid(half_extents: Vec3) -> impl Distance {
+    move |p: Point3| {
+        let q = Vec3::new(p.x().abs() - half_extents.x(), p.y().abs() - half_extents.y(), p.z().abs() - half_extents.z());
+        let outside = Vec3::new(q.x().max(0.0), q.y().max(0.0), q.z().max(0.0)).length();
+        outside + q.x().max(q.y()).max(q.z()).min(0.0)
+    }
+}
+
+/// torus around the y axis
+pub fn torus(major_radius: f64, minor_radius: f64) -> impl Distance {
+    move |p: Point3| ((p.x() * p.x() + p.z() * p.z()).sqrt() - major_radius).hypot(p.y()) - minor_radius
+}
+
+/// the points within `radius` of the segment from a to b
+pub fn capsule(a: Point3, b: Point3, radius: f64) -> impl Distance {
+    move |p: Point3| {
+        let (pa, ba) = (p - a, b - a);
+        let h = (pa.dot(ba) / ba.dot(ba)).clamp(0.0, 1.0);
+        (pa - h * ba).length() - radius
+    }
+}
+
+pub fn translate(f: impl Distance, offset: Vec3) -> impl Distance {
+    move |p: Point3| f(p - offset)
+}
+
+pub fn union(a: impl Distance, b: impl Distance) -> impl Distance {
+    move |p: Point3| a(p).min(b(p))
+}
+
+pub fn intersection(a: impl Distance, b: impl Distance) -> impl Distance {
+    move |p: Point3| a(p).max(b(p))
+}
+
+/// a with b cut away
+pub fn subtraction(a: impl Distance, b: impl Distance) -> impl Distance {
+    move |p: Point3| a(p).max(-b(p))
+}
+
+/// union blending the two shapes over a seam of about width k
+pub fn smooth_union(a: impl Distance, b: impl Distance, k: f64) -> impl Distance {
+    move |p: Point3| {
+        let (da, db) = (a(p), b(p));
+        let h = (0.5 + 0.5 * (db - da) / k).clamp(0.0, 1.0);
+        db + (da - db) * h - k * h * (1.0 - h)
+    }
+}
+
+/// a with b carved away, rounding the edges of the cut over about width k
+pub fn smooth_subtraction(a: impl Distance, b: impl Distance, k: f64) -> impl Distance {
+    move |p: Point3| {
+        let (da, db) = (a(p), b(p));
+        let h = (0.5 - 0.5 * (da + db) / k).clamp(0.0, 1.0);
+        da + (-db - da) * h + k * h * (1.0 - h)
+    }
+}
+
+/// copies of the shape on a grid with the given spacing, a spacing of 0 does not repeat along
+/// that axis. the shape should fit in one cell
+pub fn repeat(f: impl Distance, period: Vec3) -> impl Distance {
+    move |p: Point3| {
+        let mut q = p;
+        for a in 0..3 {
+            if period[a] != 0.0 {
+                q[a] = p[a] - period[a] * (p[a] / period[a]).round();
+            }
+        }
+        f(q)
+    }
+}
+
+/// turns the shape around the y axis by `angle` degrees per unit of height, this stretches
+/// distances, so raise the lipschitz bound of the shape to about sqrt(1 + (angle in radians * r)^2)
+/// for a shape reaching out to the radius r
+pub fn twist(f: impl Distance, angle: f64) -> impl Distance {
+    let rate = angle.to_radians();
+    move |p: Point3| {
+        let (sin, cos) = (rate * p.y()).sin_cos();
+        f(Point3::new(cos * p.x() - sin * p.z(), p.y(), sin * p.x() + cos * p.z()))
+    }
+}
+
+/// a shape given by a signed distance function, rendered by sphere tracing
+///
+/// the function is only evaluated within `bounds`, which must contain the whole surface
+pub struct SdfShape<F: Distance, M: Material> {
+    distance: F,
+    bounds: AABB,
+    material: M,
+    lipschitz: f64,
+    // distances below this count as the surface
+    epsilon: f64,
+    max_steps: usize
+}
+
+impl<F: Distance, M: Material> SdfShape<F, M> {
+    pub fn new(distance: F, bounds: AABB, material: M) -> SdfShape<F, M> {
+        let diagonal = (bounds.max - bounds.min).length();
+        SdfShape {
+            distance,
+            bounds,
+            material,
+            lipschitz: 1.0,
+            epsilon: 1.0e-5 * diagonal,
+            max_steps: 1000
+        }
+    }
+
+    /// how much faster than the distance to the surface the function can change, operators like
+    /// `twist` need more than 1 or the tracing steps through the surface
+    pub fn with_lipschitz(mut self, lipschitz: f64) -> SdfShape<F, M> {
+        assert!(lipschitz >= 1.0, "the lipschitz bound cannot be below 1");
+        self.lipschitz = lipschitz;
+        self
+    }
+
+    /// the precision of the hits, and the thinnest feature that is found
+    pub fn with_epsilon(mut self, epsilon: f64) -> SdfShape<F, M> {
+        assert!(epsilon > 0.0, "epsilon must be positive");
+        self.epsilon = epsilon;
+        self
+    }
+
+    // the part of [t_min, t_max] the ray spends in the bounds
+    fn clip(&self, r: &Ray, mut t_min: f64, mut t_max: f64) -> Option<(f64, f64)> {
+        for a in 0..3 {
+            let inverse = 1.0 / r.direction()[a];
+            let mut t0 = (self.bounds.min[a] - r.origin()[a]) * inverse;
+            let mut t1 = (self.bounds.max[a] - r.origin()[a]) * inverse;
+            if inverse < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            t_min = t_min.max(t0);
+            t_max = t_max.min(t1);
+            if t_max < t_min {
+                return None
+            }
+        }
+        Some((t_min, t_max))
+    }
+
+    // the gradient by central differences at the corners of a tetrahedron
+    fn gradient(&self, p: Point3) -> Vec3 {
+        let h = self.epsilon;
+        let corners = [Vec3::new(1.0, -1.0, -1.0), Vec3::new(-1.0, -1.0, 1.0), Vec3::new(-1.0, 1.0, -1.0), Vec3::new(1.0, 1.0, 1.0)];
+        corners.iter().map(|&k| (self.distance)(p + h * k) * k).sum()
+    }
+}
+
+impl<F: Distance, M: Material> Hittable for SdfShape<F, M> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, _rng: &mut Sampler) -> Option<HitRecord<'_>> {
+        let (start, end) = self.clip(r, t_min, t_max)?;
+        // converts a distance into a safe step along the ray parameter
+        let scale = 1.0 / (self.lipschitz * r.direction().length());
+
+        // step by the distance, which never crosses the surface, and look for the sign to change;
+        // the same works from inside, so rays refracted into the shape find their way out
+        let mut t = start;
+        let mut d = (self.distance)(r.at(t));
+        let mut crossing = None;
+        for _ in 0..self.max_steps {
+            let next = t + d.abs().max(self.epsilon) * scale;
+            if next > end {
+                break
+            }
+            let next_d = (self.distance)(r.at(next));
+            if (next_d < 0.0) != (d < 0.0) {
+                crossing = Some((t, next, d < 0.0));
+                break
+            }
+            t = next;
+            d = next_d;
+        }
+        let (mut lo, mut hi, inside) = crossing?;
+
+        // narrow the crossing down to well below epsilon
+        for _ in 0..64 {
+            if (hi - lo) / scale <= 0.01 * self.epsilon {
+                break
+            }
+            let mid = 0.5 * (lo + hi);
+            if ((self.distance)(r.at(mid)) < 0.0) == inside { lo = mid } else { hi = mid }
+        }
+        let t = 0.5 * (lo + hi);
+
+        let position = r.at(t);
+        let outward_normal = self.gradient(position).normalized();
+        let mut rec = HitRecord {
+            position,
+            normal: outward_normal,
+            t,
+            // there is no natural parametrization of the surface
+            u: 0.0,
+            v: 0.0,
+            tangent: Vec3::new(0.0, 0.0, 0.0),
+            front_face: false,
+            material: &self.material
+        };
+        rec.set_face_normal(r, outward_normal);
+        Some(rec)
+    }
+
+    fn bounding_box(&self, _t0: f64, _t1: f64) -> Option<AABB> {
+        Some(self.bounds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::{grey, hit};
+    use super::*;
+
+    fn cube(half: f64) -> AABB {
+        AABB::new(Point3::new(-half, -half, -half), Point3::new(half, half, half))
+    }
+
+    #[test]
+    fn primitive_distances() {
+        let b = cuboid(Vec3::new(1.0, 2.0, 3.0));
+        assert_eq!(b(Point3::new(0.0, 0.0, 0.0)), -1.0);
+        assert_eq!(b(Point3::new(4.0, 0.0, 0.0)), 3.0);
+        assert!((b(Point3::new(2.0, 3.0, 0.0)) - 2.0f64.sqrt()).abs() < 1e-12);
+        let t = torus(2.0, 0.5);
+        assert!((t(Point3::new(2.0, 0.0, 0.0)) + 0.5).abs() < 1e-12);
+        assert!((t(Point3::new(0.0, 0.0, 0.0)) - 1.5).abs() < 1e-12);
+        let c = capsule(Point3::new(0.0, -1.0, 0.0), Point3::new(0.0, 1.0, 0.0), 0.5);
+        assert!((c(Point3::new(0.0, 3.0, 0.0)) - 1.5).abs() < 1e-12);
+        assert!((c(Point3::new(2.0, 0.3, 0.0)) - 1.5).abs() < 1e-12);
+    }
+
+    #[test]
+    fn operators() {
+        let (a, b) = (|| sphere(1.0), || translate(sphere(1.0), Vec3::new(1.5, 0.0, 0.0)));
+        let p = Point3::new(-0.5, 0.0, 0.0);
+        assert!((union(a(), b())(p) + 0.5).abs() < 1e-12);
+        assert!((intersection(a(), b())(p) - 1.0).abs() < 1e-12);
+        assert!((subtraction(a(), b())(Point3::new(1.0, 0.0, 0.0)) - 0.5).abs() < 1e-12);
+        // the smooth union bulges out between the shapes and matches the union far from the seam
+        let q = Point3::new(0.75, 1.0, 0.0);
+        assert!(smooth_union(a(), b(), 0.5)(q) < union(a(), b())(q));
+        assert!((smooth_union(a(), b(), 0.5)(p) - union(a(), b())(p)).abs() < 1e-12);
+        let copies = repeat(sphere(0.5), Vec3::new(4.0, 0.0, 0.0));
+        assert!((copies(Point3::new(8.0, 1.0, 0.0)) - 0.5).abs() < 1e-12);
+    }
+
+    #[test]
+    fn sphere_tracing_matches_the_analytic_sphere() {
+        let shape = SdfShape::new(sphere(1.0), cube(1.1), grey());
+        let rec = hit(&shape, Point3::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 2.0)).unwrap();
+        assert!((rec.t - 2.0).abs() < 1e-5);
+        assert!((rec.normal - Vec3::new(0.0, 0.0, -1.0)).length() < 1e-4);
+        assert!(rec.front_face);
+
+        // an oblique ray, t scales with the length of the direction
+        let d = Vec3::new(0.1, -0.1, 1.0);
+        let t = hit(&shape, Point3::new(0.0, 0.4, -3.0), d).unwrap().t;
+        let exact = {
+            let o = Point3::new(0.0, 0.4, -3.0);
+            let (a, half_b, c) = (d.dot(d), o.dot(d), o.dot(o) - 1.0);
+            (-half_b - (half_b * half_b - a * c).sqrt()) / a
+        };
+        assert!((t - exact).abs() < 1e-5);
+        assert!(hit(&shape, Point3::new(0.0, 1.2, -5.0), Vec3::new(0.0, 0.0, 1.0)).is_none());
+    }
+
+    #[test]
+    fn leaves_from_inside() {
+        let shape = SdfShape::new(sphere(1.0), cube(1.1), grey());
+        let rec = hit(&shape, Point3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0)).unwrap();
+        assert!((rec.t - 1.0).abs() < 1e-5);
+        assert!(!rec.front_face);
+        // the normal faces the ray
+        assert!((rec.normal - Vec3::new(-1.0, 0.0, 0.0)).length() < 1e-4);
+    }
+
+    #[test]
+    fn thin_wall_is_not_stepped_over() {
+        let wall = SdfShape::new(cuboid(Vec3::new(1.0, 1.0, 0.01)), cube(1.1), grey());
+        let t = hit(&wall, Point3::new(0.3, 0.2, -5.0), Vec3::new(0.0, 0.0, 1.0)).unwrap().t;
+        assert!((t - 4.99).abs() < 1e-5);
+        let hollow = SdfShape::new(subtraction(sphere(1.0), sphere(0.9)), cube(1.1), grey());
+        // through the shell, the hole and out the far side of the shell
+        let t = hit(&hollow, Point3::new(0.0, 0.0, -0.95), Vec3::new(0.0, 0.0, 1.0)).unwrap().t;
+        assert!((t - 0.05).abs() < 1e-5);
+    }
+}
@@ -7,8 +7,10 @@ use super::mat::Material;
 use super::aabb;
 use super::aabb::AABB;
 use super::onb::ONB;
+use super::sampler::Sampler;
+use super::displace::{Tessellate, Tessellation};
 
-fn get_sphere_uv(p: &Vec3) -> (f64, f64) {
+pub fn get_sphere_uv(p: &Vec3) -> (f64, f64) {
     // p: a given point on the sphere of radius one, centered at the origin.
     // u: returned value [0,1] of angle around the Y axis from X=-1.
     // v: returned value [0,1] of angle from Y=-1 to Y=+1.
@@ -24,8 +26,7 @@ fn get_sphere_uv(p: &Vec3) -> (f64, f64) {
     (u, v)
 }
 
-fn random_to_sphere(radius: f64, distance_squared: f64) -> Vec3 {
-    let mut rng = rand::thread_rng();
+fn random_to_sphere(radius: f64, distance_squared: f64, rng: &mut Sampler) -> Vec3 {
     let r1 = rng.gen::<f64>();
     let r2 = rng.gen::<f64>();
     let z = 1.0 + r2 * ((1.0 - radius.powi(2) / distance_squared).sqrt() - 1.0);
@@ -53,7 +54,7 @@ impl<M:Material> Sphere<M>{
 }
 
 impl<M:Material> Hittable for Sphere<M> {
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, _rng: &mut Sampler) -> Option<HitRecord> {
         let oc = r.origin() - self.center;
         let a = r.direction().length().powi(2);
         let half_b = oc.dot(r.direction());
@@ -80,6 +81,7 @@ impl<M:Material> Hittable for Sphere<M> {
             t: root,
             u:0.0,
             v:0.0,
+            tangent: Vec3::new(0.0, 0.0, 0.0),
             front_face: false,
             material: &self.material
         };
@@ -90,6 +92,8 @@ impl<M:Material> Hittable for Sphere<M> {
         let(u,v) = get_sphere_uv(&outward_normal);
         rec.u = u;
         rec.v = v;
+        // u goes around the y axis
+        rec.tangent = Vec3::new(outward_normal.z(), 0.0, -outward_normal.x());
 
         Some(rec)
     }
@@ -101,8 +105,8 @@ impl<M:Material> Hittable for Sphere<M> {
         Some(AABB{min, max})
     }
 
-    fn pdf_value(&self, o: Point3, v: Vec3) -> f64 {
-        if let Some(_hit) = self.hit(&Ray::new(o, v, 0.0), 0.001, f64::MAX) {
+    fn pdf_value(&self, o: Point3, v: Vec3, rng: &mut Sampler) -> f64 {
+        if let Some(_hit) = self.hit(&Ray::new(o, v, 0.0), 0.001, f64::MAX, rng) {
             let cos_theta_max = (1.0 - self.radius.powi(2) / (self.center - o).length().powi(2)).sqrt();
             let solid_angle = 2.0 * f64::consts::PI * (1.0 - cos_theta_max);
             1.0 / solid_angle
@@ -111,14 +115,21 @@ impl<M:Material> Hittable for Sphere<M> {
         }
     }
 
-    fn random(&self, o: Vec3) -> Vec3 {
+    fn random(&self, o: Vec3, rng: &mut Sampler) -> Vec3 {
         let direction = self.center - o;
         let distance_squared = direction.length().powi(2);
         let uvw = ONB::build_from_w(&direction);
-        uvw.local(&random_to_sphere(self.radius, distance_squared))
+        uvw.local(&random_to_sphere(self.radius, distance_squared, rng))
     }
 }
 
+impl<M:Material> Tessellate for Sphere<M> {
+    fn tessellate(&self, resolution: usize) -> Tessellation {
+        Tessellation::sphere(self.center, self.radius, resolution)
+    }
+}
+
+/// sphere moving linearly from center0 at time0 to center1 at time1
 #[derive(Clone)]
 pub struct MovingSphere<M:Material>{
     center0:Point3,
@@ -147,7 +158,7 @@ impl<M:Material> MovingSphere<M>{
 }
 
 impl<M: Material> Hittable for MovingSphere<M> {
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, _rng: &mut Sampler) -> Option<HitRecord> {
         let oc = r.origin() - self.center(r.time());
         let a = r.direction().length().powi(2);
         let half_b = oc.dot(r.direction());
@@ -174,6 +185,7 @@ impl<M: Material> Hittable for MovingSphere<M> {
             t: root,
             u: 0.0,
             v: 0.0,
+            tangent: Vec3::new(0.0, 0.0, 0.0),
             front_face: false,
             material: &self.material
         };
@@ -184,15 +196,18 @@ impl<M: Material> Hittable for MovingSphere<M> {
         let (u,v) = get_sphere_uv(&outward_normal);
         rec.u = u;
         rec.v = v;
+        // u goes around the y axis
+        rec.tangent = Vec3::new(outward_normal.z(), 0.0, -outward_normal.x());
 
         Some(rec)
     }
 
-    fn bounding_box(&self, _t0: f64, _t1: f64) -> Option<AABB> {
-        let min0 = self.center0 - Vec3::new(self.radius, self.radius, self.radius);
-        let max0 = self.center0 + Vec3::new(self.radius, self.radius, self.radius);
-        let min1 = self.center1 - Vec3::new(self.radius, self.radius, self.radius);
-        let max1 = self.center1 + Vec3::new(self.radius, self.radius, self.radius);
+    fn bounding_box(&self, t0: f64, t1: f64) -> Option<AABB> {
+        // the motion is linear, so the spheres at both ends of the interval bound the whole path
+        let min0 = self.center(t0) - Vec3::new(self.radius, self.radius, self.radius);
+        let max0 = self.center(t0) + Vec3::new(self.radius, self.radius, self.radius);
+        let min1 = self.center(t1) - Vec3::new(self.radius, self.radius, self.radius);
+        let max1 = self.center(t1) + Vec3::new(self.radius, self.radius, self.radius);
 
         let box0 = AABB::new(min0, max0);
         let box1 = AABB::new(min1, max1);
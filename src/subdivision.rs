@@ -0,0 +1,521 @@
+use std::collections::HashMap;
+use std::f64;
+use super::vec::{Vec3, Point3};
+use super::mat::Material;
+use super::triangle::TriangleMesh;
+use super::bvh::SplitMethod;
+use super::displace::{Tessellate, Tessellation};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Scheme {
+    /// for triangle meshes, every triangle becomes four
+    Loop,
+    /// for any polygons, every face of n sides becomes n quads
+    CatmullClark
+}
+
+// a new vertex as weights of the vertices of the level before
+type Stencil = Vec<(usize, f64)>;
+
+fn add_to(stencil: &mut Stencil, vertex: usize, weight: f64) {
+    match stencil.iter_mut().find(|(v, _)| *v == vertex) {
+        Some((_, w)) => *w += weight,
+        None => stencil.push((vertex, weight))
+    }
+}
+
+// the weighted sum of stencils
+fn mix(parts: &[(f64, &Stencil)]) -> Stencil {
+    let mut stencil = Stencil::new();
+    for &(weight, part) in parts {
+        for &(vertex, w) in part {
+            add_to(&mut stencil, vertex, weight * w);
+        }
+    }
+    stencil
+}
+
+fn edge_key(a: usize, b: usize) -> (usize, usize) {
+    if a < b { (a, b) } else { (b, a) }
+}
+
+// the connectivity of one level of the mesh
+struct Topology {
+    // in the order they are first met going around the faces
+    edges: Vec<(usize, usize)>,
+    edge_index: HashMap<(usize, usize), usize>,
+    edge_faces: Vec<Vec<usize>>,
+    // the other end of every edge at a vertex
+    neighbours: Vec<Vec<usize>>,
+    vertex_faces: Vec<Vec<usize>>
+}
+
+impl Topology {
+    fn new(vertices: usize, faces: &[Vec<usize>]) -> Topology {
+        let mut topology = Topology {
+            edges: Vec::new(),
+            edge_index: HashMap::new(),
+            edge_faces: Vec::new(),
+            neighbours: vec![Vec::new(); vertices],
+            vertex_faces: vec![Vec::new(); vertices]
+        };
+        for (f, face) in faces.iter().enumerate() {
+            for (i, &a) in face.iter().enumerate() {
+                let b = face[(i + 1) % face.len()];
+                topology.vertex_faces[a].push(f);
+                let key = edge_key(a, b);
+                let index = match topology.edge_index.get(&key) {
+                    Some(&index) => index,
+                    None => {
+                        topology.edges.push(key);
+                        topology.edge_faces.push(Vec::new());
+                        topology.neighbours[a].push(b);
+                        topology.neighbours[b].push(a);
+                        topology.edge_index.insert(key, topology.edges.len() - 1);
+                        topology.edges.len() - 1
+                    }
+                };
+                topology.edge_faces[index].push(f);
+            }
+        }
+        topology
+    }
+}
+
+/// a polygon mesh that is smoothed by repeated subdivision
+///
+/// edges on the boundary and edges marked as creases stay sharp, a crease with a finite
+/// sharpness is only sharp for that many levels and then smoothed like the rest
+#[derive(Clone)]
+pub struct ControlMesh {
+    positions: Vec<Point3>,
+    faces: Vec<Vec<usize>>,
+    // one per vertex, smoothed along with the positions
+    uvs: Option<Vec<(f64, f64)>>,
+    creases: HashMap<(usize, usize), f64>
+}
+
+impl ControlMesh {
+    /// faces list their vertices counterclockwise seen from the outside
+    pub fn new(positions: Vec<Point3>, faces: Vec<Vec<usize>>) -> ControlMesh {
+        for face in &faces {
+            assert!(face.len() >= 3, "a face needs at least 3 vertices");
+            assert!(face.iter().all(|&v| v < positions.len()), "face index out of range");
+        }
+        ControlMesh {
+            positions,
+            faces,
+            uvs: None,
+            creases: HashMap::new()
+        }
+    }
+
+    pub fn with_uvs(mut self, uvs: Vec<(f64, f64)>) -> ControlMesh {
+        assert_eq!(uvs.len(), self.positions.len(), "the control mesh needs one uv per vertex");
+        self.uvs = Some(uvs);
+        self
+    }
+
+    /// marks the edge between the vertices a and b as sharp for `sharpness` levels,
+    /// f64::INFINITY keeps it sharp at every level
+    pub fn with_crease(mut self, a: usize, b: usize, sharpness: f64) -> ControlMesh {
+        assert!(sharpness > 0.0, "the sharpness of a crease must be positive");
+        let is_edge = self.faces.iter().any(|face| {
+            (0..face.len()).any(|i| edge_key(face[i], face[(i + 1) % face.len()]) == edge_key(a, b))
+        });
+        assert!(is_edge, "a crease must be an edge of the mesh");
+        self.creases.insert(edge_key(a, b), sharpness);
+        self
+    }
+
+    pub fn positions(&self) -> &[Point3] {
+        &self.positions
+    }
+
+    pub fn faces(&self) -> &[Vec<usize>] {
+        &self.faces
+    }
+
+    /// the mesh after `levels` steps of the scheme, loop subdivision needs a triangle mesh
+    pub fn subdivide(&self, scheme: Scheme, levels: usize) -> ControlMesh {
+        if scheme == Scheme::Loop {
+            assert!(self.faces.iter().all(|face| face.len() == 3), "loop subdivision needs a triangle mesh");
+        }
+        let mut mesh = self.clone();
+        for _ in 0..levels {
+            mesh = match scheme {
+                Scheme::Loop => mesh.loop_step(),
+                Scheme::CatmullClark => mesh.catmull_clark_step()
+            };
+        }
+        mesh
+    }
+
+    // how sharp an edge is, boundaries and edges of more than two faces are always sharp
+    fn sharpness(&self, topology: &Topology, edge: usize) -> f64 {
+        if topology.edge_faces[edge].len() != 2 {
+            return f64::INFINITY
+        }
+        self.creases.get(&topology.edges[edge]).copied().unwrap_or(0.0)
+    }
+
+    // the stencil of an edge point from its smooth and sharp rules, blended for creases that
+    // fade out within this level
+    fn edge_stencil(sharpness: f64, smooth: impl FnOnce() -> Stencil, (a, b): (usize, usize)) -> Stencil {
+        let sharp = vec![(a, 0.5), (b, 0.5)];
+        if sharpness >= 1.0 {
+            sharp
+        } else if sharpness > 0.0 {
+            mix(&[(1.0 - sharpness, &smooth()), (sharpness, &sharp)])
+        } else {
+            smooth()
+        }
+    }
+
+    // the stencil of a vertex point: with fewer than two sharp edges the smooth rule, with two
+    // the rule of a curve along them and with more the vertex stays where it is
+    fn vertex_stencil(&self, topology: &Topology, v: usize, smooth: impl FnOnce() -> Stencil) -> Stencil {
+        let sharp_edges: Vec<(usize, f64)> = topology.neighbours[v].iter().filter_map(|&n| {
+            let s = self.sharpness(topology, topology.edge_index[&edge_key(v, n)]);
+            if s > 0.0 { Some((n, s)) } else { None }
+        }).collect();
+        // a corner of an open mesh keeps its place too
+        let corner = topology.vertex_faces[v].len() == 1 && topology.neighbours[v].len() == 2;
+        if topology.vertex_faces[v].is_empty() {
+            return vec![(v, 1.0)]
+        }
+        if sharp_edges.len() < 2 && !corner {
+            return smooth()
+        }
+
+        let sharp = if sharp_edges.len() == 2 && !corner {
+            vec![(v, 0.75), (sharp_edges[0].0, 0.125), (sharp_edges[1].0, 0.125)]
+        } else {
+            vec![(v, 1.0)]
+        };
+        let sharpness = sharp_edges.iter().map(|&(_, s)| s).sum::<f64>() / sharp_edges.len() as f64;
+        if sharpness >= 1.0 || corner {
+            sharp
+        } else {
+            mix(&[(1.0 - sharpness, &smooth()), (sharpness, &sharp)])
+        }
+    }
+
+    // the creases one level down, both halves of an edge are a level less sharp
+    fn child_creases(&self, topology: &Topology, edge_vertex: impl Fn(usize) -> usize) -> HashMap<(usize, usize), f64> {
+        let mut creases = HashMap::new();
+        for (&(a, b), &sharpness) in &self.creases {
+            if sharpness > 1.0 {
+                let e = edge_vertex(topology.edge_index[&(a, b)]);
+                creases.insert(edge_key(a, e), sharpness - 1.0);
+                creases.insert(edge_key(e, b), sharpness - 1.0);
+            }
+        }
+        creases
+    }
+
+    // the next level from the stencils of its vertices
+    fn apply(&self, stencils: &[Stencil], faces: Vec<Vec<usize>>, creases: HashMap<(usize, usize), f64>) -> ControlMesh {
+        let positions = stencils.iter().map(|stencil| {
+            stencil.iter().fold(Vec3::new(0.0, 0.0, 0.0), |sum, &(v, w)| sum + w * self.positions[v])
+        }).collect();
+        let uvs = self.uvs.as_ref().map(|uvs| stencils.iter().map(|stencil| {
+            stencil.iter().fold((0.0, 0.0), |(u, v), &(i, w)| (u + w * uvs[i].0, v + w * uvs[i].1))
+        }).collect());
+        ControlMesh { positions, faces, uvs, creases }
+    }
+
+    fn catmull_clark_step(&self) -> ControlMesh {
+        let topology = Topology::new(self.positions.len(), &self.faces);
+        let (vertices, edges) = (self.positions.len(), topology.edges.len());
+        let edge_vertex = |e: usize| vertices + e;
+        let face_vertex = |f: usize| vertices + edges + f;
+
+        // new vertices in the order: moved old vertices, edge points, face points
+        let face_points: Vec<Stencil> = self.faces.iter().map(|face| {
+            face.iter().map(|&v| (v, 1.0 / face.len() as f64)).collect()
+        }).collect();
+        let edge_points: Vec<Stencil> = (0..edges).map(|e| {
+            let (a, b) = topology.edges[e];
+            Self::edge_stencil(self.sharpness(&topology, e), || {
+                let fs = &topology.edge_faces[e];
+                let midpoint = vec![(a, 0.5), (b, 0.5)];
+                mix(&[(0.5, &midpoint), (0.25, &face_points[fs[0]]), (0.25, &face_points[fs[1]])])
+            }, (a, b))
+        }).collect();
+        let vertex_points: Vec<Stencil> = (0..vertices).map(|v| {
+            self.vertex_stencil(&topology, v, || {
+                // (q + 2r + (n - 3)s) / n with q the average of the face points, r the average of
+                // the edge midpoints and s the vertex
+                let n = topology.neighbours[v].len() as f64;
+                let faces = &topology.vertex_faces[v];
+                let mut stencil = Stencil::new();
+                for &f in faces {
+                    for &(u, w) in &face_points[f] {
+                        add_to(&mut stencil, u, w / (faces.len() as f64 * n));
+                    }
+                }
+                for &u in &topology.neighbours[v] {
+                    add_to(&mut stencil, u, 1.0 / (n * n));
+                    add_to(&mut stencil, v, 1.0 / (n * n));
+                }
+                add_to(&mut stencil, v, (n - 3.0) / n);
+                stencil
+            })
+        }).collect();
+
+        let mut faces = Vec::new();
+        for (f, face) in self.faces.iter().enumerate() {
+            for i in 0..face.len() {
+                let previous = face[(i + face.len() - 1) % face.len()];
+                let next = face[(i + 1) % face.len()];
+                faces.push(vec![
+                    face[i],
+                    edge_vertex(topology.edge_index[&edge_key(face[i], next)]),
+                    face_vertex(f),
+                    edge_vertex(topology.edge_index[&edge_key(previous, face[i])])
+                ]);
+            }
+        }
+
+        let stencils: Vec<Stencil> = vertex_points.into_iter().chain(edge_points).chain(face_points).collect();
+        self.apply(&stencils, faces, self.child_creases(&topology, edge_vertex))
+    }
+
+    fn loop_step(&self) -> ControlMesh {
+        let topology = Topology::new(self.positions.len(), &self.faces);
+        let vertices = self.positions.len();
+        let edge_vertex = |e: usize| vertices + e;
+
+        let edge_points: Vec<Stencil> = (0..topology.edges.len()).map(|e| {
+            let (a, b) = topology.edges[e];
+            Self::edge_stencil(self.sharpness(&topology, e), || {
+                // the vertices facing the edge in its two triangles
+                let mut stencil = vec![(a, 0.375), (b, 0.375)];
+                for &f in &topology.edge_faces[e] {
+                    let opposite = self.faces[f].iter().copied().find(|&v| v != a && v != b).unwrap();
+                    add_to(&mut stencil, opposite, 0.125);
+                }
+                stencil
+            }, (a, b))
+        }).collect();
+        let vertex_points: Vec<Stencil> = (0..vertices).map(|v| {
+            self.vertex_stencil(&topology, v, || {
+                // warren's weights
+                let n = topology.neighbours[v].len();
+                let beta = if n == 3 { 3.0 / 16.0 } else { 3.0 / (8.0 * n as f64) };
+                let mut stencil = vec![(v, 1.0 - n as f64 * beta)];
+                for &u in &topology.neighbours[v] {
+                    add_to(&mut stencil, u, beta);
+                }
+                stencil
+            })
+        }).collect();
+
+        let mut faces = Vec::new();
+        for face in &self.faces {
+            let [a, b, c] = [face[0], face[1], face[2]];
+            let ab = edge_vertex(topology.edge_index[&edge_key(a, b)]);
+            let bc = edge_vertex(topology.edge_index[&edge_key(b, c)]);
+            let ca = edge_vertex(topology.edge_index[&edge_key(c, a)]);
+            faces.extend([vec![a, ab, ca], vec![b, bc, ab], vec![c, ca, bc], vec![ab, bc, ca]]);
+        }
+
+        let stencils: Vec<Stencil> = vertex_points.into_iter().chain(edge_points).collect();
+        self.apply(&stencils, faces, self.child_creases(&topology, edge_vertex))
+    }
+
+    /// triangles of the faces, shaded with the averaged normals of the faces around each vertex
+    /// except across the creases that are still sharp, which keep a hard edge
+    pub fn to_mesh<M: Material + 'static>(&self, material: M, split: SplitMethod) -> TriangleMesh {
+        let topology = Topology::new(self.positions.len(), &self.faces);
+
+        // area weighted normals by newell's method
+        let face_normals: Vec<Vec3> = self.faces.iter().map(|face| {
+            (0..face.len()).fold(Vec3::new(0.0, 0.0, 0.0), |sum, i| {
+                sum + self.positions[face[i]].cross(self.positions[face[(i + 1) % face.len()]])
+            })
+        }).collect();
+
+        // the corners of the faces around a vertex share a normal unless a sharp edge divides them
+        let corners: Vec<(usize, usize)> = self.faces.iter().enumerate()
+            .flat_map(|(f, face)| face.iter().map(move |&v| (f, v)))
+            .collect();
+        let corner_index: HashMap<(usize, usize), usize> = corners.iter().enumerate().map(|(i, &c)| (c, i)).collect();
+        let mut parent: Vec<usize> = (0..corners.len()).collect();
+        fn root(parent: &mut [usize], mut i: usize) -> usize {
+            while parent[i] != i {
+                parent[i] = parent[parent[i]];
+                i = parent[i];
+            }
+            i
+        }
+        for e in 0..topology.edges.len() {
+            if self.sharpness(&topology, e) > 0.0 {
+                continue
+            }
+            let (a, b) = topology.edges[e];
+            let (f, g) = (topology.edge_faces[e][0], topology.edge_faces[e][1]);
+            for v in [a, b] {
+                let (x, y) = (root(&mut parent, corner_index[&(f, v)]), root(&mut parent, corner_index[&(g, v)]));
+                parent[x] = y;
+            }
+        }
+
+        let mut group_normals: HashMap<usize, Vec3> = HashMap::new();
+        for (i, &(f, _)) in corners.iter().enumerate() {
+            let group = root(&mut parent, i);
+            *group_normals.entry(group).or_insert(Vec3::new(0.0, 0.0, 0.0)) += face_normals[f];
+        }
+
+        // one mesh vertex for every vertex and normal group
+        let mut positions = Vec::new();
+        let mut normals = Vec::new();
+        let mut uvs = Vec::new();
+        let mut vertex_of_group: HashMap<usize, usize> = HashMap::new();
+        let mut indices = Vec::new();
+        for (f, face) in self.faces.iter().enumerate() {
+            let vertices: Vec<usize> = face.iter().map(|&v| {
+                let group = root(&mut parent, corner_index[&(f, v)]);
+                *vertex_of_group.entry(group).or_insert_with(|| {
+                    positions.push(self.positions[v]);
+                    normals.push(group_normals[&group].normalized());
+                    if let Some(mesh_uvs) = &self.uvs {
+                        uvs.push(mesh_uvs[v]);
+                    }
+                    positions.len() - 1
+                })
+            }).collect();
+            for i in 1..vertices.len() - 1 {
+                indices.push([vertices[0], vertices[i], vertices[i + 1]]);
+            }
+        }
+
+        let uvs = if self.uvs.is_some() { Some(uvs) } else { None };
+        TriangleMesh::with_split(positions, indices, Some(normals), uvs, material, split)
+    }
+}
+
+impl Tessellate for ControlMesh {
+    /// the faces cut into fans of triangles that share every vertex and are refined with
+    /// `resolution` segments per edge, subdivide first for a smooth surface
+    fn tessellate(&self, resolution: usize) -> Tessellation {
+        let indices = self.faces.iter()
+            .flat_map(|face| (1..face.len() - 1).map(move |i| [face[0], face[i], face[i + 1]]))
+            .collect();
+        Tessellation::new(self.positions.clone(), indices, None, self.uvs.clone()).refined(resolution)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the cube [-1, 1]^3 with its faces counterclockwise seen from outside
+    fn cube() -> ControlMesh {
+        let positions = (0..8).map(|i| Point3::new(
+            if i & 1 == 0 { -1.0 } else { 1.0 },
+            if i & 2 == 0 { -1.0 } else { 1.0 },
+            if i & 4 == 0 { -1.0 } else { 1.0 })).collect();
+        let faces = vec![vec![0, 2, 3, 1], vec![4, 5, 7, 6], vec![0, 1, 5, 4], vec![2, 6, 7, 3], vec![0, 4, 6, 2], vec![1, 3, 7, 5]];
+        ControlMesh::new(positions, faces)
+    }
+
+    fn tetrahedron() -> ControlMesh {
+        let positions = vec![Point3::new(1.0, 1.0, 1.0), Point3::new(1.0, -1.0, -1.0), Point3::new(-1.0, 1.0, -1.0), Point3::new(-1.0, -1.0, 1.0)];
+        ControlMesh::new(positions, vec![vec![0, 1, 2], vec![0, 3, 1], vec![0, 2, 3], vec![1, 3, 2]])
+    }
+
+    fn close(a: Point3, b: Point3) -> bool {
+        (a - b).length() < 1e-12
+    }
+
+    #[test]
+    fn catmull_clark_smooth_rules() {
+        let mesh = cube().subdivide(Scheme::CatmullClark, 1);
+        assert_eq!((mesh.positions().len(), mesh.faces().len()), (8 + 12 + 6, 24));
+        assert!(mesh.faces().iter().all(|face| face.len() == 4));
+        // a corner of valence 3: (q + 2r) / 3 with q = 1/3 and r = 2/3 on every axis
+        assert!(close(mesh.positions()[7], Point3::new(5.0 / 9.0, 5.0 / 9.0, 5.0 / 9.0)));
+        // an edge point averages the ends and the two face points
+        assert!(mesh.positions()[8..20].iter().all(|p| (p.length() - (0.75f64 * 0.75 * 2.0).sqrt()).abs() < 1e-12));
+        // face points are the face centers
+        assert!(mesh.positions()[20..].iter().all(|p| (p.length() - 1.0).abs() < 1e-12));
+    }
+
+    #[test]
+    fn loop_smooth_rules() {
+        let mesh = tetrahedron().subdivide(Scheme::Loop, 1);
+        assert_eq!((mesh.positions().len(), mesh.faces().len()), (4 + 6, 16));
+        // valence 3 uses beta = 3 / 16, the neighbours sum to -v on a regular tetrahedron
+        let v = Point3::new(1.0, 1.0, 1.0);
+        assert!(close(mesh.positions()[0], (1.0 - 9.0 / 16.0) * v - 3.0 / 16.0 * v));
+        // an edge point is 3/8 of its ends and 1/8 of the two opposite vertices, which cancel here
+        let ends = [tetrahedron().positions()[0], tetrahedron().positions()[1]];
+        assert!(close(mesh.positions()[4], 0.375 * (ends[0] + ends[1]) - 0.125 * (ends[0] + ends[1])));
+    }
+
+    #[test]
+    fn crease_loop_stays_in_its_plane() {
+        // the four edges around the top face are sharp at every level
+        let mut mesh = cube();
+        for (a, b) in [(2, 3), (3, 7), (7, 6), (6, 2)] {
+            mesh = mesh.with_crease(a, b, f64::INFINITY);
+        }
+        let once = mesh.subdivide(Scheme::CatmullClark, 1);
+        // crease vertices use 3/4 of themselves and 1/8 of the two neighbours along the crease
+        assert!(close(once.positions()[7], Point3::new(0.75, 1.0, 0.75)));
+        // crease edge points are midpoints
+        let creased: Vec<Point3> = once.creases.keys().flat_map(|&(a, b)| [once.positions()[a], once.positions()[b]]).collect();
+        assert_eq!(once.creases.len(), 8);
+        assert!(creased.iter().any(|&p| close(p, Point3::new(0.0, 1.0, 1.0))));
+
+        let refined = mesh.subdivide(Scheme::CatmullClark, 3);
+        assert_eq!(refined.creases.len(), 32);
+        for &(a, b) in refined.creases.keys() {
+            assert!((refined.positions()[a].y() - 1.0).abs() < 1e-12 && (refined.positions()[b].y() - 1.0).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn three_sharp_edges_make_a_corner() {
+        let mesh = cube().with_crease(7, 3, f64::INFINITY).with_crease(7, 5, f64::INFINITY).with_crease(7, 6, f64::INFINITY);
+        for levels in 1..4 {
+            assert!(close(mesh.subdivide(Scheme::CatmullClark, levels).positions()[7], Point3::new(1.0, 1.0, 1.0)));
+        }
+    }
+
+    #[test]
+    fn finite_sharpness_fades() {
+        let sharp = cube().with_crease(7, 3, f64::INFINITY);
+        let smooth = cube();
+        // half sharp is halfway between the smooth edge point and the midpoint
+        let half = cube().with_crease(7, 3, 0.5).subdivide(Scheme::CatmullClark, 1);
+        let edge = |mesh: &ControlMesh| {
+            let topology = Topology::new(8, &cube().faces);
+            mesh.positions()[8 + topology.edge_index[&(3, 7)]]
+        };
+        let expected = 0.5 * edge(&sharp.subdivide(Scheme::CatmullClark, 1)) + 0.5 * edge(&smooth.subdivide(Scheme::CatmullClark, 1));
+        assert!(close(edge(&half), expected));
+
+        // a sharpness of 2 lasts for two levels, each level down is one less
+        let two = cube().with_crease(7, 3, 2.0);
+        assert!(two.subdivide(Scheme::CatmullClark, 1).creases.values().all(|&s| s == 1.0));
+        assert!(two.subdivide(Scheme::CatmullClark, 2).creases.is_empty());
+    }
+
+    #[test]
+    fn open_boundaries_stay_sharp() {
+        // one triangle: the corners keep their place and the edge points are midpoints
+        let triangle = ControlMesh::new(vec![Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 0.0, 0.0), Point3::new(0.0, 1.0, 0.0)], vec![vec![0, 1, 2]]);
+        let mesh = triangle.subdivide(Scheme::Loop, 2);
+        assert!(close(mesh.positions()[0], Point3::new(0.0, 0.0, 0.0)) && close(mesh.positions()[1], Point3::new(1.0, 0.0, 0.0)));
+        assert!(mesh.positions().iter().all(|p| p.z() == 0.0 && p.x() >= 0.0 && p.y() >= 0.0 && p.x() + p.y() <= 1.0 + 1e-12));
+
+        // the top of the cube alone: its boundary is a crease loop and its corners stay put
+        let quad = ControlMesh::new(cube().positions().to_vec(), vec![vec![2, 6, 7, 3]]);
+        let mesh = quad.subdivide(Scheme::CatmullClark, 2);
+        assert!(close(mesh.positions()[7], Point3::new(1.0, 1.0, 1.0)));
+        assert!(mesh.positions().iter().all(|p| (p.y() - 1.0).abs() < 1e-12 || p.y() == -1.0));
+    }
+}
@@ -0,0 +1,34 @@
+// fixtures shared by the unit tests
+
+use std::fs;
+use std::path::PathBuf;
+use super::hit::{Hittable, HitRecord};
+use super::mat::Lambertian;
+use super::ray::Ray;
+use super::sampler::Sampler;
+use super::texture::ConstantTexture;
+use super::vec::{Vec3, Point3, Color};
+
+pub type Grey = Lambertian<ConstantTexture>;
+
+/// a diffuse material for shapes whose shading does not matter
+pub fn grey() -> Grey {
+    Lambertian::new(ConstantTexture::new(Color::new(0.5, 0.5, 0.5)))
+}
+
+/// the nearest hit of a ray from `origin` at time 0, skipping the surface it starts on
+pub fn hit<'a>(h: &'a dyn Hittable, origin: Point3, direction: Vec3) -> Option<HitRecord<'a>> {
+    hit_after(h, origin, direction, 0.001)
+}
+
+pub fn hit_after<'a>(h: &'a dyn Hittable, origin: Point3, direction: Vec3, t_min: f64) -> Option<HitRecord<'a>> {
+    h.hit(&Ray::new(origin, direction, 0.0), t_min, f64::INFINITY, &mut Sampler::new(0))
+}
+
+/// an empty directory of its own for a test that reads or writes files
+pub fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("beauty-{}-{}", std::process::id(), name));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
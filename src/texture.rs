@@ -1,11 +1,22 @@
 use std::f64;
+use std::sync::Arc;
+use rand::Rng;
 use super::vec::{Color, Vec3};
 use super::perlin::Perlin;
 
-pub trait Texture:Sync{
+/// a color varying over a surface, looked up by texture coordinates and position
+pub trait Texture: Send + Sync {
     fn mapping(&self, u:f64, v:f64, p:&Vec3) -> Color;
 }
 
+// textures shared between several materials
+impl<T: Texture + ?Sized> Texture for Arc<T> {
+    fn mapping(&self, u: f64, v: f64, p: &Vec3) -> Color {
+        (**self).mapping(u, v, p)
+    }
+}
+
+/// the same color everywhere
 #[derive(Clone)]
 pub struct ConstantTexture{
     value:Color
@@ -25,6 +36,7 @@ impl Texture for ConstantTexture{
     }
 }
 
+/// 3d checker pattern alternating two textures
 #[derive(Clone)]
 pub struct CheckTexture<T:Texture, U:Texture>{
     odd:T,
@@ -51,6 +63,7 @@ impl<T: Texture, U: Texture> Texture for CheckTexture<T, U> {
     }
 }
 
+/// marble-like turbulence
 #[derive(Clone)]
 pub struct NoiseTexture{
     noise:Perlin,
@@ -58,9 +71,9 @@ pub struct NoiseTexture{
 }
 
 impl NoiseTexture {
-    pub fn new(scale:f64) -> NoiseTexture{
+    pub fn new(scale:f64, rng: &mut impl Rng) -> NoiseTexture{
         NoiseTexture { 
-            noise: Perlin::new(), 
+            noise: Perlin::new(rng), 
             scale 
         }
     }
@@ -72,6 +85,7 @@ impl Texture for NoiseTexture {
     }
 }
 
+/// rgb8 image mapped by the uv coordinates
 #[derive(Clone)]
 pub struct ImageTexture {
     data: Vec<u8>,
@@ -0,0 +1,201 @@
+use super::vec::Color;
+
+// the post process between the linear framebuffer and the 8/16 bit encoders:
+// white balance, exposure, a tone curve squeezing the radiance into [0, 1], then the transfer function
+
+/// curve mapping scene radiance to display values
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ToneCurve {
+    /// cut everything above 1
+    Clamp,
+    /// x / (1 + x) on every channel
+    Reinhard,
+    /// John Hable's Uncharted 2 curve
+    Filmic,
+    /// Krzysztof Narkowicz's fit of the ACES reference rendering transform
+    Aces
+}
+
+impl ToneCurve {
+    pub const NAMES: [&'static str; 4] = ["clamp", "reinhard", "filmic", "aces"];
+
+    pub fn from_name(name: &str) -> Option<ToneCurve> {
+        match name.to_ascii_lowercase().as_str() {
+            "clamp" | "none" => Some(ToneCurve::Clamp),
+            "reinhard" => Some(ToneCurve::Reinhard),
+            "filmic" => Some(ToneCurve::Filmic),
+            "aces" => Some(ToneCurve::Aces),
+            _ => None
+        }
+    }
+
+    pub fn apply(self, x: f64) -> f64 {
+        match self {
+            ToneCurve::Clamp => x.min(1.0),
+            ToneCurve::Reinhard => x / (1.0 + x),
+            ToneCurve::Filmic => {
+                fn hable(x: f64) -> f64 {
+                    const A: f64 = 0.15;
+                    const B: f64 = 0.50;
+                    const C: f64 = 0.10;
+                    const D: f64 = 0.20;
+                    const E: f64 = 0.02;
+                    const F: f64 = 0.30;
+                    (x * (A * x + C * B) + D * E) / (x * (A * x + B) + D * F) - E / F
+                }
+                // 11.2 is the linear white point, 2 the exposure bias of the original
+                const WHITE: f64 = 11.2;
+                (hable(2.0 * x) / hable(WHITE)).min(1.0)
+            },
+            ToneCurve::Aces => {
+                let y = (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14);
+                y.clamp(0.0, 1.0)
+            }
+        }
+    }
+}
+
+/// encoding of the display values
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Transfer {
+    /// the srgb opto-electronic transfer function
+    Srgb,
+    /// square root, what the renderer used before
+    Gamma2
+}
+
+impl Transfer {
+    pub fn from_name(name: &str) -> Option<Transfer> {
+        match name.to_ascii_lowercase().as_str() {
+            "srgb" => Some(Transfer::Srgb),
+            "2" | "gamma2" => Some(Transfer::Gamma2),
+            _ => None
+        }
+    }
+
+    pub fn apply(self, x: f64) -> f64 {
+        match self {
+            Transfer::Srgb => {
+                if x <= 0.0031308 {
+                    12.92 * x
+                } else {
+                    1.055 * x.powf(1.0 / 2.4) - 0.055
+                }
+            },
+            Transfer::Gamma2 => x.sqrt()
+        }
+    }
+}
+
+/// the color pipeline used to save a framebuffer
+#[derive(Clone, Copy)]
+pub struct ToneMapper {
+    /// in stops, every stop doubles the brightness
+    pub exposure: f64,
+    pub curve: ToneCurve,
+    pub transfer: Transfer,
+    /// color of the light that should come out neutral
+    pub white_balance: Color
+}
+
+impl Default for ToneMapper {
+    fn default() -> ToneMapper {
+        ToneMapper {
+            exposure: 0.0,
+            curve: ToneCurve::Clamp,
+            transfer: Transfer::Srgb,
+            white_balance: Color::new(1.0, 1.0, 1.0)
+        }
+    }
+}
+
+impl ToneMapper {
+    /// white balance and exposure only, the result is still linear radiance
+    pub fn linear(&self, c: Color) -> Color {
+        // keep the luminance of the white point so that balancing does not change the brightness
+        let w = self.white_balance;
+        let luminance = 0.2126 * w[0] + 0.7152 * w[1] + 0.0722 * w[2];
+        let scale = 2f64.powf(self.exposure);
+        Color::new(
+            c[0] * scale * luminance / w[0],
+            c[1] * scale * luminance / w[1],
+            c[2] * scale * luminance / w[2]
+        )
+    }
+
+    /// the whole pipeline, every channel ends up in [0, 1]
+    pub fn display(&self, c: Color) -> Color {
+        let c = self.linear(c);
+        let map = |x: f64| {
+            // nan and negative radiance from bad samples become black,
+            // huge values are capped so that the curves never divide infinity by infinity
+            let x = if x > 0.0 { x.min(1.0e6) } else { 0.0 };
+            self.transfer.apply(self.curve.apply(x)).clamp(0.0, 1.0)
+        };
+        Color::new(map(c[0]), map(c[1]), map(c[2]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn curves_at_known_values() {
+        assert_eq!(ToneCurve::Clamp.apply(0.5), 0.5);
+        assert_eq!(ToneCurve::Clamp.apply(3.0), 1.0);
+        assert_eq!(ToneCurve::Reinhard.apply(1.0), 0.5);
+        assert_eq!(ToneCurve::Reinhard.apply(3.0), 0.75);
+        // the filmic curve starts at black and reaches white at half the white point of the original
+        assert!(close(ToneCurve::Filmic.apply(0.0), 0.0));
+        assert!(close(ToneCurve::Filmic.apply(5.6), 1.0));
+        // hable(2) / hable(11.2)
+        assert!(close(ToneCurve::Filmic.apply(1.0), 0.49291854599116447));
+        assert!(close(ToneCurve::Aces.apply(0.0), 0.0));
+        assert!(close(ToneCurve::Aces.apply(1.0), 2.54 / 3.16));
+        assert_eq!(ToneCurve::Aces.apply(100.0), 1.0);
+
+        // every curve rises from black to white
+        for name in ToneCurve::NAMES {
+            let curve = ToneCurve::from_name(name).unwrap();
+            let values: Vec<f64> = (0..=100).map(|i| curve.apply(0.1 * i as f64)).collect();
+            assert!(values.windows(2).all(|w| w[0] <= w[1]), "{} is not increasing", name);
+            assert!(values[0] >= 0.0 && values[100] <= 1.0);
+        }
+    }
+
+    #[test]
+    fn srgb_encoding() {
+        assert_eq!(Transfer::Srgb.apply(0.0), 0.0);
+        // the linear segment meets the power curve
+        assert!(close(Transfer::Srgb.apply(0.0031308), 12.92 * 0.0031308));
+        assert!((Transfer::Srgb.apply(0.0031308 + 1e-12) - 12.92 * 0.0031308).abs() < 1e-6);
+        assert!(close(Transfer::Srgb.apply(0.5), 0.7353569830524495));
+        assert!(close(Transfer::Srgb.apply(1.0), 1.0));
+        assert_eq!(Transfer::Gamma2.apply(0.25), 0.5);
+    }
+
+    #[test]
+    fn white_balance_keeps_the_luminance() {
+        let white_balance = Color::new(1.0, 0.5, 0.25);
+        let tone = ToneMapper { white_balance, ..ToneMapper::default() };
+        // the light of the white point comes out grey, as bright as it was
+        let luminance = 0.2126 + 0.7152 * 0.5 + 0.0722 * 0.25;
+        let c = tone.linear(white_balance);
+        assert!(close(c[0], luminance) && close(c[1], luminance) && close(c[2], luminance));
+
+        // a stop more doubles it
+        let c = ToneMapper { exposure: 1.0, ..tone }.linear(white_balance);
+        assert!(close(c[1], 2.0 * luminance));
+    }
+
+    #[test]
+    fn display_handles_bad_samples() {
+        let c = ToneMapper::default().display(Color::new(f64::NAN, -1.0, f64::INFINITY));
+        assert!(c[0] == 0.0 && c[1] == 0.0 && close(c[2], 1.0));
+    }
+}
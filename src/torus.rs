@@ -0,0 +1,183 @@
+use std::f64;
+use super::vec::{Vec3, Point3};
+use super::ray::Ray;
+use super::sampler::Sampler;
+use super::hit::{Hittable, HitRecord};
+use super::mat::Material;
+use super::aabb::AABB;
+use super::quadric::{self, solve_quadratic};
+
+// value and slope of the polynomial with the given coefficients, lowest degree first
+fn evaluate(coefficients: &[f64], t: f64) -> (f64, f64) {
+    coefficients.iter().rev().fold((0.0, 0.0), |(value, slope), &c| (value * t + c, slope * t + value))
+}
+
+// the root in [lo, hi] when the polynomial changes sign there, newton steps kept inside the bracket
+fn refine_root(coefficients: &[f64], lo: f64, hi: f64) -> Option<f64> {
+    let (f_lo, _) = evaluate(coefficients, lo);
+    let (f_hi, _) = evaluate(coefficients, hi);
+    if f_lo == 0.0 {
+        return Some(lo)
+    }
+    if f_lo.signum() == f_hi.signum() {
+        return None
+    }
+    // below is the end where the polynomial is negative
+    let (mut below, mut above) = if f_lo < 0.0 { (lo, hi) } else { (hi, lo) };
+    let mut t = 0.5 * (lo + hi);
+    for _ in 0..64 {
+        let (value, slope) = evaluate(coefficients, t);
+        if value == 0.0 {
+            return Some(t)
+        }
+        if value < 0.0 { below = t } else { above = t }
+        let newton = t - value / slope;
+        let next = if newton > below.min(above) && newton < below.max(above) { newton } else { 0.5 * (below + above) };
+        if (next - t).abs() <= 1.0e-12 * t.abs().max(1.0) {
+            return Some(next)
+        }
+        t = next;
+    }
+    Some(t)
+}
+
+// real roots in [lo, hi] in increasing order: between consecutive roots of the derivative the
+// polynomial is monotonic, so each of those intervals holds at most one root
+fn roots_between(coefficients: &[f64], lo: f64, hi: f64) -> Vec<f64> {
+    if coefficients.len() <= 3 {
+        let (c, b, a) = (coefficients[0], coefficients[1], coefficients.get(2).copied().unwrap_or(0.0));
+        return match solve_quadratic(a, b, c) {
+            Some((t0, t1)) => [t0, t1].into_iter().filter(|&t| t >= lo && t <= hi).collect(),
+            None => Vec::new()
+        }
+    }
+
+    let derivative: Vec<f64> = coefficients.iter().enumerate().skip(1).map(|(i, &c)| i as f64 * c).collect();
+    let mut points = vec![lo];
+    points.extend(roots_between(&derivative, lo, hi));
+    points.push(hi);
+
+    let mut roots: Vec<f64> = Vec::new();
+    for pair in points.windows(2) {
+        if let Some(root) = refine_root(coefficients, pair[0], pair[1]) {
+            // a root at a shared end shows up in both intervals
+            if roots.last() != Some(&root) {
+                roots.push(root);
+            }
+        }
+    }
+    roots
+}
+
+/// ring torus around the y axis, centered at the origin
+#[derive(Clone)]
+pub struct Torus<M: Material> {
+    // from the center to the middle of the tube
+    major_radius: f64,
+    // of the tube
+    minor_radius: f64,
+    material: M
+}
+
+impl<M: Material> Torus<M> {
+    pub fn new(major_radius: f64, minor_radius: f64, material: M) -> Torus<M> {
+        assert!(0.0 < minor_radius && minor_radius < major_radius, "a torus needs 0 < minor radius < major radius");
+        Torus { major_radius, minor_radius, material }
+    }
+}
+
+impl<M: Material> Hittable for Torus<M> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, _rng: &mut Sampler) -> Option<HitRecord<'_>> {
+        let d = r.direction();
+        let dd = d.dot(d);
+        // solving from the point of the ray closest to the center keeps the quartic well conditioned
+        let shift = -r.origin().dot(d) / dd;
+        let o = r.at(shift);
+        let outer = self.major_radius + self.minor_radius;
+        if o.dot(o) > outer * outer {
+            return None
+        }
+
+        // (|p|^2 + R^2 - r^2)^2 = 4 R^2 (x^2 + z^2) along p = o + t d
+        let r2 = self.major_radius * self.major_radius;
+        let od = o.dot(d);
+        let k = o.dot(o) + r2 - self.minor_radius * self.minor_radius;
+        let coefficients = [
+            k * k - 4.0 * r2 * (o.x() * o.x() + o.z() * o.z()),
+            4.0 * od * k - 8.0 * r2 * (o.x() * d.x() + o.z() * d.z()),
+            2.0 * dd * k + 4.0 * od * od - 4.0 * r2 * (d.x() * d.x() + d.z() * d.z()),
+            4.0 * dd * od,
+            dd * dd
+        ];
+        // every root lies within the torus, no further than this from o
+        let reach = 2.0 * outer / dd.sqrt();
+        let roots = roots_between(&coefficients, (t_min - shift).max(-reach), (t_max - shift).min(reach));
+        let t = shift + *roots.first()?;
+
+        let p = r.at(t);
+        let distance = (p.x() * p.x() + p.z() * p.z()).sqrt();
+        // the normal points away from the nearest point on the ring through the middle of the tube
+        let ring = Point3::new(p.x(), 0.0, p.z()) * (self.major_radius / distance);
+        let outward_normal = (p - ring) / self.minor_radius;
+        let theta = p.y().atan2(distance - self.major_radius);
+        let v = if theta < 0.0 { theta + 2.0 * f64::consts::PI } else { theta } / (2.0 * f64::consts::PI);
+        Some(quadric::record(r, t, outward_normal, quadric::phi_of(p) / (2.0 * f64::consts::PI), v, &self.material))
+    }
+
+    fn bounding_box(&self, _t0: f64, _t1: f64) -> Option<AABB> {
+        let outer = self.major_radius + self.minor_radius;
+        Some(AABB::new(Vec3::new(-outer, -self.minor_radius, -outer), Vec3::new(outer, self.minor_radius, outer)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::{grey, hit_after, Grey};
+    use super::*;
+
+    fn torus() -> Torus<Grey> {
+        Torus::new(2.0, 1.0, grey())
+    }
+
+    fn first_hit(origin: Point3, direction: Vec3, t_min: f64) -> Option<f64> {
+        hit_after(&torus(), origin, direction, t_min).map(|rec| rec.t)
+    }
+
+    #[test]
+    fn roots_of_a_quartic() {
+        // (t - 1) (t - 2) (t - 3) (t - 4)
+        let roots = roots_between(&[24.0, -50.0, 35.0, -10.0, 1.0], -10.0, 10.0);
+        assert_eq!(roots.len(), 4);
+        for (root, expected) in roots.iter().zip([1.0, 2.0, 3.0, 4.0]) {
+            assert!((root - expected).abs() < 1e-10);
+        }
+    }
+
+    #[test]
+    fn hits_along_the_axis_through_the_tube() {
+        // the tube crosses the x axis at 1 and 3 on both sides
+        let (origin, direction) = (Point3::new(-5.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        assert!((first_hit(origin, direction, 0.001).unwrap() - 2.0).abs() < 1e-9);
+        assert!((first_hit(origin, direction, 2.5).unwrap() - 4.0).abs() < 1e-9);
+        assert!((first_hit(origin, direction, 4.5).unwrap() - 6.0).abs() < 1e-9);
+        assert!((first_hit(origin, direction, 6.5).unwrap() - 8.0).abs() < 1e-9);
+        assert!(first_hit(origin, direction, 8.5).is_none());
+    }
+
+    #[test]
+    fn hole_and_far_origin() {
+        let down = Vec3::new(0.0, -1.0, 0.0);
+        assert!(first_hit(Point3::new(0.0, 5.0, 0.0), down, 0.001).is_none());
+        assert!((first_hit(Point3::new(2.0, 5.0, 0.0), down, 0.001).unwrap() - 4.0).abs() < 1e-9);
+        // far away origins must not lose the hit to cancellation
+        assert!((first_hit(Point3::new(-1000.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0), 0.001).unwrap() - 997.0).abs() < 1e-8);
+    }
+
+    #[test]
+    fn normal_points_out_of_the_tube() {
+        let torus = torus();
+        let rec = hit_after(&torus, Point3::new(2.0, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0), 0.001).unwrap();
+        assert!((rec.normal - Vec3::new(0.0, 1.0, 0.0)).length() < 1e-9);
+        assert!(rec.front_face);
+    }
+}
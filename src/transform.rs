@@ -0,0 +1,175 @@
+use super::vec::{Vec3, Point3};
+use super::ray::Ray;
+use super::sampler::Sampler;
+use super::hit::{Hittable, HitRecord};
+use super::mat4::Mat4;
+use super::aabb::AABB;
+
+/// places an object with an affine transform: any mix of translation, rotation about any axis,
+/// scale, shear and look-at
+pub struct Transform<H: Hittable> {
+    hittable: H,
+    transform: Mat4,
+    inverse: Mat4,
+    // transforms normals, the transpose of the inverse
+    normal_matrix: Mat4,
+    // determinant of the linear part, how much volumes grow
+    determinant: f64
+}
+
+impl<H: Hittable> Transform<H> {
+    /// `transform` maps object space to world space and must be invertible
+    pub fn new(hittable: H, transform: Mat4) -> Transform<H> {
+        let inverse = transform.inverse().expect("transform is not invertible");
+        Transform {
+            hittable,
+            transform,
+            inverse,
+            normal_matrix: inverse.transpose(),
+            determinant: transform.determinant3()
+        }
+    }
+
+    /// applies `transform` after the current one
+    pub fn then(self, transform: Mat4) -> Transform<H> {
+        Transform::new(self.hittable, transform * self.transform)
+    }
+
+    pub fn translate(self, offset: Vec3) -> Transform<H> {
+        self.then(Mat4::translation(offset))
+    }
+
+    /// rotation around an axis through the origin, the angle is in degrees
+    pub fn rotate(self, axis: Vec3, angle: f64) -> Transform<H> {
+        self.then(Mat4::rotation(axis, angle))
+    }
+
+    pub fn scale(self, factors: Vec3) -> Transform<H> {
+        self.then(Mat4::scaling(factors))
+    }
+
+    /// moves the object to `from` with its +z axis pointing at `target`
+    pub fn look_at(self, from: Point3, target: Point3, up: Vec3) -> Transform<H> {
+        self.then(Mat4::look_at(from, target, up))
+    }
+
+    pub fn matrix(&self) -> &Mat4 {
+        &self.transform
+    }
+}
+
+impl<H: Hittable> Hittable for Transform<H> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rng: &mut Sampler) -> Option<HitRecord<'_>> {
+        // the direction is not normalized, so t is the same in both spaces
+        let object_ray = Ray::new(self.inverse.transform_point(r.origin()), self.inverse.transform_vector(r.direction()), r.time());
+        let mut rec = self.hittable.hit(&object_ray, t_min, t_max, rng)?;
+
+        rec.position = self.transform.transform_point(rec.position);
+        // the normal still faces against the ray after the transform, so front_face is kept
+        rec.normal = self.normal_matrix.transform_vector(rec.normal).normalized();
+        rec.tangent = self.transform.transform_vector(rec.tangent);
+        Some(rec)
+    }
+
+    fn bounding_box(&self, t0: f64, t1: f64) -> Option<AABB> {
+        self.hittable.bounding_box(t0, t1).map(|b| self.transform.transform_box(&b))
+    }
+
+    fn pdf_value(&self, o: Point3, v: Vec3, rng: &mut Sampler) -> f64 {
+        let w = self.inverse.transform_vector(v).normalized();
+        let pdf = self.hittable.pdf_value(self.inverse.transform_point(o), w, rng);
+        // the linear part maps the solid angle around w onto the one around v,
+        // stretching it by |det| / |A w|^3
+        pdf * self.transform.transform_vector(w).length().powi(3) / self.determinant.abs()
+    }
+
+    fn random(&self, o: Vec3, rng: &mut Sampler) -> Vec3 {
+        self.transform.transform_vector(self.hittable.random(self.inverse.transform_point(o), rng))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rect::{Plane, AARect};
+    use crate::sphere::Sphere;
+    use crate::test_util::{grey, hit, Grey};
+    use super::*;
+
+    fn unit_sphere() -> Sphere<Grey> {
+        Sphere::new(Point3::new(0.0, 0.0, 0.0), 1.0, grey())
+    }
+
+    #[test]
+    fn scaled_sphere_is_an_ellipsoid() {
+        // x^2 / 4 + y^2 + z^2 = 1
+        let ellipsoid = Transform::new(unit_sphere(), Mat4::identity()).scale(Vec3::new(2.0, 1.0, 1.0));
+        let rec = hit(&ellipsoid, Point3::new(-5.0, 0.5, 0.0), Vec3::new(1.0, 0.0, 0.0)).unwrap();
+        let x = -2.0 * 0.75f64.sqrt();
+        assert!((rec.t - (5.0 + x)).abs() < 1e-9);
+        assert!((rec.position - Point3::new(x, 0.5, 0.0)).length() < 1e-9);
+        // the gradient of the implicit function
+        let expected = Vec3::new(x / 4.0, 0.5, 0.0).normalized();
+        assert!((rec.normal - expected).length() < 1e-9, "normal {}", rec.normal);
+    }
+
+    #[test]
+    fn sheared_sphere() {
+        // x moves by half of y, a row at height y starts at -sqrt(1 - y^2) + y / 2
+        let shear = Mat4::new([
+            [1.0, 0.5, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0]
+        ]);
+        let sheared = Transform::new(unit_sphere(), shear);
+        for y in [-0.8, 0.0, 0.3, 0.9] {
+            let rec = hit(&sheared, Point3::new(-5.0, y, 0.0), Vec3::new(1.0, 0.0, 0.0)).unwrap();
+            let x = -(1.0 - y * y).sqrt() + 0.5 * y;
+            assert!((rec.position.x() - x).abs() < 1e-9, "at height {}", y);
+        }
+        assert!(hit(&sheared, Point3::new(-5.0, 1.01, 0.0), Vec3::new(1.0, 0.0, 0.0)).is_none());
+    }
+
+    #[test]
+    fn normals_stay_perpendicular_under_non_uniform_scale() {
+        let squashed = Transform::new(unit_sphere(), Mat4::identity())
+            .scale(Vec3::new(3.0, 0.5, 1.5))
+            .rotate(Vec3::new(1.0, 1.0, 0.0), 30.0);
+        let origin = Point3::new(0.2, 8.0, -0.3);
+        for (dx, dz) in [(0.0, 0.0), (0.3, 0.2), (-0.7, 0.4), (0.9, -0.6)] {
+            let rec = hit(&squashed, origin, Point3::new(dx, 0.0, dz) - origin).unwrap();
+            // the tangent lies in the surface
+            assert!(rec.normal.dot(rec.tangent.normalized()).abs() < 1e-9);
+            assert!((rec.normal.length() - 1.0).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn box_holds_the_transformed_object() {
+        let object = Transform::new(unit_sphere(), Mat4::identity())
+            .scale(Vec3::new(2.0, 0.5, 1.0))
+            .rotate(Vec3::new(0.0, 0.0, 1.0), 90.0)
+            .translate(Vec3::new(1.0, 0.0, 0.0));
+        let bbox = object.bounding_box(0.0, 1.0).unwrap();
+        // the long axis now points along y
+        assert!((bbox.min - Vec3::new(0.5, -2.0, -1.0)).length() < 1e-9);
+        assert!((bbox.max - Vec3::new(1.5, 2.0, 1.0)).length() < 1e-9);
+    }
+
+    #[test]
+    fn pdf_of_a_transformed_light() {
+        // the same rectangle given in world space and as a scaled, turned and moved unit square
+        let m = Mat4::translation(Vec3::new(0.5, 3.0, -1.0)) * Mat4::rotation(Vec3::new(0.0, 1.0, 0.0), 90.0) * Mat4::scaling(Vec3::new(2.0, 1.0, 3.0));
+        let transformed = Transform::new(AARect::new(Plane::XZ, 0.0, 1.0, 0.0, 1.0, 0.0, grey()), m);
+        let (a, b) = (m.transform_point(Point3::new(0.0, 0.0, 0.0)), m.transform_point(Point3::new(1.0, 0.0, 1.0)));
+        let world = AARect::new(Plane::XZ, a.x().min(b.x()), a.x().max(b.x()), a.z().min(b.z()), a.z().max(b.z()), a.y(), grey());
+
+        let mut rng = Sampler::new(0);
+        let o = Point3::new(0.1, -1.0, 0.2);
+        for _ in 0..100 {
+            let direction = transformed.random(o, &mut rng);
+            let (a, b) = (transformed.pdf_value(o, direction, &mut rng), world.pdf_value(o, direction, &mut rng));
+            assert!(b > 0.0 && (a - b).abs() < 1e-9 * b, "{} instead of {}", a, b);
+        }
+    }
+}
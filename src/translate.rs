@@ -1,8 +1,10 @@
 use super::vec::Vec3;
 use super::ray::Ray;
+use super::sampler::Sampler;
 use super::hit::{Hittable, HitRecord};
 use super::aabb::AABB;
 
+/// moves an object by an offset
 pub struct Translate<H:Hittable>{
     hittable:H,
     offset:Vec3
@@ -18,9 +20,9 @@ impl<H:Hittable> Translate<H>{
 }
 
 impl<H: Hittable> Hittable for Translate<H> {
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rng: &mut Sampler) -> Option<HitRecord> {
         let translated_ray = Ray::new(r.origin() - self.offset, r.direction(), r.time());
-        self.hittable.hit(&translated_ray, t_min, t_max).map(
+        self.hittable.hit(&translated_ray, t_min, t_max, rng).map(
             |mut hit| {
                 hit.position += self.offset;
                 hit
@@ -0,0 +1,478 @@
+use std::f64;
+use std::sync::Arc;
+use rand::Rng;
+use super::vec::{Vec3, Point3};
+use super::ray::Ray;
+use super::hit::{Hittable, HitRecord};
+use super::mat::Material;
+use super::aabb::AABB;
+use super::bvh::{BVH, BVHStats, SplitMethod};
+use super::sampler::Sampler;
+
+fn max_dimension(v: Vec3) -> usize {
+    if v.x() > v.y() {
+        if v.x() > v.z() { 0 } else { 2 }
+    } else if v.y() > v.z() {
+        1
+    } else {
+        2
+    }
+}
+
+/// watertight ray/triangle intersection (Woop, Benthin and Wald 2013)
+/// returns the ray parameter and the barycentric weights of p0, p1 and p2
+fn intersect(r: &Ray, p: &[Point3; 3], t_min: f64, t_max: f64) -> Option<(f64, [f64; 3])> {
+    let d = r.direction();
+
+    // permute the axes so that the dominant direction component becomes z,
+    // swapping x and y when it is negative to keep the winding order
+    let kz = max_dimension(Vec3::new(d.x().abs(), d.y().abs(), d.z().abs()));
+    let mut kx = (kz + 1) % 3;
+    let mut ky = (kx + 1) % 3;
+    if d[kz] < 0.0 {
+        std::mem::swap(&mut kx, &mut ky);
+    }
+
+    // shear constants
+    let sx = -d[kx] / d[kz];
+    let sy = -d[ky] / d[kz];
+    let sz = 1.0 / d[kz];
+
+    // vertices relative to the ray origin, sheared into ray space
+    let a = p[0] - r.origin();
+    let b = p[1] - r.origin();
+    let c = p[2] - r.origin();
+    let ax = a[kx] + sx * a[kz];
+    let ay = a[ky] + sy * a[kz];
+    let bx = b[kx] + sx * b[kz];
+    let by = b[ky] + sy * b[kz];
+    let cx = c[kx] + sx * c[kz];
+    let cy = c[ky] + sy * c[kz];
+
+    // scaled barycentric coordinates, an edge hit counts for both neighbours
+    let e0 = cx * by - cy * bx;
+    let e1 = ax * cy - ay * cx;
+    let e2 = bx * ay - by * ax;
+    if (e0 < 0.0 || e1 < 0.0 || e2 < 0.0) && (e0 > 0.0 || e1 > 0.0 || e2 > 0.0) {
+        return None
+    }
+
+    let det = e0 + e1 + e2;
+    if det == 0.0 {
+        return None
+    }
+
+    let t_scaled = e0 * sz * a[kz] + e1 * sz * b[kz] + e2 * sz * c[kz];
+    let t = t_scaled / det;
+    if t < t_min || t > t_max {
+        return None
+    }
+
+    Some((t, [e0 / det, e1 / det, e2 / det]))
+}
+
+pub(crate) fn hit_triangle<'a>(r: &Ray, t_min: f64, t_max: f64,
+                    p: &[Point3; 3],
+                    n: Option<[Vec3; 3]>,
+                    uv: Option<[(f64, f64); 3]>,
+                    material: &'a dyn Material) -> Option<HitRecord<'a>> {
+    let (t, b) = intersect(r, p, t_min, t_max)?;
+
+    // barycentric coordinates of p1 and p2 unless the triangle is textured
+    let (u, v) = match uv {
+        Some(uv) => (
+            b[0] * uv[0].0 + b[1] * uv[1].0 + b[2] * uv[2].0,
+            b[0] * uv[0].1 + b[1] * uv[1].1 + b[2] * uv[2].1
+        ),
+        None => (b[1], b[2])
+    };
+
+    let mut rec = HitRecord {
+        position: b[0] * p[0] + b[1] * p[1] + b[2] * p[2],
+        normal: Vec3::new(0.0, 0.0, 0.0),
+        t,
+        u,
+        v,
+        tangent: Vec3::new(0.0, 0.0, 0.0),
+        front_face: false,
+        material
+    };
+
+    let outward_normal = (p[1] - p[0]).cross(p[2] - p[0]).normalized();
+    rec.set_face_normal(r, outward_normal);
+    rec.tangent = match uv {
+        Some(uv) => texture_tangent(p, &uv),
+        None => p[1] - p[0]
+    };
+
+    if let Some(n) = n {
+        // keep the interpolated shading normal on the same side as the geometric one
+        let shading_normal = (b[0] * n[0] + b[1] * n[1] + b[2] * n[2]).normalized();
+        rec.normal = if shading_normal.dot(rec.normal) < 0.0 {
+            (-1.0) * shading_normal
+        } else {
+            shading_normal
+        };
+    }
+
+    Some(rec)
+}
+
+// dp/du from the texture coordinates, along an edge when they do not vary across the triangle
+fn texture_tangent(p: &[Point3; 3], uv: &[(f64, f64); 3]) -> Vec3 {
+    let (e1, e2) = (p[1] - p[0], p[2] - p[0]);
+    let (du1, dv1, du2, dv2) = (uv[1].0 - uv[0].0, uv[1].1 - uv[0].1, uv[2].0 - uv[0].0, uv[2].1 - uv[0].1);
+    let det = du1 * dv2 - du2 * dv1;
+    if det.abs() < 1.0e-12 {
+        return e1
+    }
+    (dv2 * e1 - dv1 * e2) / det
+}
+
+fn triangle_box(p: &[Point3; 3]) -> AABB {
+    let mut min = p[0];
+    let mut max = p[0];
+    for v in &p[1..] {
+        for a in 0..3 {
+            min[a] = min[a].min(v[a]);
+            max[a] = max[a].max(v[a]);
+        }
+    }
+
+    // the bounding box must have non-zero width in each dimension, so pad flat triangles a small amount.
+    for a in 0..3 {
+        if max[a] - min[a] < 0.0001 {
+            min[a] -= 0.0001;
+            max[a] += 0.0001;
+        }
+    }
+
+    AABB::new(min, max)
+}
+
+#[derive(Clone)]
+pub struct Triangle<M: Material> {
+    vertices: [Point3; 3],
+    normals: Option<[Vec3; 3]>,
+    uvs: Option<[(f64, f64); 3]>,
+    material: M
+}
+
+impl<M: Material> Triangle<M> {
+    pub fn new(p0: Point3, p1: Point3, p2: Point3, material: M) -> Triangle<M> {
+        Triangle {
+            vertices: [p0, p1, p2],
+            normals: None,
+            uvs: None,
+            material
+        }
+    }
+
+    pub fn with_normals(mut self, normals: [Vec3; 3]) -> Triangle<M> {
+        self.normals = Some(normals);
+        self
+    }
+
+    pub fn with_uvs(mut self, uvs: [(f64, f64); 3]) -> Triangle<M> {
+        self.uvs = Some(uvs);
+        self
+    }
+}
+
+impl<M: Material> Hittable for Triangle<M> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, _rng: &mut Sampler) -> Option<HitRecord<'_>> {
+        hit_triangle(r, t_min, t_max, &self.vertices, self.normals, self.uvs, &self.material)
+    }
+
+    fn bounding_box(&self, _t0: f64, _t1: f64) -> Option<AABB> {
+        Some(triangle_box(&self.vertices))
+    }
+
+    fn pdf_value(&self, o: Point3, v: Vec3, rng: &mut Sampler) -> f64 {
+        if let Some(rec) = self.hit(&Ray::new(o, v, 0.0), 0.001, f64::INFINITY, rng) {
+            // integration by substitution
+            let distance_squared = rec.t.powi(2) * v.length().powi(2);
+            let cosine = v.dot(rec.normal).abs() / v.length();
+            if cosine != 0.0 { distance_squared / (cosine * area(&self.vertices)) } else { 0.0 }
+        } else {
+            0.0
+        }
+    }
+
+    fn random(&self, o: Vec3, rng: &mut Sampler) -> Vec3 {
+        random_point(&self.vertices, rng) - o
+    }
+}
+
+// uniform sampling over the triangle area
+fn random_point(p: &[Point3; 3], rng: &mut Sampler) -> Point3 {
+    let r1 = rng.gen::<f64>().sqrt();
+    let r2 = rng.gen::<f64>();
+    (1.0 - r1) * p[0] + (r1 * (1.0 - r2)) * p[1] + (r1 * r2) * p[2]
+}
+
+fn area(p: &[Point3; 3]) -> f64 {
+    0.5 * (p[1] - p[0]).cross(p[2] - p[0]).length()
+}
+
+struct MeshData<M: Material> {
+    positions: Vec<Point3>,
+    normals: Option<Vec<Vec3>>,
+    uvs: Option<Vec<(f64, f64)>>,
+    indices: Vec<[usize; 3]>,
+    material: M
+}
+
+// the corners of the faces of a mesh, whatever its material
+trait Faces: Send + Sync {
+    fn vertices(&self, face: usize) -> [Point3; 3];
+}
+
+impl<M: Material> Faces for MeshData<M> {
+    fn vertices(&self, face: usize) -> [Point3; 3] {
+        let [i0, i1, i2] = self.indices[face];
+        [self.positions[i0], self.positions[i1], self.positions[i2]]
+    }
+}
+
+// a triangle referencing the vertices shared by the whole mesh
+struct MeshTriangle<M: Material> {
+    mesh: Arc<MeshData<M>>,
+    face: usize
+}
+
+impl<M: Material> MeshTriangle<M> {
+    fn vertices(&self) -> [Point3; 3] {
+        self.mesh.vertices(self.face)
+    }
+}
+
+impl<M: Material> Hittable for MeshTriangle<M> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, _rng: &mut Sampler) -> Option<HitRecord<'_>> {
+        let [i0, i1, i2] = self.mesh.indices[self.face];
+        let normals = self.mesh.normals.as_ref().map(|n| [n[i0], n[i1], n[i2]]);
+        let uvs = self.mesh.uvs.as_ref().map(|uv| [uv[i0], uv[i1], uv[i2]]);
+        hit_triangle(r, t_min, t_max, &self.vertices(), normals, uvs, &self.mesh.material)
+    }
+
+    fn bounding_box(&self, _t0: f64, _t1: f64) -> Option<AABB> {
+        Some(triangle_box(&self.vertices()))
+    }
+}
+
+/// indexed triangle mesh, every face shares the vertex arrays and the material of the mesh
+///
+/// as a light it is sampled uniformly over its whole area
+pub struct TriangleMesh {
+    bvh: BVH,
+    faces: Arc<dyn Faces>,
+    // the area of the faces up to and including each one, to pick a face by area
+    cumulative_area: Vec<f64>
+}
+
+impl TriangleMesh {
+    /// the triangles go into a bvh split with the surface area heuristic
+    pub fn new<M: Material + 'static>(positions: Vec<Point3>,
+                                     indices: Vec<[usize; 3]>,
+                                     normals: Option<Vec<Vec3>>,
+                                     uvs: Option<Vec<(f64, f64)>>,
+                                     material: M) -> TriangleMesh {
+        TriangleMesh::with_split(positions, indices, normals, uvs, material, SplitMethod::Sah)
+    }
+
+    pub fn with_split<M: Material + 'static>(positions: Vec<Point3>,
+                                            indices: Vec<[usize; 3]>,
+                                            normals: Option<Vec<Vec3>>,
+                                            uvs: Option<Vec<(f64, f64)>>,
+                                            material: M,
+                                            split: SplitMethod) -> TriangleMesh {
+        if let Some(normals) = &normals {
+            assert_eq!(normals.len(), positions.len(), "mesh needs one normal per vertex");
+        }
+        if let Some(uvs) = &uvs {
+            assert_eq!(uvs.len(), positions.len(), "mesh needs one uv per vertex");
+        }
+        assert!(!indices.is_empty(), "a mesh needs at least one face");
+        assert!(indices.iter().flatten().all(|&i| i < positions.len()), "mesh index out of range");
+
+        let faces = indices.len();
+        let mesh = Arc::new(MeshData {
+            positions,
+            normals,
+            uvs,
+            indices,
+            material
+        });
+
+        let triangles: Vec<Box<dyn Hittable>> = (0..faces).map(|face| {
+            Box::new(MeshTriangle { mesh: mesh.clone(), face }) as Box<dyn Hittable>
+        }).collect();
+        let cumulative_area = (0..faces).scan(0.0, |total, face| {
+            *total += area(&mesh.vertices(face));
+            Some(*total)
+        }).collect();
+
+        TriangleMesh {
+            bvh: BVH::with_split(triangles, 0.0, 1.0, split),
+            faces: mesh,
+            cumulative_area
+        }
+    }
+
+    fn area(&self) -> f64 {
+        self.cumulative_area[self.cumulative_area.len() - 1]
+    }
+
+    pub fn stats(&self) -> BVHStats {
+        self.bvh.stats()
+    }
+}
+
+impl Hittable for TriangleMesh {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rng: &mut Sampler) -> Option<HitRecord<'_>> {
+        self.bvh.hit(r, t_min, t_max, rng)
+    }
+
+    fn bounding_box(&self, t0: f64, t1: f64) -> Option<AABB> {
+        self.bvh.bounding_box(t0, t1)
+    }
+
+    fn pdf_value(&self, o: Point3, v: Vec3, rng: &mut Sampler) -> f64 {
+        // the direction can reach several faces, each adds the density of sampling its point
+        let r = Ray::new(o, v, 0.0);
+        let mut pdf = 0.0;
+        let mut t_min = 0.001;
+        while let Some(rec) = self.bvh.hit(&r, t_min, f64::INFINITY, rng) {
+            let distance_squared = rec.t.powi(2) * v.length().powi(2);
+            let cosine = v.dot(rec.normal).abs() / v.length();
+            if cosine != 0.0 {
+                pdf += distance_squared / (cosine * self.area());
+            }
+            t_min = rec.t + 1.0e-9 * rec.t.abs().max(1.0);
+        }
+        pdf
+    }
+
+    fn random(&self, o: Vec3, rng: &mut Sampler) -> Vec3 {
+        let target = rng.gen::<f64>() * self.area();
+        let face = self.cumulative_area.partition_point(|&area| area <= target).min(self.cumulative_area.len() - 1);
+        random_point(&self.faces.vertices(face), rng) - o
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hit::HittableList;
+    use crate::rect::{Plane, AARect};
+    use crate::test_util::{grey, hit};
+    use super::*;
+
+    // two triangles of a skewed quad sharing the edge from p[0] to p[2]
+    fn quad() -> ([Point3; 3], [Point3; 3]) {
+        let p = [Point3::new(0.1, -0.3, 0.2), Point3::new(1.7, 0.1, -0.4), Point3::new(1.3, 1.9, 0.3), Point3::new(-0.2, 1.1, 0.7)];
+        ([p[0], p[1], p[2]], [p[0], p[2], p[3]])
+    }
+
+    #[test]
+    fn shared_edge_has_no_cracks() {
+        // the ends of the edge are vertices, which only a surrounding fan is sure to cover
+        let (a, b) = quad();
+        let directions = [Vec3::new(0.0, 0.0, -1.0), Vec3::new(0.31, -0.17, -1.0), Vec3::new(-0.7, 0.4, -0.3), Vec3::new(0.2, 0.9, -0.1)];
+        for d in directions {
+            for k in 1..1000 {
+                let s = k as f64 / 1000.0;
+                let target = a[0] + s * (a[2] - a[0]);
+                let r = Ray::new(target - 3.0 * d, d, 0.0);
+                assert!(intersect(&r, &a, 0.0, f64::INFINITY).is_some() || intersect(&r, &b, 0.0, f64::INFINITY).is_some(),
+                        "ray through {} in direction {} passes between the triangles", target, d);
+            }
+        }
+    }
+
+    #[test]
+    fn vertex_shared_by_a_fan_is_hit() {
+        // a low pyramid of four triangles around an apex
+        let apex = Point3::new(0.1, 0.2, 0.3);
+        let ring = [Point3::new(-1.0, -1.1, 0.0), Point3::new(1.2, -0.9, 0.1), Point3::new(0.9, 1.3, -0.1), Point3::new(-1.1, 0.8, 0.0)];
+        let fan: Vec<[Point3; 3]> = (0..4).map(|i| [apex, ring[i], ring[(i + 1) % 4]]).collect();
+        for d in [Vec3::new(0.0, 0.0, -1.0), Vec3::new(0.13, 0.29, -1.0), Vec3::new(-0.4, 0.3, -0.5), Vec3::new(0.7, -0.6, -0.9)] {
+            let r = Ray::new(apex - 2.0 * d, d, 0.0);
+            assert!(fan.iter().any(|p| intersect(&r, p, 0.0, f64::INFINITY).is_some()), "ray in direction {} misses the apex", d);
+        }
+    }
+
+    #[test]
+    fn barycentric_uv() {
+        let triangle = Triangle::new(Point3::new(0.0, 0.0, 0.0), Point3::new(2.0, 0.0, 0.0), Point3::new(0.0, 2.0, 0.0), grey());
+        let (origin, down) = (Point3::new(0.5, 1.0, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        let rec = hit(&triangle, origin, down).unwrap();
+        assert!((rec.t - 1.0).abs() < 1e-12);
+        assert!((rec.u - 0.25).abs() < 1e-12 && (rec.v - 0.5).abs() < 1e-12);
+
+        // texture coordinates are interpolated with the same weights
+        let triangle = triangle.with_uvs([(0.2, 0.2), (1.0, 0.2), (0.2, 0.6)]);
+        let rec = hit(&triangle, origin, down).unwrap();
+        assert!((rec.u - 0.4).abs() < 1e-12 && (rec.v - 0.4).abs() < 1e-12);
+    }
+
+    #[test]
+    fn misses_outside_and_behind() {
+        let triangle = Triangle::new(Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 0.0, 0.0), Point3::new(0.0, 1.0, 0.0), grey());
+        assert!(hit(&triangle, Point3::new(0.6, 0.6, 1.0), Vec3::new(0.0, 0.0, -1.0)).is_none());
+        // the triangle is behind the origin
+        assert!(hit(&triangle, Point3::new(0.2, 0.2, 1.0), Vec3::new(0.0, 0.0, 1.0)).is_none());
+    }
+
+    #[test]
+    fn mesh_is_sampled_by_area() {
+        // the rectangle [0, 4] x [0, 1] cut into faces of area 2, 1.5 and 0.5
+        let positions = vec![Point3::new(0.0, 0.0, 0.0), Point3::new(4.0, 0.0, 0.0), Point3::new(4.0, 1.0, 0.0),
+                             Point3::new(0.0, 1.0, 0.0), Point3::new(1.0, 1.0, 0.0)];
+        let mesh = TriangleMesh::new(positions, vec![[0, 1, 2], [0, 2, 4], [0, 4, 3]], None, None, grey());
+        let rectangle = AARect::new(Plane::XY, 0.0, 4.0, 0.0, 1.0, 0.0, grey());
+
+        let o = Point3::new(1.0, 0.3, 2.0);
+        let mut rng = Sampler::new(0);
+        for target in [Point3::new(0.5, 0.5, 0.0), Point3::new(3.5, 0.2, 0.0), Point3::new(1.0, 0.9, 0.0)] {
+            let (a, b) = (mesh.pdf_value(o, target - o, &mut rng), rectangle.pdf_value(o, target - o, &mut rng));
+            assert!((a - b).abs() < 1e-9 * b, "mesh pdf {} but rectangle pdf {}", a, b);
+        }
+        assert_eq!(mesh.pdf_value(o, Vec3::new(0.0, 0.0, 1.0), &mut rng), 0.0);
+
+        // uniform over the rectangle, whatever the size of the faces
+        let n = 100_000;
+        let mut mean = Vec3::new(0.0, 0.0, 0.0);
+        for _ in 0..n {
+            let p = o + mesh.random(o, &mut rng);
+            assert!(p.z().abs() < 1e-12 && (0.0..=4.0).contains(&p.x()) && (0.0..=1.0).contains(&p.y()));
+            mean += p / n as f64;
+        }
+        assert!((mean.x() - 2.0).abs() < 0.02 && (mean.y() - 0.5).abs() < 0.01, "mean sample {}", mean);
+    }
+
+    #[test]
+    fn mesh_pdf_counts_every_layer() {
+        // two stacked unit squares, a direction through both may have come from either
+        let positions = vec![Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 0.0, 0.0), Point3::new(1.0, 1.0, 0.0), Point3::new(0.0, 1.0, 0.0),
+                             Point3::new(0.0, 0.0, 1.0), Point3::new(1.0, 0.0, 1.0), Point3::new(1.0, 1.0, 1.0), Point3::new(0.0, 1.0, 1.0)];
+        let indices = vec![[0, 1, 2], [0, 2, 3], [4, 5, 6], [4, 6, 7]];
+        let mesh = TriangleMesh::new(positions, indices, None, None, grey());
+        let mut layers = HittableList::default();
+        for z in [0.0, 1.0] {
+            layers.push(AARect::new(Plane::XY, 0.0, 1.0, 0.0, 1.0, z, grey()));
+        }
+
+        let o = Point3::new(0.3, 0.4, 3.0);
+        let mut rng = Sampler::new(0);
+        for v in [Vec3::new(0.0, 0.0, -1.0), Vec3::new(0.05, 0.1, -1.0)] {
+            let (a, b) = (mesh.pdf_value(o, v, &mut rng), layers.pdf_value(o, v, &mut rng));
+            assert!((a - b).abs() < 1e-9 * b, "mesh pdf {} but layers pdf {}", a, b);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "at least one face")]
+    fn empty_mesh_is_rejected() {
+        TriangleMesh::new(vec![Point3::new(0.0, 0.0, 0.0)], Vec::new(), None, None, grey());
+    }
+}
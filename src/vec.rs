@@ -6,6 +6,7 @@ use rand::Rng;
 
 // variables
 
+/// 3d vector, also used for points and rgb colors
 #[derive(Default, Clone, Copy)]
 pub struct Vec3 {
     e: [f64; 3]
@@ -57,25 +58,23 @@ impl Vec3 {
         self / self.length()
     }
 
-    pub fn random(rand: Range<f64>) -> Vec3 {
-        let mut rng = rand::thread_rng();
-
+    pub fn random(rand: Range<f64>, rng: &mut impl Rng) -> Vec3 {
         Vec3 {
             e: [rng.gen_range(rand.clone()), rng.gen_range(rand.clone()), rng.gen_range(rand.clone())]
         }
     }
 
-    pub fn random_in_unit_sphere() -> Vec3 {
+    pub fn random_in_unit_sphere(rng: &mut impl Rng) -> Vec3 {
         loop {
-            let v = Vec3::random(-1.0..1.0);
+            let v = Vec3::random(-1.0..1.0, rng);
             if v.length() < 1.0 {
                 return v
             }
         }
     }
 
-    pub fn random_in_hemisphere(normal: Vec3) -> Vec3 {
-        let in_unit_sphere = Self::random_in_unit_sphere();
+    pub fn random_in_hemisphere(normal: Vec3, rng: &mut impl Rng) -> Vec3 {
+        let in_unit_sphere = Self::random_in_unit_sphere(rng);
         if in_unit_sphere.dot(normal) > 0.0 {
             in_unit_sphere
         } else {
@@ -83,9 +82,7 @@ impl Vec3 {
         }
     }
 
-    pub fn random_in_unit_disk() -> Vec3{
-        let mut rng = rand::thread_rng();
-
+    pub fn random_in_unit_disk(rng: &mut impl Rng) -> Vec3{
         loop{
             let p = Vec3::new(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0), 0.0);
             if p.length()<1.0{